@@ -0,0 +1,142 @@
+//! One audit record per dialog decision
+//!
+//! Written to stderr as a single line of `key=value` pairs, strings quoted
+//! with Rust escaping so the line never splits:
+//!
+//! ```text
+//! session-dialog: audit ts=1760000000 kind=PrivilegeEscalation title="Authorization Required" detail="/usr/bin/pacman -Syu" result=Confirmed code=0 elapsed_ms=2140 source=User request_id="42"
+//! ```
+//!
+//! `detail` has the config's `redact` rules applied, and `request_id` is
+//! omitted when the config has none. Title and detail are cut to
+//! `MAX_LOGGED_LEN` bytes, since the daemon may not read stderr until the
+//! dialog exits. With the `syslog` feature the same record (without the
+//! `session-dialog: ` prefix) also goes to syslog at
+//! `LOG_AUTHPRIV | LOG_NOTICE`.
+
+use crate::{DecisionSource, DialogConfig, DialogKind, DialogResult};
+use std::fmt::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Longest title or detail written to a log line, in bytes
+pub(crate) const MAX_LOGGED_LEN: usize = 4096;
+
+/// Log the decision on `config` to stderr (and syslog, if enabled)
+pub(crate) fn record(
+    config: &DialogConfig,
+    result: DialogResult,
+    code: i32,
+    elapsed: Duration,
+    source: DecisionSource,
+) {
+    let line = format_record(config, result, code, elapsed, source);
+    eprintln!("session-dialog: {}", line);
+    #[cfg(feature = "syslog")]
+    syslog(&line);
+}
+
+fn format_record(
+    config: &DialogConfig,
+    result: DialogResult,
+    code: i32,
+    elapsed: Duration,
+    source: DecisionSource,
+) -> String {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let mut line = format!(
+        "audit ts={} kind={} title={:?} detail={:?} result={:?} code={} elapsed_ms={} source={:?}",
+        ts,
+        kind_name(&config.kind),
+        clip(config.title()),
+        clip(&config.log_detail()),
+        result,
+        code,
+        elapsed.as_millis(),
+        source
+    );
+    if let Some(id) = &config.request_id {
+        let _ = write!(line, " request_id={:?}", id);
+    }
+    line
+}
+
+/// `text` cut to `MAX_LOGGED_LEN` bytes (at a character boundary), with
+/// a note of how much was dropped
+pub(crate) fn clip(text: &str) -> String {
+    if text.len() <= MAX_LOGGED_LEN {
+        return text.to_string();
+    }
+    let mut end = MAX_LOGGED_LEN;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}… [truncated, {} bytes]", &text[..end], text.len())
+}
+
+fn kind_name(kind: &DialogKind) -> &'static str {
+    match kind {
+        DialogKind::PrivilegeEscalation { .. } => "PrivilegeEscalation",
+        DialogKind::NetworkConnection { .. } => "NetworkConnection",
+        DialogKind::Generic { .. } => "Generic",
+        DialogKind::DeviceAccess { .. } => "DeviceAccess",
+    }
+}
+
+#[cfg(feature = "syslog")]
+fn syslog(line: &str) {
+    // Debug quoting already escaped any NUL in the strings
+    let Ok(message) = std::ffi::CString::new(line) else {
+        return;
+    };
+    // SAFETY: both pointers are valid NUL-terminated strings and the format
+    // consumes exactly one string argument
+    unsafe {
+        libc::syslog(
+            libc::LOG_AUTHPRIV | libc::LOG_NOTICE,
+            c"%s".as_ptr(),
+            message.as_ptr(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_keeps_short_text() {
+        assert_eq!(clip("pacman -Syu"), "pacman -Syu");
+        let exact = "a".repeat(MAX_LOGGED_LEN);
+        assert_eq!(clip(&exact), exact);
+    }
+
+    #[test]
+    fn clip_cuts_long_text_at_char_boundary() {
+        // 'é' is two bytes, so MAX_LOGGED_LEN falls inside one after "a"
+        let text = format!("a{}", "é".repeat(MAX_LOGGED_LEN));
+        let clipped = clip(&text);
+        let (kept, note) = clipped.split_once('…').unwrap();
+        assert_eq!(kept.len(), MAX_LOGGED_LEN - 1);
+        assert_eq!(note, format!(" [truncated, {} bytes]", text.len()));
+    }
+
+    #[test]
+    fn record_redacts_detail_but_screen_does_not() {
+        let mut config = DialogConfig::new(DialogKind::PrivilegeEscalation {
+            command: "mysql --password=hunter2".to_string(),
+        });
+        config.redact = vec![crate::RedactRule::new(r"--password=\S+")];
+        let line = format_record(
+            &config,
+            DialogResult::Confirmed,
+            0,
+            Duration::from_millis(5),
+            DecisionSource::User,
+        );
+        assert!(line.contains(r#"detail="mysql [REDACTED]""#), "{}", line);
+        assert!(!line.contains("hunter2"), "{}", line);
+        assert_eq!(config.detail(), "mysql --password=hunter2");
+    }
+}
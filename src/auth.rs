@@ -0,0 +1,211 @@
+//! Dialogs that hand a typed secret back to the daemon
+//!
+//! # Security model
+//!
+//! The secret never travels through argv, the environment, the exit code or
+//! the result socket, all of which other processes or logs can observe.
+//! Instead the daemon creates a pipe, the dialog binary inherits only its
+//! write end as fd `SECRET_FD`, and the secret is written there once, after
+//! the user confirms, before the dialog exits. The read end is close-on-exec
+//! and never leaves the daemon.
+//!
+//! Both ends hold the secret in `Zeroizing` buffers, so it is wiped from
+//! memory when dropped; callers should keep it in the returned `Zeroizing`
+//! wrapper rather than copying it out. The daemon reads it into a single
+//! buffer of `MAX_SECRET_LEN` bytes allocated up front, since growing a
+//! buffer frees the old copy unwiped; longer secrets are refused. A
+//! denied, timed-out or failed dialog yields no secret at all.
+//!
+//! A secret is only captured by dialogs with a secret-entry field
+//! (`DialogConfig::secret_prompt`); for any other dialog
+//! `AuthOutcome::secret` is None. The field is masked, and the dialog can't
+//! be confirmed while it is empty. The dialog's own copies (its state and
+//! the edit messages) are `Zeroizing` too, but iced keeps short-lived
+//! copies for drawing the field that are freed without being wiped.
+
+use crate::{detect_compositor, drain_stderr, result_from_output, spawn_dialog, Inherit};
+use crate::{DialogConfig, DialogError, DialogResult, SpawnOptions};
+use std::collections::HashMap;
+use std::io::Read;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use zeroize::Zeroizing;
+
+/// Descriptor the dialog binary writes the secret to
+pub const SECRET_FD: i32 = 3;
+
+/// Longest secret accepted from the dialog, in bytes
+pub const MAX_SECRET_LEN: usize = 4096;
+
+/// Result of an authentication dialog
+#[derive(Debug)]
+pub struct AuthOutcome {
+    /// What the user (or timeout) decided
+    pub result: DialogResult,
+    /// The entered secret, only when the user confirmed
+    pub secret: Option<Zeroizing<String>>,
+}
+
+/// Show a dialog and read back the secret the user entered
+///
+/// Arguments are as for `show_dialog`. See the module docs for how the
+/// secret is transferred.
+pub fn show_auth_dialog(
+    config: &DialogConfig,
+    uid: u32,
+    gid: u32,
+    env: &HashMap<String, String>,
+) -> Result<AuthOutcome, DialogError> {
+    let (read_end, write_end) = cloexec_pipe()?;
+
+    let mut config = config.clone();
+    config.secret_fd = Some(SECRET_FD);
+    let mut child = spawn_dialog(
+        &config,
+        uid,
+        gid,
+        env,
+        &SpawnOptions::default(),
+        Inherit::SecretFd(write_end.as_raw_fd()),
+    )?;
+    // Only the child may hold the write end, or we'd never see EOF
+    drop(write_end);
+
+    let stderr = drain_stderr(&mut child);
+    // Closes the read end, so a child writing too much can't block `wait`
+    let read = read_secret(std::fs::File::from(read_end));
+    let status = child.wait()?;
+    let stderr = stderr.join().unwrap_or_default();
+    let result = result_from_output(status, &stderr, detect_compositor(env))?;
+    let bytes = read?;
+
+    let secret = match result {
+        result if result.is_confirmed() && !bytes.is_empty() => {
+            let text = std::str::from_utf8(&bytes).map_err(|_| DialogError::Failed {
+                code: status.code(),
+                stderr: "secret is not valid UTF-8".to_string(),
+                compositor: detect_compositor(env),
+            })?;
+            Some(Zeroizing::new(text.to_string()))
+        }
+        _ => None,
+    };
+    Ok(AuthOutcome { result, secret })
+}
+
+// Everything written to `pipe`, read into one buffer allocated up front:
+// `read_to_end` would grow it, freeing copies of the secret unwiped
+fn read_secret(mut pipe: std::fs::File) -> std::io::Result<Zeroizing<Vec<u8>>> {
+    // One byte past the limit is enough to tell it was exceeded
+    let mut bytes = Zeroizing::new(vec![0; MAX_SECRET_LEN + 1]);
+    let mut len = 0;
+    while len < bytes.len() {
+        match pipe.read(&mut bytes[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if len > MAX_SECRET_LEN {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("secret is longer than {} bytes", MAX_SECRET_LEN),
+        ));
+    }
+    // Shortening keeps the allocation, and the zeroes past `len`
+    bytes.truncate(len);
+    Ok(bytes)
+}
+
+// Close-on-exec pipe as (read end, write end)
+pub(crate) fn cloexec_pipe() -> std::io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for the two descriptors pipe2 writes
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: pipe2 succeeded, so both descriptors are open and ours
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::io::Write;
+
+    const MARKER: &[u8] = b"correct horse battery staple";
+
+    thread_local! {
+        // Set on the testing thread only, so other tests' frees don't count
+        static WATCHING: Cell<bool> = const { Cell::new(false) };
+        static FREED_WITH_MARKER: Cell<bool> = const { Cell::new(false) };
+    }
+
+    /// Notes blocks freed while still holding `MARKER`
+    ///
+    /// Every block is zeroed on allocation, so scanning one reads only
+    /// initialized bytes. Reallocation goes through `alloc` and `dealloc`.
+    struct Watch;
+
+    unsafe impl GlobalAlloc for Watch {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            System.alloc_zeroed(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            if WATCHING.with(Cell::get) {
+                // SAFETY: the block is still allocated and was initialized
+                // by `alloc_zeroed`
+                let block = std::slice::from_raw_parts(ptr, layout.size());
+                if block.windows(MARKER.len()).any(|w| w == MARKER) {
+                    FREED_WITH_MARKER.with(|f| f.set(true));
+                }
+            }
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Watch = Watch;
+
+    /// Whether anything freed during `f` still held `MARKER`
+    fn leaves_marker(f: impl FnOnce()) -> bool {
+        FREED_WITH_MARKER.with(|f| f.set(false));
+        WATCHING.with(|w| w.set(true));
+        f();
+        WATCHING.with(|w| w.set(false));
+        FREED_WITH_MARKER.with(Cell::get)
+    }
+
+    fn pipe_with(contents: &[u8]) -> std::fs::File {
+        let (read_end, write_end) = cloexec_pipe().unwrap();
+        std::fs::File::from(write_end).write_all(contents).unwrap();
+        std::fs::File::from(read_end)
+    }
+
+    #[test]
+    fn secret_is_wiped_after_drop() {
+        // The check itself works: a plain buffer leaves the secret behind
+        assert!(leaves_marker(|| drop(MARKER.to_vec())));
+
+        let pipe = pipe_with(MARKER);
+        let leaked = leaves_marker(|| {
+            let bytes = read_secret(pipe).unwrap();
+            assert_eq!(&bytes[..], MARKER);
+            // As `show_auth_dialog` hands it out
+            let text = std::str::from_utf8(&bytes).unwrap();
+            drop(Zeroizing::new(text.to_string()));
+        });
+        assert!(!leaked);
+    }
+
+    #[test]
+    fn oversized_secrets_are_refused() {
+        let pipe = pipe_with(&[b'x'; MAX_SECRET_LEN + 1]);
+        assert!(read_secret(pipe).is_err());
+        let pipe = pipe_with(&[b'x'; MAX_SECRET_LEN]);
+        assert_eq!(read_secret(pipe).unwrap().len(), MAX_SECRET_LEN);
+    }
+}
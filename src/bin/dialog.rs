@@ -1,22 +1,179 @@
 //! session-dialog binary
 //!
 //! This binary is spawned by daemons (authd, fwd) to show session-lock dialogs.
-//! It receives configuration via --config (base64-encoded msgpack).
+//! It receives configuration via --config-stdin (raw msgpack on stdin, or
+//! JSON with --json), which is what `show_dialog` uses.
+//!
+//! --config (base64-encoded msgpack) and --config-json (raw JSON) are kept
+//! for compatibility but are insecure: argv is world-readable through
+//! `/proc/<pid>/cmdline`, so other local users can see the command or
+//! connection being authorized for as long as the dialog is up.
+//!
+//! Every config is checked with `DialogConfig::validate` before it is
+//! shown, and one that fails exits 3. With --validate the checked config is
+//! printed instead of shown, so payloads can be tested without a
+//! compositor and pass exactly when a real run would accept them.
+//!
+//! `--result-fd <n>` additionally writes the `DialogReport` as JSON to the
+//! inherited descriptor n before exiting, which `show_dialog_report` uses;
+//! the exit code is the same either way.
+//!
+//! `--stream` keeps the session locked for a series of configs instead,
+//! which `open_dialog_stream` uses: each is a 4-byte big-endian length and
+//! that much msgpack on stdin, answered by a `DialogReport` framed the same
+//! way on stdout. It exits 0 once stdin closes.
+//!
+//! `--preview <privilege|network|generic|device> [--timeout <secs>] [--theme <name>]`
+//! shows a built-in sample request instead, for iterating on appearance
+//! without a daemon. It never reads a config.
 
-use session_dialog::{base64_decode, run_dialog, DialogConfig};
+use session_dialog::{
+    base64_decode, run_dialog, run_dialog_stream, run_dialog_with_result_fd, DecodeError,
+    DialogConfig, DialogKind, Protocol, ThemeSelection, LEGACY_TIMEOUT_ENV, MALFORMED_CONFIG_ENV,
+    MAX_CONFIG_LEN,
+};
 use std::env;
+use std::io::Read;
 
 fn main() {
     // Force Wayland backend, skip X11 fallback
     // SAFETY: Called before any threads are spawned
     unsafe { std::env::set_var("WINIT_UNIX_BACKEND", "wayland") };
 
+    let args: Vec<String> = env::args().collect();
+    if let Some(pos) = args.iter().position(|a| a == "--preview") {
+        run_dialog(preview_config(&args, pos));
+    }
+    if args.iter().any(|a| a == "--stream") {
+        run_dialog_stream();
+    }
+
+    let validate_only = args.iter().any(|a| a == "--validate");
+    let result_fd = result_fd(&args);
     let config = parse_args();
-    run_dialog(config);
+    validate(&config);
+    if validate_only {
+        println!("{:#?}", config);
+        std::process::exit(0);
+    }
+    match result_fd {
+        Some(fd) => run_dialog_with_result_fd(config, fd),
+        None => run_dialog(config),
+    }
+}
+
+/// Descriptor from `--result-fd <n>` to write the JSON report to
+fn result_fd(args: &[String]) -> Option<i32> {
+    let pos = args.iter().position(|a| a == "--result-fd")?;
+    let fd = args.get(pos + 1).and_then(|n| n.parse().ok());
+    if fd.is_none_or(|fd| fd < 3) {
+        eprintln!("session-dialog: --result-fd requires a descriptor number above 2");
+        malformed();
+    }
+    fd
+}
+
+/// Exit after a missing or unparseable config
+///
+/// Denies (exit 1), unless `SESSION_DIALOG_MALFORMED=error` asks for an
+/// error (exit 3) so `try_show_dialog` reports why with the stderr.
+/// Neither ever confirms.
+fn malformed() -> ! {
+    let as_error = env::var(MALFORMED_CONFIG_ENV).is_ok_and(|v| v.eq_ignore_ascii_case("error"));
+    std::process::exit(if as_error { 3 } else { 1 })
+}
+
+/// Sample config for `--preview`, with `--timeout` and `--theme` applied
+fn preview_config(args: &[String], pos: usize) -> DialogConfig {
+    let usage = || -> ! {
+        eprintln!("usage: session-dialog --preview <privilege|network|generic|device> [--timeout <secs>] [--theme <name>]");
+        std::process::exit(3)
+    };
+    let option = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|i| args.get(i + 1).unwrap_or_else(|| usage()))
+    };
+
+    let kind = match args.get(pos + 1).map(String::as_str) {
+        Some("privilege") => DialogKind::PrivilegeEscalation {
+            command: "/usr/bin/pacman -Syu --noconfirm ~/pkg/local.pkg.tar.zst".to_string(),
+        },
+        Some("network") => DialogKind::NetworkConnection {
+            process: "curl".to_string(),
+            process_path: "/usr/bin/curl".into(),
+            destination: "example.com (93.184.215.14)".to_string(),
+            port: 443,
+            protocol: Protocol::Tcp,
+            pid: None,
+            exe_hash: None,
+            cmdline: Some("curl -fsSL https://example.com/install.sh".to_string()),
+            parent_process: Some("bash".to_string()),
+            ancestry: vec![
+                "bash".to_string(),
+                "alacritty".to_string(),
+                "sway".to_string(),
+            ],
+        },
+        Some("device") => DialogKind::DeviceAccess {
+            device_name: "sdb1".to_string(),
+            vendor: "SanDisk".to_string(),
+            product: "Ultra Fit".to_string(),
+            serial: Some("4C530001231219117483".to_string()),
+            action: "mount".to_string(),
+        },
+        Some("generic") => DialogKind::Generic {
+            title: "Preview".to_string(),
+            message: "A service needs your confirmation:".to_string(),
+            detail: "This is a sample request shown by --preview.".into(),
+        },
+        _ => usage(),
+    };
+
+    let mut config = DialogConfig::new(kind);
+    if let Some(secs) = option("--timeout") {
+        config.timeout_secs = Some(secs.parse().unwrap_or_else(|_| usage()));
+    }
+    if let Some(name) = option("--theme") {
+        config.theme = Some(ThemeSelection::Named(name.clone()));
+    }
+    eprintln!("session-dialog: preview only, the result is not reported anywhere");
+    config
+}
+
+/// Exit 3 if the config decodes but can't be shown as given
+fn validate(config: &DialogConfig) {
+    if let Err(e) = config.validate() {
+        eprintln!("session-dialog: invalid config: {}", e);
+        std::process::exit(3);
+    }
 }
 
 fn parse_args() -> DialogConfig {
-    let args: Vec<String> = env::args().collect();
+    // --validate is handled in main and must not end up in a legacy command
+    let args: Vec<String> = env::args().filter(|a| a != "--validate").collect();
+
+    #[cfg(feature = "schema")]
+    if args.iter().any(|a| a == "--print-schema") {
+        println!("{}", session_dialog::schema());
+        std::process::exit(0);
+    }
+
+    if args.iter().any(|a| a == "--config-stdin") {
+        return stdin_config(args.iter().any(|a| a == "--json"));
+    }
+
+    if let Some(pos) = args.iter().position(|a| a == "--config-json") {
+        let Some(json) = args.get(pos + 1) else {
+            eprintln!("session-dialog: --config-json requires an argument");
+            malformed();
+        };
+        check_len(json.len());
+        return DialogConfig::from_json(json).unwrap_or_else(|e| {
+            eprintln!("session-dialog: failed to parse config: {}", e);
+            malformed();
+        });
+    }
 
     let Some(pos) = args.iter().position(|a| a == "--config") else {
         return legacy_config(&args);
@@ -24,28 +181,86 @@ fn parse_args() -> DialogConfig {
 
     let Some(config_b64) = args.get(pos + 1) else {
         eprintln!("session-dialog: --config requires an argument");
-        std::process::exit(3);
+        malformed();
     };
 
     let bytes = base64_decode(config_b64).unwrap_or_else(|e| {
         eprintln!("session-dialog: failed to decode config: {}", e);
-        std::process::exit(3);
+        malformed();
     });
 
-    DialogConfig::from_bytes(&bytes).unwrap_or_else(|e| {
+    decode(&bytes).unwrap_or_else(|e| {
         eprintln!("session-dialog: failed to parse config: {}", e);
-        std::process::exit(3);
+        malformed();
+    })
+}
+
+/// Decode a msgpack config, exiting 3 if it is over `MAX_CONFIG_LEN`
+fn decode(bytes: &[u8]) -> Result<DialogConfig, DecodeError> {
+    match DialogConfig::from_bytes_limited(bytes, MAX_CONFIG_LEN) {
+        Err(DecodeError::TooLarge(len)) => too_large(len),
+        decoded => decoded,
+    }
+}
+
+/// Exit 3 if a config of `len` bytes is over `MAX_CONFIG_LEN`
+fn check_len(len: usize) {
+    if len > MAX_CONFIG_LEN {
+        too_large(len);
+    }
+}
+
+/// Oversized input is an error whatever `SESSION_DIALOG_MALFORMED` says: it
+/// is never a corrupted copy of a config the daemon meant to send
+fn too_large(len: usize) -> ! {
+    eprintln!(
+        "session-dialog: config too large ({} bytes, limit {})",
+        len, MAX_CONFIG_LEN
+    );
+    std::process::exit(3)
+}
+
+fn stdin_config(json: bool) -> DialogConfig {
+    // One byte past the limit is enough to tell it was exceeded
+    let mut bytes = Vec::new();
+    let limit = MAX_CONFIG_LEN as u64 + 1;
+    if let Err(e) = std::io::stdin().take(limit).read_to_end(&mut bytes) {
+        eprintln!("session-dialog: failed to read config from stdin: {}", e);
+        malformed();
+    }
+
+    let parsed = if json {
+        check_len(bytes.len());
+        std::str::from_utf8(&bytes)
+            .map_err(|e| e.to_string())
+            .and_then(|s| DialogConfig::from_json(s).map_err(|e| e.to_string()))
+    } else {
+        decode(&bytes).map_err(|e| e.to_string())
+    };
+    parsed.unwrap_or_else(|e| {
+        eprintln!("session-dialog: failed to parse config: {}", e);
+        malformed();
     })
 }
 
 fn legacy_config(args: &[String]) -> DialogConfig {
     let command = args.iter().skip(1).cloned().collect::<Vec<_>>().join(" ");
     if command.is_empty() {
-        eprintln!("usage: session-dialog --config <base64> | <command>");
-        std::process::exit(3);
+        eprintln!("usage: session-dialog [--validate] [--result-fd <n>] --config <base64> | --config-json <json> | --config-stdin [--json] | --stream | --preview <kind> | <command>");
+        malformed();
     }
+    eprintln!(
+        "session-dialog: warning: command-only invocation is deprecated, use --config or --config-stdin"
+    );
+
+    let timeout_secs = env::var(LEGACY_TIMEOUT_ENV).ok().map(|secs| {
+        secs.trim().parse().unwrap_or_else(|_| {
+            eprintln!("session-dialog: invalid {}: {:?}", LEGACY_TIMEOUT_ENV, secs);
+            malformed();
+        })
+    });
     DialogConfig {
-        kind: session_dialog::DialogKind::PrivilegeEscalation { command },
-        timeout_secs: None,
+        timeout_secs,
+        ..DialogConfig::new(session_dialog::DialogKind::PrivilegeEscalation { command })
     }
 }
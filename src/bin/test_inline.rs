@@ -1,27 +1,30 @@
 //! Test show_dialog_inline
 
-use session_dialog::{show_dialog_inline, DialogConfig, DialogKind};
-use std::collections::HashMap;
+use session_dialog::{
+    current_wayland_env, show_dialog_inline, wayland_env_from, DialogConfig, DialogKind,
+};
 
 fn main() {
-    let mut env = HashMap::new();
-
-    // Get Wayland env from current environment
-    for key in session_dialog::WAYLAND_ENV_VARS {
-        if let Ok(val) = std::env::var(key) {
-            env.insert(key.to_string(), val);
-        }
+    let env = current_wayland_env();
+    if let Err(e) = wayland_env_from(&env) {
+        eprintln!("{}", e);
+        std::process::exit(3);
     }
 
     let config = DialogConfig {
-        kind: DialogKind::NetworkConnection {
+        timeout_secs: Some(10),
+        ..DialogConfig::new(DialogKind::NetworkConnection {
             process: "firefox".into(),
             process_path: "/usr/lib/firefox/firefox".into(),
             destination: "api.anthropic.com".into(),
             port: 443,
             protocol: "TCP".into(),
-        },
-        timeout_secs: Some(10),
+            pid: None,
+            exe_hash: None,
+            cmdline: None,
+            parent_process: None,
+            ancestry: Vec::new(),
+        })
     };
 
     println!("Showing dialog...");
@@ -0,0 +1,90 @@
+//! Step-by-step construction of a `DialogConfig`
+
+use crate::{ConfigError, DetailSource, DialogConfig, DialogKind, Protocol};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Builder for `DialogConfig`, checked by `build`
+///
+/// ```ignore
+/// let config = DialogConfig::builder()
+///     .privilege_escalation("/usr/bin/pacman -Syu")
+///     .timeout(Duration::from_secs(30))
+///     .build()?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DialogConfigBuilder {
+    kind: Option<DialogKind>,
+    timeout: Option<Duration>,
+}
+
+impl DialogConfigBuilder {
+    /// Ask to run `command` with elevated privileges
+    pub fn privilege_escalation(mut self, command: impl Into<String>) -> Self {
+        self.kind = Some(DialogKind::PrivilegeEscalation {
+            command: command.into(),
+        });
+        self
+    }
+
+    /// Ask whether `process` may connect to `destination:port`
+    pub fn network_connection(
+        mut self,
+        process: impl Into<String>,
+        process_path: impl Into<PathBuf>,
+        destination: impl Into<String>,
+        port: u16,
+        protocol: Protocol,
+    ) -> Self {
+        self.kind = Some(DialogKind::NetworkConnection {
+            process: process.into(),
+            process_path: process_path.into(),
+            destination: destination.into(),
+            port,
+            protocol,
+            pid: None,
+            exe_hash: None,
+            cmdline: None,
+            parent_process: None,
+            ancestry: Vec::new(),
+        });
+        self
+    }
+
+    /// A confirmation with caller-supplied text
+    pub fn generic(
+        mut self,
+        title: impl Into<String>,
+        message: impl Into<String>,
+        detail: impl Into<DetailSource>,
+    ) -> Self {
+        self.kind = Some(DialogKind::Generic {
+            title: title.into(),
+            message: message.into(),
+            detail: detail.into(),
+        });
+        self
+    }
+
+    /// Time out after `timeout`, rounded down to whole seconds
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The config, or why it can't be shown (see `DialogConfig::validate`)
+    ///
+    /// Empty commands and port 0 are rejected, as are timeouts under a
+    /// second.
+    pub fn build(self) -> Result<DialogConfig, ConfigError> {
+        let kind = self.kind.ok_or(ConfigError::MissingKind)?;
+        let config = DialogConfig {
+            timeout_secs: self
+                .timeout
+                .map(|timeout| timeout.as_secs().min(u32::MAX as u64) as u32),
+            ..DialogConfig::new(kind)
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
@@ -0,0 +1,196 @@
+//! Splitting command lines into words for display
+//!
+//! Only for readability: the classification is a guess from the text and
+//! never used to decide anything.
+
+use std::ops::Range;
+
+/// What a word of a command line looks like
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Part {
+    /// The first word, the program that runs
+    Program,
+    /// A word starting with `-`
+    Flag,
+    /// A word that looks like a file (`/`, `./`, `~`)
+    Path,
+    /// Any other word
+    Argument,
+    /// Whitespace between words
+    Space,
+}
+
+/// A byte range of the command and what it is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub part: Part,
+    pub range: Range<usize>,
+}
+
+/// Split `command` into segments that together cover all of it
+///
+/// Quoted text (single or double) and backslash escapes stay within their
+/// word, so `"my file"` is one argument. Quotes are kept in the segment.
+pub fn segments(command: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut chars = command.char_indices().peekable();
+    let mut first_word = true;
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            while chars.next_if(|&(_, c)| c.is_whitespace()).is_some() {}
+            let end = chars.peek().map_or(command.len(), |&(i, _)| i);
+            segments.push(Segment {
+                part: Part::Space,
+                range: start..end,
+            });
+            continue;
+        }
+
+        let mut quote = None;
+        while let Some(&(_, c)) = chars.peek() {
+            match (quote, c) {
+                (None, c) if c.is_whitespace() => break,
+                (None, '\'' | '"') => quote = Some(c),
+                (Some(q), c) if c == q => quote = None,
+                (Some('\''), _) => {}
+                (_, '\\') => {
+                    chars.next();
+                }
+                _ => {}
+            }
+            chars.next();
+        }
+        let end = chars.peek().map_or(command.len(), |&(i, _)| i);
+        let part = if first_word {
+            Part::Program
+        } else {
+            classify(&command[start..end])
+        };
+        first_word = false;
+        segments.push(Segment {
+            part,
+            range: start..end,
+        });
+    }
+
+    segments
+}
+
+fn classify(word: &str) -> Part {
+    let unquoted = word.trim_start_matches(['\'', '"']);
+    if word.starts_with('-') {
+        Part::Flag
+    } else if unquoted.starts_with(['/', '~'])
+        || unquoted.starts_with("./")
+        || unquoted.starts_with("../")
+    {
+        Part::Path
+    } else {
+        Part::Argument
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(command: &str) -> Vec<(Part, &str)> {
+        segments(command)
+            .into_iter()
+            .map(|s| (s.part, &command[s.range]))
+            .collect()
+    }
+
+    /// Ranges are contiguous and together cover the whole command
+    fn assert_covers(command: &str) {
+        let mut end = 0;
+        for segment in segments(command) {
+            assert_eq!(segment.range.start, end, "gap in {:?}", command);
+            assert!(segment.range.end > segment.range.start);
+            end = segment.range.end;
+        }
+        assert_eq!(end, command.len(), "{:?} not fully covered", command);
+    }
+
+    #[test]
+    fn words_are_classified() {
+        assert_eq!(
+            parts("/usr/bin/pacman -Syu --noconfirm ./pkg.tar ~/x ../y linux"),
+            [
+                (Part::Program, "/usr/bin/pacman"),
+                (Part::Space, " "),
+                (Part::Flag, "-Syu"),
+                (Part::Space, " "),
+                (Part::Flag, "--noconfirm"),
+                (Part::Space, " "),
+                (Part::Path, "./pkg.tar"),
+                (Part::Space, " "),
+                (Part::Path, "~/x"),
+                (Part::Space, " "),
+                (Part::Path, "../y"),
+                (Part::Space, " "),
+                (Part::Argument, "linux"),
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_and_escapes_stay_in_their_word() {
+        assert_eq!(
+            parts(r#"rm "/tmp/my file" 'a b'  x\ y"#),
+            [
+                (Part::Program, "rm"),
+                (Part::Space, " "),
+                (Part::Path, r#""/tmp/my file""#),
+                (Part::Space, " "),
+                (Part::Argument, "'a b'"),
+                (Part::Space, "  "),
+                (Part::Argument, r"x\ y"),
+            ]
+        );
+        // A backslash is literal inside single quotes
+        assert_eq!(
+            parts(r"echo 'a\' b"),
+            [
+                (Part::Program, "echo"),
+                (Part::Space, " "),
+                (Part::Argument, r"'a\'"),
+                (Part::Space, " "),
+                (Part::Argument, "b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn segments_cover_the_whole_command() {
+        for command in [
+            "",
+            " ",
+            "ls",
+            "  leading and trailing\t ",
+            "unterminated \"quote here",
+            "trailing backslash \\",
+            "日本語 --フラグ /パス",
+            "tab\tseparated\nlines",
+        ] {
+            assert_covers(command);
+        }
+        assert!(segments("").is_empty());
+    }
+
+    #[test]
+    fn leading_space_does_not_take_the_program_slot() {
+        assert_eq!(
+            parts("  sudo -u root"),
+            [
+                (Part::Space, "  "),
+                (Part::Program, "sudo"),
+                (Part::Space, " "),
+                (Part::Flag, "-u"),
+                (Part::Space, " "),
+                (Part::Argument, "root"),
+            ]
+        );
+    }
+}
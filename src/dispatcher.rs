@@ -0,0 +1,209 @@
+//! Serialized dispatch of dialogs, with aggregate statistics
+//!
+//! A session can hold only one lock at a time, so daemons that prompt from
+//! several threads (e.g. one per intercepted connection) route every prompt
+//! through a shared `DialogDispatcher`, which shows them one after another.
+
+use crate::{show_dialog, DialogConfig, DialogKind, DialogResult};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Shows dialogs one at a time and counts their outcomes
+///
+/// Share it behind an `Arc`; all methods take `&self`.
+#[derive(Debug, Default)]
+pub struct DialogDispatcher {
+    // Held for the whole time a dialog is shown
+    showing: Mutex<()>,
+    // Separate from `showing` so `stats` never waits on the user
+    stats: Mutex<DialogStats>,
+}
+
+/// Snapshot of the outcomes a dispatcher has seen
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DialogStats {
+    /// Counts over all prompts
+    pub total: OutcomeCounts,
+    /// Counts per requesting process (`NetworkConnection` prompts only)
+    pub per_process: HashMap<String, OutcomeCounts>,
+}
+
+/// Prompt counts by outcome
+///
+/// `prompts` includes every dialog; outcomes other than confirm, deny and
+/// timeout (cancel, custom actions, errors) only show up there.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutcomeCounts {
+    pub prompts: u64,
+    pub confirmed: u64,
+    pub denied: u64,
+    pub timed_out: u64,
+}
+
+impl OutcomeCounts {
+    fn record(&mut self, result: DialogResult) {
+        self.prompts += 1;
+        match result {
+            result if result.is_confirmed() => self.confirmed += 1,
+            DialogResult::Denied | DialogResult::DeniedRemember => self.denied += 1,
+            DialogResult::Timeout => self.timed_out += 1,
+            _ => {}
+        }
+    }
+}
+
+impl DialogDispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show a dialog once no other dialog from this dispatcher is showing
+    ///
+    /// Arguments are as for `show_dialog`.
+    pub fn dispatch(
+        &self,
+        config: &DialogConfig,
+        uid: u32,
+        gid: u32,
+        env: &HashMap<String, String>,
+    ) -> DialogResult {
+        self.serialized(&config.kind, || show_dialog(config, uid, gid, env))
+    }
+
+    // Run `show` once no other dialog is showing, and count its result
+    fn serialized(&self, kind: &DialogKind, show: impl FnOnce() -> DialogResult) -> DialogResult {
+        let result = {
+            let _showing = self.showing.lock().unwrap_or_else(|e| e.into_inner());
+            show()
+        };
+        self.record(kind, result);
+        result
+    }
+
+    /// Snapshot of the counters, safe to call while dialogs are showing
+    pub fn stats(&self) -> DialogStats {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn record(&self, kind: &DialogKind, result: DialogResult) {
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        stats.total.record(result);
+        if let DialogKind::NetworkConnection { process, .. } = kind {
+            stats
+                .per_process
+                .entry(process.clone())
+                .or_default()
+                .record(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConnectionScope, Protocol};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn connection(process: &str) -> DialogKind {
+        DialogKind::NetworkConnection {
+            process: process.to_string(),
+            process_path: format!("/usr/bin/{}", process).into(),
+            destination: "example.com".to_string(),
+            port: 443,
+            protocol: Protocol::Tcp,
+            pid: None,
+            exe_hash: None,
+            cmdline: None,
+            parent_process: None,
+            ancestry: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn counts_each_result() {
+        let dispatcher = DialogDispatcher::new();
+        let results = [
+            DialogResult::Confirmed,
+            DialogResult::ConfirmedRemember,
+            DialogResult::ConfirmedScope(ConnectionScope::Host),
+            DialogResult::Denied,
+            DialogResult::DeniedRemember,
+            DialogResult::Timeout,
+            DialogResult::Cancelled,
+            DialogResult::Error,
+            DialogResult::Action(0),
+        ];
+        for result in results {
+            assert_eq!(
+                dispatcher.serialized(&connection("curl"), || result),
+                result
+            );
+        }
+        let command = DialogKind::PrivilegeEscalation {
+            command: "/usr/bin/true".to_string(),
+        };
+        dispatcher.serialized(&command, || DialogResult::Denied);
+
+        let stats = dispatcher.stats();
+        let curl = OutcomeCounts {
+            prompts: 9,
+            confirmed: 3,
+            denied: 2,
+            timed_out: 1,
+        };
+        assert_eq!(
+            stats.per_process,
+            HashMap::from([("curl".to_string(), curl)])
+        );
+        assert_eq!(
+            stats.total,
+            OutcomeCounts {
+                prompts: 10,
+                denied: 3,
+                ..curl
+            }
+        );
+    }
+
+    #[test]
+    fn concurrent_dispatch_shows_one_at_a_time() {
+        let dispatcher = Arc::new(DialogDispatcher::new());
+        let showing = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let dispatcher = Arc::clone(&dispatcher);
+                let showing = Arc::clone(&showing);
+                std::thread::spawn(move || {
+                    let process = if i % 2 == 0 { "curl" } else { "wget" };
+                    dispatcher.serialized(&connection(process), || {
+                        assert_eq!(
+                            showing.fetch_add(1, Ordering::SeqCst),
+                            0,
+                            "two dialogs at once"
+                        );
+                        std::thread::sleep(Duration::from_millis(10));
+                        showing.fetch_sub(1, Ordering::SeqCst);
+                        if i % 4 == 0 {
+                            DialogResult::Confirmed
+                        } else {
+                            DialogResult::Denied
+                        }
+                    })
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let stats = dispatcher.stats();
+        assert_eq!(stats.total.prompts, 8);
+        assert_eq!(stats.total.confirmed, 2);
+        assert_eq!(stats.total.denied, 6);
+        assert_eq!(stats.per_process["curl"].prompts, 4);
+        assert_eq!(stats.per_process["curl"].confirmed, 2);
+        assert_eq!(stats.per_process["wget"].denied, 4);
+    }
+}
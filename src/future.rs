@@ -0,0 +1,241 @@
+//! Future-based waiting on a spawned dialog process
+
+use crate::{drain_stderr, flatten_result, result_from_output, DialogResult, CANCEL_SIGNAL};
+use std::future::Future;
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::pin::Pin;
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+
+/// A spawned dialog that resolves to its `DialogResult`
+///
+/// Exit readiness is tracked through a pidfd (`pidfd_open`, Linux 5.3+).
+/// On older kernels a watcher thread falls back to `waitid(WNOWAIT)`.
+/// Either way the child is only reaped by `poll`, never by the watcher.
+/// Its stderr is drained from the start, so it can't block writing to it.
+pub struct DialogFuture {
+    child: Option<Child>,
+    stderr: Option<JoinHandle<Vec<u8>>>,
+    compositor: Option<String>,
+    watch: Option<Arc<Mutex<WatchState>>>,
+}
+
+#[derive(Default)]
+struct WatchState {
+    exited: bool,
+    waker: Option<Waker>,
+}
+
+impl DialogFuture {
+    pub(crate) fn new(mut child: Option<Child>, compositor: Option<String>) -> Self {
+        Self {
+            stderr: child.as_mut().map(drain_stderr),
+            child,
+            compositor,
+            watch: None,
+        }
+    }
+
+    /// Dismiss the dialog; it unlocks and resolves to `DialogResult::Cancelled`
+    ///
+    /// For when the request is moot, e.g. the connection being prompted for
+    /// was abandoned. Has no effect once the dialog has exited.
+    pub fn cancel(&self) {
+        if let Some(child) = &self.child {
+            // SAFETY: kill only sends a signal. The child is reaped by
+            // `poll` alone, which drops it, so its pid can't be reused yet.
+            unsafe { libc::kill(child.id() as libc::pid_t, CANCEL_SIGNAL) };
+        }
+    }
+
+    // Start the watcher on first poll, then keep its waker current
+    fn register(&mut self, pid: u32, waker: &Waker) {
+        let watch = self.watch.get_or_insert_with(|| {
+            let watch = Arc::new(Mutex::new(WatchState::default()));
+            spawn_watcher(pid, Arc::clone(&watch));
+            watch
+        });
+
+        let mut state = watch.lock().unwrap();
+        if state.exited {
+            // Exited between try_wait and registration
+            waker.wake_by_ref();
+        } else {
+            state.waker = Some(waker.clone());
+        }
+    }
+}
+
+impl Future for DialogFuture {
+    type Output = DialogResult;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<DialogResult> {
+        let Some(child) = self.child.as_mut() else {
+            return Poll::Ready(DialogResult::Error); // Spawn failed
+        };
+
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                self.child = None;
+                // The pipe hits EOF once the child has exited
+                let stderr = self
+                    .stderr
+                    .take()
+                    .and_then(|drain| drain.join().ok())
+                    .unwrap_or_default();
+                let compositor = self.compositor.take();
+                Poll::Ready(flatten_result(result_from_output(
+                    status, &stderr, compositor,
+                )))
+            }
+            Ok(None) => {
+                let pid = child.id();
+                self.register(pid, cx.waker());
+                Poll::Pending
+            }
+            Err(_) => Poll::Ready(DialogResult::Error),
+        }
+    }
+}
+
+// Block on the child's exit without reaping it, then wake the future
+fn spawn_watcher(pid: u32, watch: Arc<Mutex<WatchState>>) {
+    std::thread::spawn(move || {
+        match pidfd_open(pid) {
+            Some(pidfd) => wait_pidfd(&pidfd),
+            None => wait_nowait(pid),
+        }
+
+        let mut state = watch.lock().unwrap();
+        state.exited = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    });
+}
+
+fn pidfd_open(pid: u32) -> Option<OwnedFd> {
+    // SAFETY: pidfd_open takes a pid and flags and returns a new fd or -1
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd < 0 {
+        return None;
+    }
+    // SAFETY: the syscall succeeded, so we own this fd
+    Some(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+fn wait_pidfd(pidfd: &OwnedFd) {
+    use std::os::fd::AsRawFd;
+
+    let mut pollfd = libc::pollfd {
+        fd: pidfd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // A pidfd becomes readable once the process exits
+    // SAFETY: pollfd is a valid single-element array for the call
+    while unsafe { libc::poll(&mut pollfd, 1, -1) } < 0 {
+        if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            break;
+        }
+    }
+}
+
+fn wait_nowait(pid: u32) {
+    // SAFETY: siginfo_t is plain data and waitid only writes into it
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    // WNOWAIT leaves the child waitable so try_wait can reap it
+    while unsafe {
+        libc::waitid(
+            libc::P_PID,
+            pid as libc::id_t,
+            &mut info,
+            libc::WEXITED | libc::WNOWAIT,
+        )
+    } < 0
+    {
+        if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+    use std::task::Wake;
+    use std::thread::{self, Thread};
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future + Unpin>(mut future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match Pin::new(&mut future).poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    /// A future for `sh -c script` standing in for the dialog binary
+    fn dialog(script: &str) -> DialogFuture {
+        let child = Command::new("sh")
+            .args(["-c", script])
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("spawn sh");
+        DialogFuture::new(Some(child), None)
+    }
+
+    #[test]
+    fn resolves_to_the_exit_code() {
+        for (script, result) in [
+            ("exit 0", DialogResult::Confirmed),
+            ("exit 1", DialogResult::Denied),
+            ("sleep 0.1; exit 2", DialogResult::Timeout),
+            ("exit 5", DialogResult::Cancelled),
+            ("echo broken >&2; exit 3", DialogResult::Error),
+        ] {
+            assert_eq!(block_on(dialog(script)), result, "{}", script);
+        }
+    }
+
+    #[test]
+    fn cancel_dismisses_a_dialog_without_timeout() {
+        // Dies of the signal, like a binary that hasn't installed its
+        // handler yet
+        let mut future = dialog("exec sleep 30");
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        assert!(Pin::new(&mut future)
+            .poll(&mut Context::from_waker(&waker))
+            .is_pending());
+        future.cancel();
+        assert_eq!(block_on(future), DialogResult::Cancelled);
+    }
+
+    #[test]
+    fn cancel_after_exit_changes_nothing() {
+        let mut future = dialog("exit 0");
+        thread::sleep(std::time::Duration::from_millis(100));
+        future.cancel();
+        assert_eq!(block_on(&mut future), DialogResult::Confirmed);
+        // Resolved futures drop the child, so there is nothing to signal
+        future.cancel();
+    }
+
+    #[test]
+    fn failed_spawn_is_an_error() {
+        assert_eq!(block_on(DialogFuture::new(None, None)), DialogResult::Error);
+    }
+}
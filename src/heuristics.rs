@@ -0,0 +1,284 @@
+//! Advisory checks on what a dialog asks about (commands, connections)
+//!
+//! These are heuristics: they look at the request as the user would
+//! read it and never try to evaluate it.
+
+use crate::Protocol;
+
+/// Find shell expansions that leave the real command indeterminate
+///
+/// Returns each `$VAR`, `${...}`, `$(...)` and `` `...` `` construct in
+/// order of appearance. Text inside single quotes and backslash-escaped
+/// characters are skipped, since the shell passes those through literally.
+/// Double quotes don't stop expansion, but a `'` inside them is literal.
+pub fn shell_expansions(command: &str) -> Vec<String> {
+    let chars: Vec<char> = command.chars().collect();
+    let mut found = Vec::new();
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\'' if !in_double => in_single = !in_single,
+            _ if in_single => {}
+            '"' => in_double = !in_double,
+            '\\' => i += 1,
+            '`' => {
+                let end = find_from(&chars, i + 1, '`').unwrap_or(chars.len() - 1);
+                found.push(chars[i..=end].iter().collect());
+                i = end;
+            }
+            '$' => {
+                if let Some(end) = expansion_end(&chars, i + 1) {
+                    found.push(chars[i..end].iter().collect());
+                    i = end - 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    found
+}
+
+// Index just past the expansion whose body starts at `start`, if any
+fn expansion_end(chars: &[char], start: usize) -> Option<usize> {
+    match *chars.get(start)? {
+        '(' => Some(matching_close(chars, start, '(', ')')),
+        '{' => Some(matching_close(chars, start, '{', '}')),
+        c if c.is_ascii_alphabetic() || c == '_' => {
+            let len = chars[start..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                .count();
+            Some(start + len)
+        }
+        c if c.is_ascii_digit() || "?@*#!$-".contains(c) => Some(start + 1),
+        _ => None,
+    }
+}
+
+// Index past the bracket closing the one at `open_at` (or the end if unclosed)
+fn matching_close(chars: &[char], open_at: usize, open: char, close: char) -> usize {
+    let mut depth = 0;
+    for (i, &c) in chars.iter().enumerate().skip(open_at) {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return i + 1;
+            }
+        }
+    }
+    chars.len()
+}
+
+fn find_from(chars: &[char], start: usize, target: char) -> Option<usize> {
+    chars[start..]
+        .iter()
+        .position(|&c| c == target)
+        .map(|offset| start + offset)
+}
+
+/// Well-known service on a port, as a hint for non-technical users
+///
+/// Advisory only: nothing stops a program from using port 443 for
+/// something other than HTTPS.
+pub fn port_hint(port: u16, protocol: &Protocol) -> Option<&'static str> {
+    const TCP: &[(u16, &str)] = &[
+        (21, "FTP"),
+        (22, "SSH"),
+        (23, "Telnet"),
+        (25, "SMTP"),
+        (53, "DNS"),
+        (80, "HTTP"),
+        (110, "POP3"),
+        (143, "IMAP"),
+        (443, "HTTPS"),
+        (465, "SMTPS"),
+        (587, "SMTP"),
+        (853, "DNS over TLS"),
+        (993, "IMAPS"),
+        (995, "POP3S"),
+        (3306, "MySQL"),
+        (3389, "RDP"),
+        (5432, "PostgreSQL"),
+        (6667, "IRC"),
+        (8080, "HTTP"),
+    ];
+    const UDP: &[(u16, &str)] = &[
+        (53, "DNS"),
+        (67, "DHCP"),
+        (123, "NTP"),
+        (443, "QUIC"),
+        (5353, "mDNS"),
+        (51820, "WireGuard"),
+    ];
+
+    let table = match protocol {
+        Protocol::Tcp => TCP,
+        Protocol::Udp => UDP,
+        Protocol::Other(_) => return None,
+    };
+    table
+        .iter()
+        .find(|&&(known, _)| known == port)
+        .map(|&(_, name)| name)
+}
+
+/// Privilege-escalation tool invoked by a command that is itself being
+/// escalated (e.g. `sh -c "sudo rm ..."`), a sign of chaining or obfuscation
+///
+/// Looks at every word, with paths and shell punctuation stripped, so
+/// `/usr/bin/sudo` and `true;doas x` are caught too.
+pub fn nested_escalation(command: &str) -> Option<&'static str> {
+    const TOOLS: &[&str] = &["sudo", "su", "pkexec", "doas", "run0"];
+
+    command
+        .split(|c: char| c.is_whitespace() || ";|&()`$'\"".contains(c))
+        .map(|word| word.rsplit('/').next().unwrap_or(word))
+        .find_map(|name| TOOLS.iter().copied().find(|&tool| tool == name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_expansions_found() {
+        let cases: &[(&str, &[&str])] = &[
+            ("rm -rf /tmp/x", &[]),
+            ("rm -rf $HOME", &["$HOME"]),
+            ("rm ${DIR}/x $(pwd) `id`", &["${DIR}", "$(pwd)", "`id`"]),
+            (
+                "echo $1 $? $@ $$ $# $*",
+                &["$1", "$?", "$@", "$$", "$#", "$*"],
+            ),
+            ("echo $ 5", &[]),
+        ];
+        for (command, expected) in cases {
+            assert_eq!(shell_expansions(command), *expected, "{command:?}");
+        }
+    }
+
+    #[test]
+    fn shell_expansions_quoting() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo '$HOME `id`'", &[]),
+            ("echo \"$HOME `id`\"", &["$HOME", "`id`"]),
+            // A single quote inside double quotes is literal
+            ("echo \"it's $USER\"", &["$USER"]),
+            ("echo \"'$USER'\"", &["$USER"]),
+            ("echo '\"' $X '\"'", &["$X"]),
+            ("echo 'a'$B'c'", &["$B"]),
+        ];
+        for (command, expected) in cases {
+            assert_eq!(shell_expansions(command), *expected, "{command:?}");
+        }
+    }
+
+    #[test]
+    fn shell_expansions_escapes() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo \\$HOME", &[]),
+            ("echo \\`id\\`", &[]),
+            ("echo \"\\$HOME\" $A", &["$A"]),
+            ("echo \\\\$HOME", &["$HOME"]),
+            ("echo \"\\\"$X\"", &["$X"]),
+        ];
+        for (command, expected) in cases {
+            assert_eq!(shell_expansions(command), *expected, "{command:?}");
+        }
+    }
+
+    #[test]
+    fn shell_expansions_nesting() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo $(cat $(ls))", &["$(cat $(ls))"]),
+            ("echo ${A:-${B}} $C", &["${A:-${B}}", "$C"]),
+            ("x $(echo \"$(id)\") y", &["$(echo \"$(id)\")"]),
+        ];
+        for (command, expected) in cases {
+            assert_eq!(shell_expansions(command), *expected, "{command:?}");
+        }
+    }
+
+    #[test]
+    fn shell_expansions_unterminated() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo $(rm -rf /", &["$(rm -rf /"]),
+            ("echo ${HOME", &["${HOME"]),
+            ("echo `id", &["`id"]),
+            ("echo '$HOME", &[]),
+            ("echo \"$HOME", &["$HOME"]),
+            ("echo \\", &[]),
+            ("echo $", &[]),
+        ];
+        for (command, expected) in cases {
+            assert_eq!(shell_expansions(command), *expected, "{command:?}");
+        }
+    }
+
+    #[test]
+    fn nested_escalation_found() {
+        let cases = [
+            ("sudo rm -rf /", "sudo"),
+            ("su -c 'id'", "su"),
+            ("sh -c \"pkexec true\"", "pkexec"),
+            ("doas reboot", "doas"),
+            ("env sudo id", "sudo"),
+            ("sh -c 'sudo id'", "sudo"),
+            ("bash -c \"$(sudo id)\"", "sudo"),
+            ("/usr/bin/sudo id", "sudo"),
+            ("true;doas x", "doas"),
+            ("a|run0 b", "run0"),
+            ("`su`", "su"),
+        ];
+        for (command, tool) in cases {
+            assert_eq!(nested_escalation(command), Some(tool), "{command:?}");
+        }
+    }
+
+    #[test]
+    fn nested_escalation_not_found() {
+        for command in [
+            "sudoku --new",
+            "subversion update",
+            "/usr/bin/svn up",
+            "/opt/sudo-tools/report",
+            "pseudo-terminal",
+            "echo dosa",
+            "",
+        ] {
+            assert_eq!(nested_escalation(command), None, "{command:?}");
+        }
+    }
+
+    #[test]
+    fn port_hint_names_well_known_ports() {
+        assert_eq!(port_hint(443, &Protocol::Tcp), Some("HTTPS"));
+        assert_eq!(port_hint(80, &Protocol::Tcp), Some("HTTP"));
+        assert_eq!(port_hint(22, &Protocol::Tcp), Some("SSH"));
+        assert_eq!(port_hint(53, &Protocol::Udp), Some("DNS"));
+        assert_eq!(port_hint(51820, &Protocol::Udp), Some("WireGuard"));
+    }
+
+    #[test]
+    fn port_hint_depends_on_the_protocol() {
+        assert_eq!(port_hint(443, &Protocol::Udp), Some("QUIC"));
+        assert_eq!(port_hint(22, &Protocol::Udp), None);
+        assert_eq!(port_hint(123, &Protocol::Tcp), None);
+        assert_eq!(port_hint(443, &Protocol::Other("sctp".to_string())), None);
+    }
+
+    #[test]
+    fn port_hint_is_none_for_unknown_ports() {
+        for port in [0, 1, 8443, 65535] {
+            assert_eq!(port_hint(port, &Protocol::Tcp), None);
+            assert_eq!(port_hint(port, &Protocol::Udp), None);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Translations of the dialog's built-in text
+//!
+//! Strings are looked up by their English text, gettext style, so anything
+//! without a translation (a missing entry, an unknown locale, a caller's
+//! own label) is shown as given. `{}` in an entry stands for a value the
+//! caller substitutes.
+
+use std::sync::Mutex;
+
+type Catalog = &'static [(&'static str, &'static str)];
+
+// Catalog of the dialog being shown; empty means English
+static ACTIVE: Mutex<Catalog> = Mutex::new(&[]);
+
+/// Catalogs by language code (the part of a locale before `_`)
+const CATALOGS: &[(&str, Catalog)] = &[("de", DE)];
+
+/// Pick the catalog for `locale`, or else for the process's LC_ALL,
+/// LC_MESSAGES or LANG (the first one set)
+pub(crate) fn select(locale: Option<&str>) {
+    let from_env = || {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+    };
+    let locale = locale.map(str::to_string).or_else(from_env);
+    let language = locale
+        .as_deref()
+        .and_then(|locale| locale.split(['_', '.', '@', '-']).next())
+        .unwrap_or_default();
+    *ACTIVE.lock().unwrap() = CATALOGS
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(language))
+        .map(|&(_, catalog)| catalog)
+        .unwrap_or_default();
+}
+
+/// `english` in the selected language, or unchanged if not translated
+pub(crate) fn tr(english: &str) -> &str {
+    let catalog: Catalog = *ACTIVE.lock().unwrap();
+    catalog
+        .iter()
+        .find(|(key, _)| *key == english)
+        .map_or(english, |&(_, translated)| translated)
+}
+
+const DE: Catalog = &[
+    // Titles and subtitles
+    ("Authorization Required", "Autorisierung erforderlich"),
+    ("Network Connection Request", "Anfrage für Netzwerkverbindung"),
+    ("Device Access Request", "Anfrage für Gerätezugriff"),
+    (
+        "An application wants to run as root:",
+        "Eine Anwendung möchte als root ausführen:",
+    ),
+    (
+        "An application wants to connect to:",
+        "Eine Anwendung möchte sich verbinden mit:",
+    ),
+    (
+        "A newly connected device wants access:",
+        "Ein neu angeschlossenes Gerät möchte Zugriff:",
+    ),
+    ("{} Requests Pending", "{} offene Anfragen"),
+    (
+        "Review each request, or decide all at once:",
+        "Prüfen Sie jede Anfrage oder entscheiden Sie alle auf einmal:",
+    ),
+    // Actions and key hints
+    ("Allow", "Erlauben"),
+    ("Deny", "Ablehnen"),
+    ("Allow once", "Einmal erlauben"),
+    ("Allow always", "Immer erlauben"),
+    ("Deny once", "Einmal ablehnen"),
+    ("Deny always", "Immer ablehnen"),
+    ("Cancel", "Abbrechen"),
+    ("Select", "Auswählen"),
+    ("Allow rest", "Rest erlauben"),
+    ("Deny rest", "Rest ablehnen"),
+    ("No timeout", "Kein Zeitlimit"),
+    ("Copy", "Kopieren"),
+    ("Copied {}", "{} kopiert"),
+    ("Details", "Details"),
+    ("Path", "Pfad"),
+    ("PID", "PID"),
+    ("Command line", "Befehlszeile"),
+    ("Process tree", "Prozessbaum"),
+    // Scope of an allowed connection
+    ("Allow for", "Erlauben für"),
+    ("Only {}", "Nur {}"),
+    ("{}, any port", "{}, beliebiger Port"),
+    ("{}, anywhere", "{}, beliebiges Ziel"),
+    // Copyable fields
+    ("command", "Befehl"),
+    ("process", "Prozess"),
+    ("path", "Pfad"),
+    ("destination", "Ziel"),
+    ("port", "Port"),
+    ("detail", "Details"),
+    ("device", "Gerät"),
+    ("vendor", "Hersteller"),
+    ("product", "Produkt"),
+    ("serial", "Seriennummer"),
+    // Batch item states
+    ("Pending", "Offen"),
+    ("Allowed", "Erlaubt"),
+    ("Denied", "Abgelehnt"),
+    // Footer and badge
+    ("Secure prompt", "Sichere Abfrage"),
+    ("Confirming in {}…", "Bestätigung in {}…"),
+    ("to cancel", "zum Abbrechen"),
+    ("Auto-deny in", "Automatische Ablehnung in"),
+    ("Continuing in", "Fortsetzung in"),
+    ("Closes in", "Schließt in"),
+    (
+        "({} more characters, Ctrl+C copies all)",
+        "({} weitere Zeichen, Strg+C kopiert alles)",
+    ),
+    // Warnings about the request
+    (
+        "Contains shell expansion ({}); the command that actually runs may differ",
+        "Enthält Shell-Erweiterungen ({}); der tatsächlich ausgeführte Befehl kann abweichen",
+    ),
+    (
+        "{}. This may not be the program that made the request",
+        "{}. Dies ist möglicherweise nicht das Programm, das die Anfrage gestellt hat",
+    ),
+    (
+        "Runs {} itself: a privilege request inside a privilege request",
+        "Führt selbst {} aus: eine Rechteanfrage innerhalb einer Rechteanfrage",
+    ),
+    // Context menu
+    ("Show full path", "Vollständigen Pfad anzeigen"),
+    ("Why am I seeing this?", "Warum sehe ich das?"),
+    ("Report", "Melden"),
+    (
+        "Reported. You still need to allow or deny.",
+        "Gemeldet. Sie müssen trotzdem erlauben oder ablehnen.",
+    ),
+    (
+        "A program asked to run this command with administrator rights. Allow it only if you just started something that needs them.",
+        "Ein Programm möchte diesen Befehl mit Administratorrechten ausführen. Erlauben Sie es nur, wenn Sie gerade etwas gestartet haben, das diese Rechte braucht.",
+    ),
+    (
+        "The firewall stopped a program from connecting to the network until you decide. Allow it only if you expect this program to go online.",
+        "Die Firewall hält eine Netzwerkverbindung eines Programms zurück, bis Sie entscheiden. Erlauben Sie sie nur, wenn Sie erwarten, dass dieses Programm online geht.",
+    ),
+    (
+        "A system service needs your decision before it continues. The screen is locked so no other program can answer for you.",
+        "Ein Systemdienst braucht Ihre Entscheidung, bevor er fortfährt. Der Bildschirm ist gesperrt, damit kein anderes Programm für Sie antworten kann.",
+    ),
+    (
+        "A device was just plugged in and is held back until you decide. Allow it only if you connected it yourself; unknown USB devices can carry malware.",
+        "Ein Gerät wurde gerade angeschlossen und wird zurückgehalten, bis Sie entscheiden. Erlauben Sie es nur, wenn Sie es selbst angeschlossen haben; unbekannte USB-Geräte können Schadsoftware enthalten.",
+    ),
+];
@@ -12,40 +12,295 @@
 //! use session_dialog::{DialogConfig, DialogKind, show_dialog};
 //! use std::collections::HashMap;
 //!
-//! let config = DialogConfig {
-//!     kind: DialogKind::PrivilegeEscalation {
-//!         command: "/usr/bin/pacman -Syu".into(),
-//!     },
-//!     timeout_secs: None,
-//! };
+//! let config = DialogConfig::new(DialogKind::PrivilegeEscalation {
+//!     command: "/usr/bin/pacman -Syu".into(),
+//! });
 //!
 //! let env: HashMap<String, String> = HashMap::new();
 //! let result = show_dialog(&config, 1000, 1000, &env);
 //! ```
+//!
+//! # Input confidentiality
+//!
+//! There is no separate keyboard grab to request: while an ext-session-lock
+//! is held, the compositor must deliver all keyboard and pointer input to
+//! the lock surfaces only, so keystrokes cannot reach background clients.
+//! If the compositor refuses the lock, no dialog is shown at all and the
+//! caller gets `DialogError::LockUnavailable` rather than an unsecured
+//! prompt.
+//!
+//! # Multiple outputs
+//!
+//! Every output is locked and shows the same prompt, so it can't end up on
+//! a monitor nobody is looking at; answering on any of them answers all.
+//! There is no choosing an output: the session-lock surfaces don't say
+//! which output (connector) they cover.
+//!
+//! # Environment
+//!
+//! `SESSION_DIALOG_FORCE_DENY=1` makes every dialog deny immediately without
+//! showing anything, overriding all config. It is read when each dialog
+//! starts (in the daemon's environment, which the spawned binary inherits),
+//! so admins can disable approvals globally during an incident.
+//!
+//! For integration tests without a compositor, `SESSION_DIALOG_TEST=1` plus
+//! `SESSION_DIALOG_AUTO_RESULT=confirmed|denied|timeout` skips the UI and
+//! returns that result (after `SESSION_DIALOG_AUTO_DELAY_MS`, if set). The
+//! auto result is ignored unless test mode is also enabled.
+//!
+//! `SESSION_DIALOG_BIN` picks the dialog binary `show_dialog` runs, for
+//! packagers installing it outside `/usr/bin` (see `SpawnOptions::binary`).
+//!
+//! The deprecated command-only invocation (`session-dialog <command>`) never
+//! times out unless `SESSION_DIALOG_LEGACY_TIMEOUT` gives a timeout in
+//! seconds.
+//!
+//! A missing or malformed config makes the binary deny (exit 1), the safe
+//! answer for security deployments. `SESSION_DIALOG_MALFORMED=error` makes
+//! it exit with an error (exit 3) instead, which `try_show_dialog` returns
+//! as `DialogError::Failed` with the binary's explanation. Either way
+//! corrupt input fails closed.
+//!
+//! # Audit log
+//!
+//! Every decision, including ones made without showing the dialog, is
+//! written to the binary's stderr as one `session-dialog: audit ...` line
+//! of `key=value` pairs (timestamp, kind, title, redacted detail, result,
+//! exit code, elapsed time, decision source and request id). Daemons can
+//! capture and forward it; building with the `syslog` feature also sends
+//! it to syslog under `LOG_AUTHPRIV`.
 
+mod audit;
+mod auth;
+mod builder;
+mod cmdline;
+mod dispatcher;
+mod future;
+mod heuristics;
+mod i18n;
+mod markup;
+mod policy;
+mod procinfo;
+mod redact;
+mod stream;
+mod task;
+pub mod theming;
+mod tty;
 mod ui;
 
+pub use auth::{show_auth_dialog, AuthOutcome, MAX_SECRET_LEN, SECRET_FD};
+pub use builder::DialogConfigBuilder;
+pub use dispatcher::{DialogDispatcher, DialogStats, OutcomeCounts};
+pub use future::DialogFuture;
+pub use policy::{should_prompt, Decision, Policy};
+pub use stream::DialogStream;
+pub use task::DialogTask;
+pub use tty::show_dialog_tty;
+pub use zeroize::Zeroizing;
+
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::os::unix::process::CommandExt;
+use std::io::Write;
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
 
 /// Result of showing a confirmation dialog
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum DialogResult {
     /// User confirmed the action
     Confirmed,
-    /// User denied the action
+    /// User explicitly denied the action (a "no" the caller may persist)
     Denied,
     /// Dialog timed out
     Timeout,
     /// Error showing dialog
     Error,
+    /// User picked the custom action at this index (see `DialogAction`)
+    Action(u8),
+    /// User dismissed the prompt without deciding (the caller may ask again)
+    Cancelled,
+    /// User allowed and asked not to be prompted again for this request
+    /// (see `DialogConfig::offer_remember`)
+    ConfirmedRemember,
+    /// User denied and asked not to be prompted again for this request
+    DeniedRemember,
+    /// User allowed connections within this scope (see
+    /// `DialogConfig::offer_scope`)
+    ConfirmedScope(ConnectionScope),
+}
+
+/// How far an allowed network connection should extend, for the rule a
+/// firewall writes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ConnectionScope {
+    /// This destination and port only
+    #[default]
+    Exact,
+    /// This destination on any port
+    Host,
+    /// This process to any destination
+    Process,
+}
+
+impl ConnectionScope {
+    /// In the order the dialog offers them (keys 1, 2, 3)
+    pub const ALL: [ConnectionScope; 3] = [
+        ConnectionScope::Exact,
+        ConnectionScope::Host,
+        ConnectionScope::Process,
+    ];
+}
+
+// Custom action `n` exits with `ACTION_EXIT_BASE + n`
+const ACTION_EXIT_BASE: i32 = 16;
+
+// Scope `ConnectionScope::ALL[n]` exits with `SCOPE_EXIT_BASE + n`
+const SCOPE_EXIT_BASE: i32 = 10;
+
+impl DialogResult {
+    /// Exit code the dialog binary reports this result with
+    pub(crate) fn exit_code(self) -> i32 {
+        match self {
+            DialogResult::Confirmed => 0,
+            DialogResult::Denied => 1,
+            DialogResult::Timeout => 2,
+            DialogResult::Error => 3,
+            DialogResult::Action(index) => ACTION_EXIT_BASE + index as i32,
+            DialogResult::Cancelled => 5,
+            DialogResult::ConfirmedRemember => 7,
+            DialogResult::DeniedRemember => 8,
+            DialogResult::ConfirmedScope(scope) => SCOPE_EXIT_BASE + scope as i32,
+        }
+    }
+
+    /// Whether the user allowed the request, once, always or for a scope
+    pub fn is_confirmed(self) -> bool {
+        matches!(
+            self,
+            DialogResult::Confirmed
+                | DialogResult::ConfirmedRemember
+                | DialogResult::ConfirmedScope(_)
+        )
+    }
+
+    /// Whether the user asked for the decision to be remembered
+    pub fn remember(self) -> bool {
+        matches!(
+            self,
+            DialogResult::ConfirmedRemember | DialogResult::DeniedRemember
+        )
+    }
+}
+
+/// Structured outcome of a dialog, richer than its exit code
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DialogReport {
+    /// What the user (or timeout) decided
+    pub result: DialogResult,
+    /// How long the dialog was shown before the decision
+    pub elapsed: Duration,
+    /// `DialogConfig::request_id` of the dialog this reports on
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// Per-item results of a batch dialog, in `DialogConfig::batch` order
+    #[serde(default)]
+    pub batch: Vec<DialogResult>,
+    /// Red flags the dialog showed alongside the request
+    #[serde(default)]
+    pub warnings: Vec<RequestWarning>,
+    /// Who or what made the decision
+    ///
+    /// Anything but `User` is an automated decision: don't remember it as
+    /// the user's choice.
+    #[serde(default)]
+    pub source: DecisionSource,
+    /// Whether the user pressed a key or clicked before the decision
+    ///
+    /// On a timeout this means the user was present, so callers may
+    /// re-prompt rather than treat it as a hard deny. Modifier keys alone
+    /// don't count, and nothing typed is ever reported.
+    #[serde(default)]
+    pub interacted: bool,
+}
+
+impl DialogReport {
+    /// Whether the timeout decided, rather than the user or the caller
+    pub fn timed_out(&self) -> bool {
+        self.source == DecisionSource::Timeout
+    }
+}
+
+/// Where a dialog's decision came from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DecisionSource {
+    /// The user answered the prompt
+    #[default]
+    User,
+    /// The timeout expired
+    Timeout,
+    /// A `Policy` decided without a prompt (see `Decision::report`)
+    Policy,
+    /// The environment or caller decided: `SESSION_DIALOG_FORCE_DENY`,
+    /// a test auto-result, SIGTERM/SIGINT or `DialogTask::cancel`
+    Forced,
+    /// Too many prompts; for daemons that throttle requests themselves,
+    /// never set by this crate
+    RateLimited,
+}
+
+impl DecisionSource {
+    /// Best guess from the result alone, for dialogs that sent no report
+    pub(crate) fn inferred(result: DialogResult) -> Self {
+        match result {
+            DialogResult::Timeout => DecisionSource::Timeout,
+            _ => DecisionSource::User,
+        }
+    }
+}
+
+/// A red flag in a request, found by heuristics and shown to the user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RequestWarning {
+    /// The command has shell expansions (listed), so what runs may differ
+    ShellExpansion(Vec<String>),
+    /// The command itself runs a privilege-escalation tool (named)
+    NestedEscalation(String),
+    /// The requesting process isn't the one the daemon saw (reason given)
+    ProcessMismatch(String),
+}
+
+/// A key-bound choice shown in the dialog's action row
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DialogAction {
+    /// Text shown next to the key hint (e.g. "Allow for this site only")
+    pub label: String,
+    /// Key name that triggers the action ("Enter", "Escape", "r", "F1", ...)
+    pub key: String,
+    /// Result reported when the action is picked
+    pub result: DialogResult,
+}
+
+impl DialogAction {
+    /// Create an action bound to `key`
+    pub fn new(label: impl Into<String>, key: impl Into<String>, result: DialogResult) -> Self {
+        Self {
+            label: label.into(),
+            key: key.into(),
+            result,
+        }
+    }
 }
 
 /// Type of confirmation dialog to show
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum DialogKind {
     /// Privilege escalation (authd/sudo replacement)
     PrivilegeEscalation {
@@ -63,7 +318,28 @@ pub enum DialogKind {
         /// Port number
         port: u16,
         /// Protocol (TCP/UDP)
-        protocol: String,
+        #[cfg_attr(feature = "schema", schemars(with = "String"))]
+        protocol: Protocol,
+        /// PID that opened the connection, re-checked before the prompt
+        /// shows: if it no longer runs `process_path`, a warning is shown
+        #[serde(default)]
+        pid: Option<u32>,
+        /// SHA-256 of the binary the daemon saw, compared against what the
+        /// PID runs now (only checked when `pid` is set)
+        #[serde(default)]
+        exe_hash: Option<[u8; 32]>,
+        /// Full command line of the process, shown when the user expands
+        /// the details
+        #[serde(default)]
+        cmdline: Option<String>,
+        /// Name of the parent process, when the requester is a helper
+        /// (e.g. a browser's sandboxed child); shown as "via <parent>"
+        #[serde(default)]
+        parent_process: Option<String>,
+        /// Executable names from the parent up to the session leader,
+        /// nearest first; shown as a process tree in the details
+        #[serde(default)]
+        ancestry: Vec<String>,
     },
     /// Generic confirmation
     Generic {
@@ -71,21 +347,813 @@ pub enum DialogKind {
         title: String,
         /// Main message
         message: String,
-        /// Detail/command text
-        detail: String,
+        /// Detail/command text, inline or read from a file by the dialog
+        #[cfg_attr(feature = "schema", schemars(with = "DetailRepr"))]
+        detail: DetailSource,
     },
+    /// Newly attached device asking for access (e.g. a USB stick to mount)
+    DeviceAccess {
+        /// Kernel or udev name (e.g. "sdb1")
+        device_name: String,
+        /// Vendor as reported by the device
+        vendor: String,
+        /// Product as reported by the device
+        product: String,
+        /// Serial number, if the device has one
+        serial: Option<String>,
+        /// What would be done with it (e.g. "mount")
+        action: String,
+    },
+}
+
+/// Where `Generic` detail text comes from
+///
+/// Large details (e.g. a full diff) can be passed as a file path so the
+/// spawn payload stays small. The file is read by the dialog process, so it
+/// must be readable by the user the dialog runs as. Inline text serializes
+/// as a plain string, as `detail` did before files were supported.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "DetailRepr", into = "DetailRepr")]
+pub enum DetailSource {
+    /// The text itself
+    Inline(String),
+    /// A file read when the dialog starts (capped at `MAX_DETAIL_FILE_LEN`)
+    File(PathBuf),
+}
+
+impl From<String> for DetailSource {
+    fn from(text: String) -> Self {
+        DetailSource::Inline(text)
+    }
+}
+
+impl From<&str> for DetailSource {
+    fn from(text: &str) -> Self {
+        DetailSource::Inline(text.to_string())
+    }
+}
+
+// Wire form: a bare string for inline text, `{"File": path}` otherwise
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+enum DetailRepr {
+    Inline(String),
+    Tagged(TaggedDetail),
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+enum TaggedDetail {
+    File(PathBuf),
+}
+
+impl From<DetailRepr> for DetailSource {
+    fn from(repr: DetailRepr) -> Self {
+        match repr {
+            DetailRepr::Inline(text) => DetailSource::Inline(text),
+            DetailRepr::Tagged(TaggedDetail::File(path)) => DetailSource::File(path),
+        }
+    }
+}
+
+impl From<DetailSource> for DetailRepr {
+    fn from(source: DetailSource) -> Self {
+        match source {
+            DetailSource::Inline(text) => DetailRepr::Inline(text),
+            DetailSource::File(path) => DetailRepr::Tagged(TaggedDetail::File(path)),
+        }
+    }
+}
+
+/// Largest detail file the dialog will display, in bytes
+pub const MAX_DETAIL_FILE_LEN: u64 = 256 * 1024;
+
+/// Transport protocol of a network connection
+///
+/// Serialized as a plain string, so payloads from older callers that sent
+/// free-form strings like "tcp" still parse (and are normalized).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Protocol {
+    /// TCP
+    Tcp,
+    /// UDP
+    Udp,
+    /// Anything else, kept verbatim
+    Other(String),
+}
+
+impl From<&str> for Protocol {
+    fn from(s: &str) -> Self {
+        if s.eq_ignore_ascii_case("tcp") {
+            Protocol::Tcp
+        } else if s.eq_ignore_ascii_case("udp") {
+            Protocol::Udp
+        } else {
+            Protocol::Other(s.to_string())
+        }
+    }
+}
+
+impl From<String> for Protocol {
+    fn from(s: String) -> Self {
+        Protocol::from(s.as_str())
+    }
+}
+
+impl std::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Protocol::Tcp => f.write_str("TCP"),
+            Protocol::Udp => f.write_str("UDP"),
+            Protocol::Other(s) => f.write_str(s),
+        }
+    }
+}
+
+impl Serialize for Protocol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Protocol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Protocol::from)
+    }
 }
 
 /// Configuration for a dialog
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DialogConfig {
     /// Type of dialog to show
     pub kind: DialogKind,
     /// Optional timeout in seconds (None = no timeout)
     pub timeout_secs: Option<u32>,
+    /// Seconds to wait for the first rendered frame before denying
+    /// (None = no watchdog)
+    ///
+    /// Only the spawned binary (`show_dialog`) can act on it: it exits with
+    /// code 4. An inline dialog runs in the caller's process, which the
+    /// watchdog won't kill, so there it only logs the stall.
+    #[serde(default)]
+    pub watchdog_secs: Option<u32>,
+    /// Actions offered to the user, in display order (empty = Allow/Deny)
+    ///
+    /// At most 240 actions can be reported back by index.
+    #[serde(default)]
+    pub actions: Vec<DialogAction>,
+    /// Make Escape cancel (`Cancelled`) and bind explicit deny to D
+    ///
+    /// Only affects the default action set. Cancelling means "ask me again
+    /// later", while denying is a decision the daemon may remember.
+    #[serde(default)]
+    pub escape_cancels: bool,
+    /// How often countdowns refresh (None = 100ms when a timeout is set)
+    #[serde(default)]
+    pub tick_interval: Option<Duration>,
+    /// Render `Generic` message/detail with bold, bullets and code spans
+    #[serde(default)]
+    pub rich_text: bool,
+    /// Restart the timeout on every keypress or pointer movement
+    ///
+    /// Turns `timeout_secs` into an inactivity timeout, so a user reading a
+    /// long command isn't auto-denied mid-review.
+    #[serde(default)]
+    pub timeout_resets_on_activity: bool,
+    /// Preset name or custom colors (None = Ayu Dark)
+    #[serde(default)]
+    pub theme: Option<ThemeSelection>,
+    /// Card padding and spacing, for matching a desktop's design density
+    #[serde(default)]
+    pub layout: LayoutConfig,
+    /// Start pulsing the deny hint after this long (None = never)
+    ///
+    /// Purely cosmetic: a middle ground between no timeout and auto-deny.
+    #[serde(default)]
+    pub nag_after: Option<Duration>,
+    /// Count down this many seconds after confirm before unlocking
+    ///
+    /// Escape during the countdown aborts it, so an accidental approval
+    /// can be caught. None = confirm immediately.
+    #[serde(default)]
+    pub confirm_delay_secs: Option<u32>,
+    /// Unix socket the dialog writes its `DialogReport` to (as msgpack)
+    /// before exiting
+    ///
+    /// For callers that listen on a socket of their own; `show_dialog_report`
+    /// doesn't need it. The exit code is reported either way.
+    #[serde(default)]
+    pub result_socket: Option<PathBuf>,
+    /// Personal anti-spoofing token shown in the "Secure prompt" badge
+    #[serde(default)]
+    pub secure_token: Option<SecureToken>,
+    /// Caller's correlation ID, for matching results and logs to requests
+    ///
+    /// Echoed in `DialogReport` and the dialog binary's stderr lines; never
+    /// shown to the user.
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// Let the user press T to remove the timeout for this prompt
+    ///
+    /// For users who need more time to inspect a command; the countdown
+    /// disappears and the dialog waits for an explicit decision.
+    #[serde(default)]
+    pub allow_timeout_cancel: bool,
+    /// Inherited descriptor to write an entered secret to
+    ///
+    /// Set by `show_auth_dialog`, never by hand; see the `auth` module docs.
+    #[serde(default)]
+    pub secret_fd: Option<i32>,
+    /// Rules applied to the detail before it is logged (never on screen)
+    #[serde(default)]
+    pub redact: Vec<RedactRule>,
+    /// Extra font files tried for glyphs the default font lacks
+    ///
+    /// Common Noto CJK and emoji fonts are picked up automatically; list
+    /// others here (e.g. for scripts those don't cover).
+    #[serde(default)]
+    pub fallback_fonts: Vec<PathBuf>,
+    /// Requests listed with per-item choices instead of showing `kind`
+    ///
+    /// Set by `show_dialog_batch`; see there.
+    #[serde(default)]
+    pub batch: Vec<DialogKind>,
+    /// Offer a right-click (or long-press) menu with Copy, Show full path,
+    /// "Why am I seeing this?" and Report
+    ///
+    /// Off by default: the dialog is keyboard-driven and the menu is extra
+    /// surface a user has to understand.
+    #[serde(default)]
+    pub context_menu: bool,
+    /// Program and arguments run when the user picks Report
+    ///
+    /// Not run through a shell. The (redacted) detail and request ID are
+    /// passed in `SESSION_DIALOG_REPORT_DETAIL` and
+    /// `SESSION_DIALOG_REQUEST_ID`; stdin is /dev/null and the secret and
+    /// report pipes are closed. Either way a line is written to stderr.
+    #[serde(default)]
+    pub report_command: Option<Vec<String>>,
+    /// Offer "Allow always" (A) and "Deny always" (N) next to the one-off
+    /// choices in the default action set
+    ///
+    /// These report `ConfirmedRemember`/`DeniedRemember`; remembering is up
+    /// to the caller. Meant for firewall prompts, where asking for every
+    /// connection wears users down.
+    #[serde(default)]
+    pub offer_remember: bool,
+    /// What the timeout decides when it expires
+    #[serde(default)]
+    pub timeout_action: TimeoutAction,
+    /// Key that allows in the default action set (None = Enter)
+    ///
+    /// Named keys as iced names them ("Enter", "F1", "Esc") or a single
+    /// character ("y"). Ignored when `actions` is set.
+    #[serde(default)]
+    pub confirm_key: Option<String>,
+    /// Key that denies in the default action set (None = Esc, or D with
+    /// `escape_cancels`), e.g. for kiosk keyboards without Escape
+    #[serde(default)]
+    pub deny_key: Option<String>,
+    /// Label of a masked field (e.g. "Password", "PIN") the user must fill
+    /// in before confirming
+    ///
+    /// The typed text is handed back by `show_auth_dialog` and nowhere else;
+    /// other entry points discard it. Ignored by batch dialogs.
+    #[serde(default)]
+    pub secret_prompt: Option<String>,
+    /// Picture shown above the title
+    #[serde(default)]
+    pub icon: Option<DialogIcon>,
+    /// Let `show_dialog` ask on the terminal (`show_dialog_tty`) when the
+    /// environment has no `WAYLAND_DISPLAY` or the session lock is
+    /// unavailable, instead of failing
+    #[serde(default)]
+    pub tty_fallback: bool,
+    /// Extra facts shown as a two-column grid under the detail, e.g.
+    /// ("User", "root"), ("Working dir", "/etc")
+    ///
+    /// Display only: `detail()` and the logs keep the single-line form.
+    #[serde(default)]
+    pub detail_rows: Vec<(String, String)>,
+    /// Language of the dialog's built-in text, as a locale ("de",
+    /// "de_DE.UTF-8"); None follows LC_ALL, LC_MESSAGES or LANG
+    ///
+    /// English and German are built in; other languages show English.
+    /// `Generic` titles and messages are shown as given.
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Label of the allowing action in the default action set, e.g.
+    /// "Discard" (None = "Allow", or "Allow once" with `offer_remember`)
+    ///
+    /// Keys are unchanged. Ignored when `actions` is set.
+    #[serde(default)]
+    pub confirm_label: Option<String>,
+    /// Label of the denying action in the default action set, e.g. "Keep"
+    /// (None = "Deny", or "Deny once" with `offer_remember`)
+    #[serde(default)]
+    pub deny_label: Option<String>,
+    /// Let the user choose how widely an allowed connection applies
+    /// (`NetworkConnection` only; keys 1-3)
+    ///
+    /// Allowing then reports `ConfirmedScope` with the chosen scope
+    /// (`Exact` unless changed) instead of `Confirmed`.
+    #[serde(default)]
+    pub offer_scope: bool,
+    /// Darken the lock screen behind the card (None = flat lock color,
+    /// the cheapest to draw)
+    #[serde(default)]
+    pub backdrop: Option<BackdropConfig>,
+    /// Size of text and card relative to the default, e.g. 1.5 for low
+    /// vision (None = 1.0; clamped to 0.5-3.0)
+    ///
+    /// HiDPI needs no setting: the compositor's output scale already
+    /// applies on top of this.
+    #[serde(default)]
+    pub scale: Option<f32>,
+    /// How much the prompt should stand out
+    #[serde(default)]
+    pub priority: DialogPriority,
+    /// Ignore confirming for this long after the prompt appears, so a key
+    /// held or queued for something else can't approve it unseen
+    /// (None = 500ms, Some(0) = off)
+    #[serde(default)]
+    pub min_visible_ms: Option<u32>,
+    /// Also ignore denying during `min_visible_ms` (by default a deny is
+    /// always accepted at once)
+    #[serde(default)]
+    pub min_visible_gates_deny: bool,
+    /// Result of re-checking a network request's `pid`, filled in by
+    /// `show_dialog` while it can still read the process; leave it None
+    /// and the dialog checks by itself
+    #[serde(default)]
+    pub process_check: Option<ProcessCheck>,
+}
+
+/// Whether the process behind a network request still runs the binary the
+/// daemon saw (see `NetworkConnection::pid`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ProcessCheck {
+    /// Same binary, and the same hash if `exe_hash` was given
+    Matches,
+    /// Exited, replaced or running something else; shown as a warning
+    Mismatch(String),
+    /// Couldn't be inspected, e.g. for lack of privileges; only logged
+    Unverified(String),
+}
+
+/// Visual urgency of a prompt
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum DialogPriority {
+    /// The regular card
+    #[default]
+    Normal,
+    /// The card border pulses in the danger color until the first
+    /// keypress, e.g. for a firewall holding up a running download
+    Urgent,
+}
+
+/// Decision taken when `DialogConfig::timeout_secs` runs out
+///
+/// Whatever it decides, the report's `source` is `DecisionSource::Timeout`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum TimeoutAction {
+    /// Report `Timeout`, which callers treat as a deny ("Auto-deny in Ns")
+    #[default]
+    Deny,
+    /// Report `Confirmed` ("Continuing in Ns"), for prompts where going
+    /// ahead is the safe default, e.g. "locking the screen, Esc to stop".
+    /// Privilege escalation and secret prompts deny instead, and
+    /// `validate` rejects it for them.
+    Confirm,
+    /// Report `Timeout` with a neutral countdown ("Closes in Ns"), for
+    /// callers that decide what a timeout means themselves
+    ReturnTimeout,
+}
+
+/// Picture shown above the dialog title
+///
+/// A daemon that always sends the same icon gives users one more thing a
+/// spoofed prompt has to get right. Builtins keep the config small.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum DialogIcon {
+    Shield,
+    Network,
+    Warning,
+    /// PNG (or JPEG) image, e.g. the requesting app's icon (at most
+    /// `MAX_ICON_LEN` bytes)
+    Png(Vec<u8>),
+}
+
+/// Largest `DialogIcon::Png` accepted, in bytes
+///
+/// Icons travel inside the config, so this stays well under
+/// `MAX_CONFIG_LEN`. With `WireFormat::Json` each byte takes up to four
+/// characters, so large icons need the default msgpack format.
+pub const MAX_ICON_LEN: usize = 32 * 1024;
+
+// Where `from_polkit` looks for icons by name, preferred size first
+const ICON_DIRS: &[&str] = &[
+    "/usr/share/icons/hicolor/64x64/apps",
+    "/usr/share/icons/hicolor/48x48/apps",
+    "/usr/share/icons/hicolor/128x128/apps",
+    "/usr/share/pixmaps",
+];
+
+/// Icon for a polkit `icon_name`
+///
+/// The generic freedesktop names polkit actions commonly use map to a
+/// builtin; any other name is looked up as `<name>.png` in `ICON_DIRS`.
+fn polkit_icon(name: &str) -> Option<DialogIcon> {
+    match name {
+        "" => None,
+        "dialog-password" | "security-high" | "security-medium" | "system-lock-screen"
+        | "changes-prevent" | "changes-allow" => Some(DialogIcon::Shield),
+        "network-wired"
+        | "network-wireless"
+        | "network-workgroup"
+        | "network-server"
+        | "preferences-system-network" => Some(DialogIcon::Network),
+        "dialog-warning" | "dialog-error" | "security-low" => Some(DialogIcon::Warning),
+        _ => themed_icon(name, ICON_DIRS),
+    }
+}
+
+/// `<name>.png` from the first of `dirs` that has it, within `MAX_ICON_LEN`
+fn themed_icon(name: &str, dirs: &[&str]) -> Option<DialogIcon> {
+    // A name, never a path: the request may come from an untrusted app
+    if name.contains('/') || name.starts_with('.') {
+        return None;
+    }
+    dirs.iter().find_map(|dir| {
+        let path = PathBuf::from(dir).join(format!("{}.png", name));
+        let meta = std::fs::metadata(&path).ok()?;
+        if !meta.is_file() || meta.len() > MAX_ICON_LEN as u64 {
+            return None;
+        }
+        let bytes = std::fs::read(&path).ok()?;
+        (bytes.len() <= MAX_ICON_LEN).then_some(DialogIcon::Png(bytes))
+    })
+}
+
+/// A phrase or picture the user chose, shown on every genuine prompt
+///
+/// Like a bank's "site key": a spoofing app drawing a fake prompt in an
+/// ordinary window doesn't know the token, so a prompt without it is
+/// suspect. This only holds while the token stays secret:
+///
+/// - Only the system (the daemon, from its own per-user settings) may set
+///   it, never the application requesting the prompt.
+/// - Token files must be owned by the user the dialog runs as and not
+///   readable by group or others (e.g. mode 0600). Files failing that
+///   check are not shown, since other users could have copied them.
+/// - The token is never logged or included in reports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum SecureToken {
+    /// The phrase itself
+    Phrase(String),
+    /// Text file holding the phrase
+    PhraseFile(PathBuf),
+    /// Small PNG or JPEG image (at most `MAX_TOKEN_IMAGE_LEN` bytes)
+    Image(PathBuf),
+}
+
+/// Largest secure token image the dialog will load, in bytes
+pub const MAX_TOKEN_IMAGE_LEN: u64 = 1024 * 1024;
+
+/// Regex-based redaction of logged text, e.g. tokens in a command line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RedactRule {
+    /// Regular expression (`regex` crate syntax) matching what to hide
+    pub pattern: String,
+    /// Text put in place of each match (None = "[REDACTED]")
+    #[serde(default)]
+    pub replacement: Option<String>,
+}
+
+impl RedactRule {
+    /// Replace matches of `pattern` with "[REDACTED]"
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            replacement: None,
+        }
+    }
+}
+
+/// Spacing of the dialog card, in logical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LayoutConfig {
+    /// Padding inside the card
+    pub padding: f32,
+    /// Vertical space between header, detail and actions
+    pub spacing: f32,
+    /// Horizontal gap between actions in the footer
+    pub action_spacing: f32,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            padding: 32.0,
+            spacing: 22.0,
+            action_spacing: 16.0,
+        }
+    }
+}
+
+/// Dimmed backdrop behind the dialog card
+///
+/// The session lock hides the desktop, so there is nothing to blur: `dim`
+/// darkens the lock color around the card and `card_opacity` lets it show
+/// through the card. Both range from 0.0 to 1.0.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BackdropConfig {
+    /// Opacity of the black layer over the lock color
+    pub dim: f32,
+    /// Opacity of the card background
+    pub card_opacity: f32,
+}
+
+impl Default for BackdropConfig {
+    fn default() -> Self {
+        Self {
+            dim: 0.7,
+            card_opacity: 1.0,
+        }
+    }
 }
 
+/// Dialog colors, by preset name or spelled out
+///
+/// Serialized untagged: a bare string is a preset name, anything else a
+/// `ThemeConfig`, so payloads from before presets existed still parse.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ThemeSelection {
+    /// A built-in preset: "ayu-dark", "light", "high-contrast", "nord" or
+    /// "gruvbox"
+    Named(String),
+    /// Custom colors
+    Custom(ThemeConfig),
+}
+
+/// Custom dialog colors as sRGB triples
+///
+/// Borders, insets and muted text are derived from these. A theme whose
+/// text would be illegible is replaced by the default palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ThemeConfig {
+    /// Card background
+    pub background: [u8; 3],
+    /// Title and body text
+    pub text: [u8; 3],
+    /// Detail/command text
+    pub accent: [u8; 3],
+    /// Confirm hints
+    pub success: [u8; 3],
+    /// Deny hints and urgent countdowns
+    pub danger: [u8; 3],
+}
+
+/// Errors that prevent a dialog from producing a user decision
+#[derive(Debug, thiserror::Error)]
+pub enum DialogError {
+    /// The UI never rendered a frame and the watchdog gave up (denied)
+    #[error("dialog rendering stalled")]
+    RenderStalled,
+    /// The dialog binary could not be started
+    #[error("failed to spawn dialog: {0}")]
+    SpawnFailed(#[from] std::io::Error),
+    /// No dialog binary at the path that would be run
+    #[error("dialog binary not found at {}", .0.display())]
+    BinaryNotFound(PathBuf),
+    /// The dialog binary could be replaced by someone else: it isn't owned
+    /// by root or the current user, or is group- or world-writable
+    #[error("refusing to run dialog binary {}: {reason}", .path.display())]
+    UnsafeBinary {
+        /// Canonical path of the binary
+        path: PathBuf,
+        /// What is wrong with it
+        reason: &'static str,
+    },
+    /// The compositor refused the session lock or doesn't implement
+    /// ext-session-lock-v1 (e.g. GNOME), so nothing was shown; a daemon may
+    /// fall back to another prompt
+    #[error("session lock unavailable (compositor {compositor:?} may lack ext-session-lock-v1): {stderr}")]
+    LockUnavailable {
+        /// Best-effort compositor name, see `detect_compositor`
+        compositor: Option<String>,
+        /// Captured stderr of the dialog binary
+        stderr: String,
+    },
+    /// The encoded config exceeds `MAX_CONFIG_LEN`
+    #[error("config too large ({0} bytes)")]
+    ConfigTooLarge(usize),
+    /// The config fails `DialogConfig::validate`, so it wasn't shown
+    #[error("invalid config: {0}")]
+    InvalidConfig(#[from] ConfigError),
+    /// The binary exited with a code this library doesn't know, usually a
+    /// version mismatch between library and binary
+    #[error("dialog protocol error: {0}")]
+    Protocol(String),
+    /// The dialog binary exited with an error; `stderr` explains why
+    #[error("dialog failed (exit code {code:?}, compositor {compositor:?}): {stderr}")]
+    Failed {
+        /// Exit code, or None if killed by a signal
+        code: Option<i32>,
+        /// Captured stderr of the dialog binary
+        stderr: String,
+        /// Best-effort compositor name, see `detect_compositor`
+        compositor: Option<String>,
+    },
+}
+
+impl DialogError {
+    /// Map a dialog binary exit code to the error it reports, if any
+    pub fn from_exit_code(code: i32) -> Option<Self> {
+        match code {
+            4 => Some(DialogError::RenderStalled),
+            LOCK_UNAVAILABLE_EXIT => Some(DialogError::LockUnavailable {
+                compositor: None,
+                stderr: String::new(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Why `DialogConfig::from_bytes_limited` rejected its input
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    /// More bytes than the limit; nothing was decoded
+    #[error("config too large ({0} bytes)")]
+    TooLarge(usize),
+    #[error(transparent)]
+    Msgpack(#[from] rmp_serde::decode::Error),
+}
+
+/// Problems that make a decoded config unusable
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ConfigError {
+    /// A zero timeout would expire before the first frame
+    #[error("timeout_secs must be greater than zero")]
+    ZeroTimeout,
+    /// A zero tick interval would spin the event loop
+    #[error("tick_interval must be greater than zero")]
+    ZeroTickInterval,
+    /// An action has no label or no key
+    #[error("action {index} has an empty {field}")]
+    EmptyAction { index: usize, field: &'static str },
+    /// Two actions share a key, so one of them can never be picked
+    #[error("key {key:?} is bound to more than one action")]
+    DuplicateKey { key: String },
+    /// A custom action index doesn't fit in an exit code
+    #[error("custom action {0} exceeds the maximum of {max}", max = MAX_ACTION_INDEX)]
+    ActionOutOfRange(u8),
+    /// Padding or spacing is negative or not a number
+    #[error("layout values must be finite and non-negative")]
+    InvalidLayout,
+    /// A redaction rule's pattern is not a valid regex
+    #[error("invalid redaction pattern {pattern:?}: {reason}")]
+    InvalidRedactPattern { pattern: String, reason: String },
+    /// A privilege-escalation request with nothing to run
+    #[error("command must not be empty")]
+    EmptyCommand,
+    /// A network connection to port 0, which no connection uses
+    #[error("port must not be zero")]
+    ZeroPort,
+    /// `DialogConfigBuilder::build` without choosing a dialog kind
+    #[error("no dialog kind was set")]
+    MissingKind,
+    /// A `DialogIcon::Png` over `MAX_ICON_LEN`
+    #[error("icon is {0} bytes, more than the maximum of {max}", max = MAX_ICON_LEN)]
+    IconTooLarge(usize),
+    /// `TimeoutAction::Confirm` on a privilege escalation or secret prompt
+    #[error("timeout_action Confirm is not allowed for privilege escalation or secret entry")]
+    ConfirmOnTimeout,
+}
+
+// Exit codes are 8 bits, so `ACTION_EXIT_BASE + n` must stay below 256
+const MAX_ACTION_INDEX: u8 = (255 - ACTION_EXIT_BASE) as u8;
+
 impl DialogConfig {
+    /// Start building a config; see `DialogConfigBuilder`
+    pub fn builder() -> DialogConfigBuilder {
+        DialogConfigBuilder::default()
+    }
+
+    /// Create a config for the given kind with default settings
+    pub fn new(kind: DialogKind) -> Self {
+        Self {
+            kind,
+            timeout_secs: None,
+            watchdog_secs: None,
+            actions: Vec::new(),
+            escape_cancels: false,
+            tick_interval: None,
+            rich_text: false,
+            timeout_resets_on_activity: false,
+            theme: None,
+            layout: LayoutConfig::default(),
+            nag_after: None,
+            confirm_delay_secs: None,
+            result_socket: None,
+            secure_token: None,
+            request_id: None,
+            allow_timeout_cancel: false,
+            secret_fd: None,
+            redact: Vec::new(),
+            fallback_fonts: Vec::new(),
+            batch: Vec::new(),
+            context_menu: false,
+            report_command: None,
+            offer_remember: false,
+            timeout_action: TimeoutAction::Deny,
+            confirm_key: None,
+            deny_key: None,
+            secret_prompt: None,
+            icon: None,
+            tty_fallback: false,
+            detail_rows: Vec::new(),
+            locale: None,
+            confirm_label: None,
+            deny_label: None,
+            offer_scope: false,
+            backdrop: None,
+            scale: None,
+            priority: DialogPriority::Normal,
+            min_visible_ms: None,
+            min_visible_gates_deny: false,
+            process_check: None,
+        }
+    }
+
+    /// Build a config from a polkit authentication request
+    ///
+    /// pkexec requests (`org.freedesktop.policykit.exec`) become
+    /// `PrivilegeEscalation` with the requested command line; every other
+    /// action becomes `Generic` with the action id and its details.
+    /// `icon_name` becomes `icon`: common generic names map to a builtin,
+    /// others are looked up as PNGs in the hicolor theme and
+    /// /usr/share/pixmaps. Names that resolve to nothing leave `icon` unset.
+    pub fn from_polkit(
+        action_id: &str,
+        message: &str,
+        icon_name: &str,
+        details: &HashMap<String, String>,
+    ) -> Self {
+        let icon = polkit_icon(icon_name);
+
+        if action_id == "org.freedesktop.policykit.exec" {
+            if let Some(command) = details
+                .get("command_line")
+                .or_else(|| details.get("program"))
+            {
+                return Self {
+                    icon,
+                    ..Self::new(DialogKind::PrivilegeEscalation {
+                        command: command.clone(),
+                    })
+                };
+            }
+        }
+
+        // polkit.* keys are internal bookkeeping (message, gettext domain)
+        let mut rows: Vec<_> = details
+            .iter()
+            .filter(|(key, _)| !key.starts_with("polkit."))
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect();
+        rows.sort();
+
+        let mut detail = action_id.to_string();
+        for row in rows {
+            detail.push('\n');
+            detail.push_str(&row);
+        }
+
+        Self {
+            icon,
+            ..Self::new(DialogKind::Generic {
+                title: "Authentication Required".into(),
+                message: message.to_string(),
+                detail: detail.into(),
+            })
+        }
+    }
+
     /// Serialize config to msgpack bytes
     pub fn to_bytes(&self) -> Vec<u8> {
         rmp_serde::to_vec(self).expect("serialize config")
@@ -96,12 +1164,168 @@ impl DialogConfig {
         rmp_serde::from_slice(bytes)
     }
 
+    /// Deserialize config from msgpack bytes, refusing more than `max_len`
+    ///
+    /// For untrusted input: the length is checked before anything is
+    /// decoded. The binary uses this with `MAX_CONFIG_LEN`.
+    pub fn from_bytes_limited(bytes: &[u8], max_len: usize) -> Result<Self, DecodeError> {
+        if bytes.len() > max_len {
+            return Err(DecodeError::TooLarge(bytes.len()));
+        }
+        Ok(Self::from_bytes(bytes)?)
+    }
+
+    /// Serialize config to JSON (for debugging and manual invocation)
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("serialize config")
+    }
+
+    /// Deserialize config from JSON
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Length of the base64 `--config` argument this config encodes to
+    ///
+    /// Single argv strings are capped by the kernel (`MAX_ARG_STRLEN`), so
+    /// callers passing `--config` themselves can check it fits. Prefer
+    /// stdin, which `show_dialog` uses: argv is readable by other users.
+    pub fn encoded_arg_len(&self) -> usize {
+        base64_len(self.to_bytes().len())
+    }
+
+    /// Check the config for values the dialog can't honour
+    ///
+    /// Decoding only checks the shape of a payload; this catches configs
+    /// that decode fine but would misbehave once shown.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        match &self.kind {
+            DialogKind::PrivilegeEscalation { command } if command.trim().is_empty() => {
+                return Err(ConfigError::EmptyCommand);
+            }
+            DialogKind::NetworkConnection { port: 0, .. } => return Err(ConfigError::ZeroPort),
+            _ => {}
+        }
+        if self.timeout_secs == Some(0) {
+            return Err(ConfigError::ZeroTimeout);
+        }
+        if self.timeout_action != self.effective_timeout_action() {
+            return Err(ConfigError::ConfirmOnTimeout);
+        }
+        if self.tick_interval.is_some_and(|tick| tick.is_zero()) {
+            return Err(ConfigError::ZeroTickInterval);
+        }
+        if let Some(DialogIcon::Png(bytes)) = &self.icon {
+            if bytes.len() > MAX_ICON_LEN {
+                return Err(ConfigError::IconTooLarge(bytes.len()));
+            }
+        }
+
+        let layout = self.layout;
+        let lengths = [layout.padding, layout.spacing, layout.action_spacing];
+        if lengths.iter().any(|v| !v.is_finite() || *v < 0.0) {
+            return Err(ConfigError::InvalidLayout);
+        }
+
+        for rule in &self.redact {
+            if let Err(e) = regex::Regex::new(&rule.pattern) {
+                return Err(ConfigError::InvalidRedactPattern {
+                    pattern: rule.pattern.clone(),
+                    reason: e.to_string(),
+                });
+            }
+        }
+
+        // The effective set, so clashing confirm_key/deny_key are caught too
+        let actions = self.actions();
+        for (index, action) in actions.iter().enumerate() {
+            if action.label.trim().is_empty() {
+                return Err(ConfigError::EmptyAction {
+                    index,
+                    field: "label",
+                });
+            }
+            if action.key.trim().is_empty() {
+                return Err(ConfigError::EmptyAction {
+                    index,
+                    field: "key",
+                });
+            }
+            if let DialogResult::Action(n) = action.result {
+                if n > MAX_ACTION_INDEX {
+                    return Err(ConfigError::ActionOutOfRange(n));
+                }
+            }
+            let duplicate = actions[..index]
+                .iter()
+                .any(|earlier| earlier.key.eq_ignore_ascii_case(&action.key));
+            if duplicate {
+                return Err(ConfigError::DuplicateKey {
+                    key: action.key.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// What the timeout decides when it expires
+    ///
+    /// `timeout_action`, except that `Confirm` becomes `Deny` for privilege
+    /// escalation and secret prompts: nobody at the screen must never
+    /// approve those, even when the config was never `validate`d.
+    pub fn effective_timeout_action(&self) -> TimeoutAction {
+        let escalation = matches!(self.kind, DialogKind::PrivilegeEscalation { .. });
+        match self.timeout_action {
+            TimeoutAction::Confirm if escalation || self.secret_prompt.is_some() => {
+                TimeoutAction::Deny
+            }
+            action => action,
+        }
+    }
+
+    /// Get the actions to offer, falling back to Enter=Allow / Esc=Deny
+    /// (or `confirm_key`/`deny_key` and `confirm_label`/`deny_label`)
+    pub fn actions(&self) -> Vec<DialogAction> {
+        if !self.actions.is_empty() {
+            return self.actions.clone();
+        }
+        let confirm = self.confirm_key.as_deref().unwrap_or("Enter");
+        if self.offer_remember {
+            let deny = self.deny_key.as_deref().unwrap_or("Esc");
+            let allow_label = self.confirm_label.as_deref().unwrap_or("Allow once");
+            let deny_label = self.deny_label.as_deref().unwrap_or("Deny once");
+            return vec![
+                DialogAction::new(allow_label, confirm, DialogResult::Confirmed),
+                DialogAction::new("Allow always", "a", DialogResult::ConfirmedRemember),
+                DialogAction::new(deny_label, deny, DialogResult::Denied),
+                DialogAction::new("Deny always", "n", DialogResult::DeniedRemember),
+            ];
+        }
+        let allow_label = self.confirm_label.as_deref().unwrap_or("Allow");
+        let deny_label = self.deny_label.as_deref().unwrap_or("Deny");
+        if self.escape_cancels {
+            let deny = self.deny_key.as_deref().unwrap_or("d");
+            return vec![
+                DialogAction::new(allow_label, confirm, DialogResult::Confirmed),
+                DialogAction::new(deny_label, deny, DialogResult::Denied),
+                DialogAction::new("Cancel", "Esc", DialogResult::Cancelled),
+            ];
+        }
+        let deny = self.deny_key.as_deref().unwrap_or("Esc");
+        vec![
+            DialogAction::new(allow_label, confirm, DialogResult::Confirmed),
+            DialogAction::new(deny_label, deny, DialogResult::Denied),
+        ]
+    }
+
     /// Get the title for this dialog kind
     pub fn title(&self) -> &str {
         match &self.kind {
             DialogKind::PrivilegeEscalation { .. } => "Authorization Required",
             DialogKind::NetworkConnection { .. } => "Network Connection Request",
             DialogKind::Generic { title, .. } => title,
+            DialogKind::DeviceAccess { .. } => "Device Access Request",
         }
     }
 
@@ -111,9 +1335,35 @@ impl DialogConfig {
             DialogKind::PrivilegeEscalation { .. } => "An application wants to run as root:",
             DialogKind::NetworkConnection { .. } => "An application wants to connect to:",
             DialogKind::Generic { message, .. } => message,
+            DialogKind::DeviceAccess { .. } => "A newly connected device wants access:",
         }
     }
 
+    /// Red flags in the request, as shown in the dialog
+    pub fn warnings(&self) -> Vec<RequestWarning> {
+        let DialogKind::PrivilegeEscalation { command } = &self.kind else {
+            return Vec::new();
+        };
+        if !self.batch.is_empty() {
+            return Vec::new();
+        }
+
+        let mut warnings = Vec::new();
+        let expansions = heuristics::shell_expansions(command);
+        if !expansions.is_empty() {
+            warnings.push(RequestWarning::ShellExpansion(expansions));
+        }
+        if let Some(tool) = heuristics::nested_escalation(command) {
+            warnings.push(RequestWarning::NestedEscalation(tool.to_string()));
+        }
+        warnings
+    }
+
+    /// The detail text with `redact` rules applied, for logs and audits
+    pub fn log_detail(&self) -> String {
+        redact::redact(&self.detail(), &self.redact)
+    }
+
     /// Get the detail text (command, connection info, etc.)
     pub fn detail(&self) -> String {
         match &self.kind {
@@ -123,21 +1373,255 @@ impl DialogConfig {
                 destination,
                 port,
                 protocol,
+                parent_process,
+                ancestry,
                 ..
-            } => format!("{} → {}:{} ({})", process, destination, port, protocol),
-            DialogKind::Generic { detail, .. } => detail.clone(),
+            } => {
+                let process = match parent_process.as_ref().or(ancestry.first()) {
+                    Some(parent) => format!("{} (via {})", process, parent),
+                    None => process.clone(),
+                };
+                match heuristics::port_hint(*port, protocol) {
+                    Some(hint) => format!(
+                        "{} → {}:{} ({}, {})",
+                        process, destination, port, protocol, hint
+                    ),
+                    None => format!("{} → {}:{} ({})", process, destination, port, protocol),
+                }
+            }
+            DialogKind::Generic { detail, .. } => match detail {
+                DetailSource::Inline(text) => text.clone(),
+                DetailSource::File(path) => format!("(details in {})", path.display()),
+            },
+            DialogKind::DeviceAccess {
+                device_name,
+                vendor,
+                product,
+                serial,
+                action,
+            } => {
+                let mut detail = format!("{} {} ({})", vendor, product, device_name);
+                if let Some(serial) = serial {
+                    detail.push_str(&format!("\nSerial: {}", serial));
+                }
+                detail.push_str(&format!("\nAction: {}", action));
+                detail
+            }
         }
     }
 }
 
+/// JSON Schema describing `DialogConfig`, pretty-printed
+///
+/// Lets non-Rust daemons build and validate config payloads. Only
+/// available with the `schema` feature.
+#[cfg(feature = "schema")]
+pub fn schema() -> String {
+    serde_json::to_string_pretty(&schemars::schema_for!(DialogConfig)).expect("serialize schema")
+}
+
 /// Wayland environment variables needed for dialog
 pub const WAYLAND_ENV_VARS: &[&str] = &[
     "WAYLAND_DISPLAY",
     "XDG_RUNTIME_DIR",
     "XDG_SESSION_TYPE",
     "DBUS_SESSION_BUS_ADDRESS",
+    // Cursor theme for the lock surface's pointer (see `ui::configure_cursor`)
+    "XCURSOR_THEME",
+    "XCURSOR_SIZE",
+    // The session's language, for `DialogConfig::locale`
+    "LC_ALL",
+    "LC_MESSAGES",
+    "LANG",
 ];
 
+/// Variables a dialog can't connect without
+const REQUIRED_ENV_VARS: &[&str] = &["WAYLAND_DISPLAY", "XDG_RUNTIME_DIR"];
+
+/// `WAYLAND_ENV_VARS` as set in this process, for dialogs in its own session
+pub fn current_wayland_env() -> HashMap<String, String> {
+    WAYLAND_ENV_VARS
+        .iter()
+        .filter_map(|&key| std::env::var(key).ok().map(|val| (key.to_string(), val)))
+        .collect()
+}
+
+/// Check `env` has the variables a dialog needs to connect
+///
+/// Lets callers report "no WAYLAND_DISPLAY" up front instead of a failed
+/// dialog.
+pub fn wayland_env_from(env: &HashMap<String, String>) -> Result<(), MissingVars> {
+    let missing: Vec<_> = REQUIRED_ENV_VARS
+        .iter()
+        .copied()
+        .filter(|key| env.get(*key).is_none_or(|val| val.is_empty()))
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(MissingVars(missing))
+    }
+}
+
+/// Required Wayland variables absent from an environment
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("missing environment variables: {}", .0.join(", "))]
+pub struct MissingVars(pub Vec<&'static str>);
+
+/// Best-effort name of the running Wayland compositor, for diagnostics
+///
+/// Checks compositor-specific sockets first (Hyprland, sway, niri), then
+/// `XDG_CURRENT_DESKTOP` / `XDG_SESSION_DESKTOP`.
+pub fn detect_compositor(env: &HashMap<String, String>) -> Option<String> {
+    const SOCKETS: &[(&str, &str)] = &[
+        ("HYPRLAND_INSTANCE_SIGNATURE", "Hyprland"),
+        ("SWAYSOCK", "sway"),
+        ("NIRI_SOCKET", "niri"),
+    ];
+
+    if let Some((_, name)) = SOCKETS.iter().find(|(key, _)| env.contains_key(*key)) {
+        return Some(name.to_string());
+    }
+
+    ["XDG_CURRENT_DESKTOP", "XDG_SESSION_DESKTOP"]
+        .iter()
+        .filter_map(|key| env.get(*key))
+        .filter_map(|val| val.split(':').next())
+        .find(|name| !name.is_empty())
+        .map(str::to_string)
+}
+
+/// Environment variable that forces every dialog to deny (set to "1")
+pub const FORCE_DENY_ENV: &str = "SESSION_DIALOG_FORCE_DENY";
+
+/// Environment variable holding a pre-supplied result (test mode only)
+pub const AUTO_RESULT_ENV: &str = "SESSION_DIALOG_AUTO_RESULT";
+
+/// Environment variable giving a timeout (seconds) for legacy invocations
+pub const LEGACY_TIMEOUT_ENV: &str = "SESSION_DIALOG_LEGACY_TIMEOUT";
+
+/// Environment variable choosing the exit for malformed configs
+///
+/// "error" exits 3 (`DialogResult::Error`); anything else, or unset, exits
+/// 1 (`DialogResult::Denied`).
+pub const MALFORMED_CONFIG_ENV: &str = "SESSION_DIALOG_MALFORMED";
+
+/// Environment variable naming the dialog binary `show_dialog` runs
+///
+/// Read from the daemon's own environment, never from the session `env`
+/// passed to `show_dialog`. See `SpawnOptions::binary` for the order.
+pub const BINARY_ENV: &str = "SESSION_DIALOG_BIN";
+
+/// Descriptor `show_dialog_report` hands the dialog for its JSON report
+pub const RESULT_FD: RawFd = 4;
+
+/// Environment variable that must be "1" for `AUTO_RESULT_ENV` to apply
+pub const TEST_MODE_ENV: &str = "SESSION_DIALOG_TEST";
+
+/// Largest encoded config `show_dialog` hands to the binary, in bytes
+///
+/// The binary refuses larger input with exit code 3. Ample for any real
+/// request (an icon is at most `MAX_ICON_LEN`), small enough that a caller
+/// can't make the dialog allocate and lay out megabytes of text.
+pub const MAX_CONFIG_LEN: usize = 64 * 1024;
+
+// Exit code of a dialog that couldn't take the session lock
+pub(crate) const LOCK_UNAVAILABLE_EXIT: i32 = 9;
+
+// Signal asking a spawned dialog to close with `Cancelled`
+pub(crate) const CANCEL_SIGNAL: i32 = libc::SIGUSR1;
+
+/// Largest base64 config worth passing as `--config` on argv
+///
+/// `show_dialog` always uses stdin now; this is for callers that still
+/// build `--config` invocations themselves (which leaks the config to
+/// other local users, see the binary's docs).
+pub const MAX_CONFIG_ARG_LEN: usize = 32 * 1024;
+
+/// Encoding used to hand the config to the dialog binary
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WireFormat {
+    /// Compact msgpack
+    #[default]
+    MsgPack,
+    /// Human-readable JSON, handy when debugging payloads
+    Json,
+}
+
+/// Options controlling how `show_dialog` spawns the dialog binary
+#[derive(Debug, Clone, Default)]
+pub struct SpawnOptions {
+    /// Config encoding passed to the binary
+    pub format: WireFormat,
+    /// Retries when the binary can't be started for a transient reason
+    pub retry: RetryPolicy,
+    /// Dialog binary to run
+    ///
+    /// Resolved in order: this path, then `SESSION_DIALOG_BIN`, then
+    /// `session-dialog` next to the current executable, then
+    /// `/usr/bin/session-dialog`. The result is canonicalized and must be
+    /// owned by root or the current user and not group- or world-writable.
+    pub binary: Option<PathBuf>,
+}
+
+/// Bounded retry with exponential backoff for starting the dialog binary
+///
+/// Only spawn failures that may clear up on their own (EAGAIN or ENOMEM
+/// from fork under memory pressure, EINTR) are retried. Once the binary
+/// runs, its exit code is final: a dialog the user answered is never shown
+/// again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total spawn attempts, including the first (0 behaves like 1)
+    pub attempts: u32,
+    /// Wait before the second attempt, doubled for each one after
+    pub initial_backoff: Duration,
+    /// Longest wait between attempts
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Try once and report any failure
+    pub const NONE: RetryPolicy = RetryPolicy {
+        attempts: 1,
+        initial_backoff: Duration::ZERO,
+        max_backoff: Duration::ZERO,
+    };
+
+    /// Run `attempt` until it succeeds, fails permanently or runs out of tries
+    fn run<T>(&self, mut attempt: impl FnMut() -> std::io::Result<T>) -> std::io::Result<T> {
+        let mut backoff = self.initial_backoff;
+        for _ in 1..self.attempts {
+            match attempt() {
+                Err(e) if is_transient(&e) => {
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(self.max_backoff);
+                }
+                other => return other,
+            }
+        }
+        attempt()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+// Spawn errors worth another try
+fn is_transient(error: &std::io::Error) -> bool {
+    matches!(
+        error.raw_os_error(),
+        Some(libc::EAGAIN | libc::ENOMEM | libc::EINTR)
+    )
+}
+
 /// Show a confirmation dialog by spawning the session-dialog binary
 ///
 /// This spawns the dialog binary with dropped privileges (caller's UID/GID)
@@ -151,27 +1635,273 @@ pub const WAYLAND_ENV_VARS: &[&str] = &[
 ///
 /// # Returns
 /// DialogResult indicating user's choice or error
+///
+/// Without `WAYLAND_DISPLAY` in `env`, or on a compositor without
+/// ext-session-lock-v1, this fails, unless `DialogConfig::tty_fallback`
+/// asks on the terminal instead. Use `try_show_dialog` to tell the two
+/// apart from other errors (`DialogError::LockUnavailable`).
 pub fn show_dialog(
     config: &DialogConfig,
     uid: u32,
     gid: u32,
     env: &HashMap<String, String>,
 ) -> DialogResult {
-    // Find session-dialog binary
-    let dialog_bin = std::env::current_exe()
-        .ok()
-        .and_then(|p| p.parent().map(|d| d.join("session-dialog")))
-        .filter(|p| p.exists())
+    if config.tty_fallback && !env.contains_key("WAYLAND_DISPLAY") {
+        return show_dialog_tty(config);
+    }
+    match try_show_dialog(config, uid, gid, env) {
+        Err(DialogError::LockUnavailable { .. }) if config.tty_fallback => show_dialog_tty(config),
+        result => flatten_result(result),
+    }
+}
+
+/// Show a confirmation dialog, reporting why it failed on error
+///
+/// Same as `show_dialog`, but the dialog binary's stderr is captured and
+/// returned in `DialogError::Failed` (e.g. "failed to parse config", with
+/// `SESSION_DIALOG_MALFORMED=error`).
+pub fn try_show_dialog(
+    config: &DialogConfig,
+    uid: u32,
+    gid: u32,
+    env: &HashMap<String, String>,
+) -> Result<DialogResult, DialogError> {
+    try_show_dialog_with(config, uid, gid, env, &SpawnOptions::default())
+}
+
+/// Same as `try_show_dialog`, with explicit spawn options
+pub fn try_show_dialog_with(
+    config: &DialogConfig,
+    uid: u32,
+    gid: u32,
+    env: &HashMap<String, String>,
+    options: &SpawnOptions,
+) -> Result<DialogResult, DialogError> {
+    let output = spawn_dialog(config, uid, gid, env, options, Inherit::None)?.wait_with_output()?;
+    result_from_output(output.status, &output.stderr, detect_compositor(env))
+}
+
+/// Show a confirmation dialog, returning the raw exit code with the result
+///
+/// For callers with their own exit-code conventions: the code is None if
+/// the binary couldn't be spawned or was killed by a signal, and unknown
+/// codes map to `DialogResult::Error` as in `show_dialog`.
+pub fn show_dialog_raw(
+    config: &DialogConfig,
+    uid: u32,
+    gid: u32,
+    env: &HashMap<String, String>,
+) -> (Option<i32>, DialogResult) {
+    let output = spawn_dialog(
+        config,
+        uid,
+        gid,
+        env,
+        &SpawnOptions::default(),
+        Inherit::None,
+    )
+    .and_then(|child| Ok(child.wait_with_output()?));
+    match output {
+        Ok(output) => {
+            let result = result_from_output(output.status, &output.stderr, detect_compositor(env));
+            (output.status.code(), flatten_result(result))
+        }
+        Err(e) => (None, flatten_result(Err(e))),
+    }
+}
+
+/// Show a confirmation dialog and read back its full `DialogReport`
+///
+/// The dialog writes the report as JSON to a pipe it inherits as
+/// `RESULT_FD` (`--result-fd`) before exiting. If it exits without
+/// reporting (e.g. an older binary), the report is rebuilt from the exit
+/// code with the elapsed time measured here.
+pub fn show_dialog_report(
+    config: &DialogConfig,
+    uid: u32,
+    gid: u32,
+    env: &HashMap<String, String>,
+) -> Result<DialogReport, DialogError> {
+    use std::io::Read;
+
+    let (read_end, write_end) = auth::cloexec_pipe()?;
+    let started = Instant::now();
+    let mut child = spawn_dialog(
+        config,
+        uid,
+        gid,
+        env,
+        &SpawnOptions::default(),
+        Inherit::ResultFd(write_end.as_raw_fd()),
+    )?;
+    // Only the child may hold the write end, or we'd never see EOF
+    drop(write_end);
+
+    let stderr = drain_stderr(&mut child);
+    let mut bytes = Vec::new();
+    let read = std::fs::File::from(read_end).read_to_end(&mut bytes);
+    let status = child.wait()?;
+    let stderr = stderr.join().unwrap_or_default();
+    let result = result_from_output(status, &stderr, detect_compositor(env))?;
+    read?;
+
+    if bytes.is_empty() {
+        return Ok(DialogReport {
+            result,
+            elapsed: started.elapsed(),
+            request_id: config.request_id.clone(),
+            batch: Vec::new(),
+            warnings: config.warnings(),
+            source: DecisionSource::inferred(result),
+            interacted: false,
+        });
+    }
+    serde_json::from_slice(&bytes)
+        .map_err(|e| DialogError::Protocol(format!("malformed result report: {}", e)))
+}
+
+/// Show several requests in one dialog and decide each of them
+///
+/// The user picks Allow/Deny per item, or for all remaining items at once.
+/// Returns one result per config, in input order. Display settings
+/// (timeout, theme, ...) come from the first config.
+pub fn show_dialog_batch(
+    configs: &[DialogConfig],
+    uid: u32,
+    gid: u32,
+    env: &HashMap<String, String>,
+) -> Vec<DialogResult> {
+    match configs {
+        [] => Vec::new(),
+        [config] => vec![show_dialog(config, uid, gid, env)],
+        [first, ..] => {
+            let config = DialogConfig {
+                batch: configs.iter().map(|c| c.kind.clone()).collect(),
+                ..first.clone()
+            };
+            batch_results(show_dialog_report(&config, uid, gid, env), configs.len())
+        }
+    }
+}
+
+// Per-item results, or the overall result for every item if the dialog
+// ended without per-item choices (cancelled, signalled, failed)
+fn batch_results(report: Result<DialogReport, DialogError>, count: usize) -> Vec<DialogResult> {
+    match report {
+        Ok(report) if report.batch.len() == count => report.batch,
+        Ok(report) => vec![report.result; count],
+        Err(e) => vec![flatten_result(Err(e)); count],
+    }
+}
+
+/// Show a confirmation dialog as a future
+///
+/// The returned `DialogFuture` resolves when the dialog process exits, so
+/// async daemons can `select!` it against a timeout or cancellation
+/// without `spawn_blocking`. See `DialogFuture` for platform notes.
+pub fn show_dialog_future(
+    config: &DialogConfig,
+    uid: u32,
+    gid: u32,
+    env: &HashMap<String, String>,
+) -> DialogFuture {
+    DialogFuture::new(
+        spawn_dialog(
+            config,
+            uid,
+            gid,
+            env,
+            &SpawnOptions::default(),
+            Inherit::None,
+        )
+        .ok(),
+        detect_compositor(env),
+    )
+}
+
+/// Start a dialog binary that shows many prompts under one session lock
+///
+/// For bursts of requests, e.g. a firewall seeing many connections at
+/// once: instead of a process and a lock per prompt, each
+/// `DialogStream::show` swaps the next config into the prompt already on
+/// screen. Nothing is locked before the first `show`; between prompts the
+/// lock stays up with an empty screen until the stream is closed, so close
+/// it once the burst is over. `tty_fallback` doesn't apply.
+pub fn open_dialog_stream(
+    uid: u32,
+    gid: u32,
+    env: &HashMap<String, String>,
+) -> Result<DialogStream, DialogError> {
+    let options = SpawnOptions::default();
+    let mut command = dialog_command(uid, gid, env, &options)?;
+    command
+        .arg("--stream")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    let child = options.retry.run(|| command.spawn())?;
+    Ok(DialogStream::new(child, detect_compositor(env)))
+}
+
+// The binary `spawn_dialog` runs, in the order documented on
+// `SpawnOptions::binary`
+fn dialog_binary(options: &SpawnOptions) -> Result<PathBuf, DialogError> {
+    use std::os::unix::fs::MetadataExt;
+
+    let path = options
+        .binary
+        .clone()
+        .or_else(|| std::env::var_os(BINARY_ENV).map(PathBuf::from))
+        .or_else(|| {
+            std::env::current_exe()
+                .ok()
+                .and_then(|p| p.parent().map(|d| d.join("session-dialog")))
+                .filter(|p| p.exists())
+        })
         .unwrap_or_else(|| PathBuf::from("/usr/bin/session-dialog"));
+    let Ok(path) = path.canonicalize() else {
+        return Err(DialogError::BinaryNotFound(path));
+    };
+
+    // The daemon usually runs as root: a binary others can swap out would
+    // let them answer (or read) every prompt
+    let meta = std::fs::metadata(&path)?;
+    // SAFETY: geteuid has no preconditions
+    let euid = unsafe { libc::geteuid() };
+    let reason = if !meta.is_file() {
+        Some("not a regular file")
+    } else if meta.uid() != 0 && meta.uid() != euid {
+        Some("not owned by root or the current user")
+    } else if meta.mode() & 0o022 != 0 {
+        Some("group- or world-writable")
+    } else {
+        None
+    };
+    match reason {
+        Some(reason) => Err(DialogError::UnsafeBinary { path, reason }),
+        None => Ok(path),
+    }
+}
 
-    // Encode config as base64 msgpack
-    let config_bytes = config.to_bytes();
-    let config_b64 = base64_encode(&config_bytes);
+/// Pipe end a spawned dialog inherits, at the descriptor it expects
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Inherit {
+    None,
+    /// Write end for the secret, moved to `SECRET_FD`
+    SecretFd(RawFd),
+    /// Write end for the JSON report, moved to `RESULT_FD`
+    ResultFd(RawFd),
+}
 
-    // Spawn dialog with dropped privileges
-    let result = Command::new(&dialog_bin)
-        .arg("--config")
-        .arg(&config_b64)
+// The dialog binary, to run with dropped privileges, the session's Wayland
+// variables and its stderr captured
+fn dialog_command(
+    uid: u32,
+    gid: u32,
+    env: &HashMap<String, String>,
+    options: &SpawnOptions,
+) -> Result<Command, DialogError> {
+    let mut command = Command::new(dialog_binary(options)?);
+    command
         .uid(uid)
         .gid(gid)
         .envs(
@@ -179,32 +1909,221 @@ pub fn show_dialog(
                 .iter()
                 .filter_map(|&key| env.get(key).map(|val| (key, val))),
         )
-        .status();
+        .stderr(Stdio::piped());
+    Ok(command)
+}
+
+// `config` with the requesting process checked, unless the caller did
+//
+// The dialog runs as the session user, who usually can't read the
+// requesting process's /proc/<pid>/exe, so it is checked from here.
+fn with_process_check(config: &DialogConfig) -> Cow<'_, DialogConfig> {
+    match procinfo::check(&config.kind) {
+        Some(check) if config.process_check.is_none() => Cow::Owned(DialogConfig {
+            process_check: Some(check),
+            ..config.clone()
+        }),
+        _ => Cow::Borrowed(config),
+    }
+}
+
+// Spawn the dialog binary with dropped privileges and hand it the config
+fn spawn_dialog(
+    config: &DialogConfig,
+    uid: u32,
+    gid: u32,
+    env: &HashMap<String, String>,
+    options: &SpawnOptions,
+    inherit: Inherit,
+) -> Result<Child, DialogError> {
+    // The binary would refuse it too, but e.g. an out-of-range action
+    // must not get as far as an exit code it could wrap around
+    config.validate()?;
+    let mut command = dialog_command(uid, gid, env, options)?;
+    let config = with_process_check(config);
+
+    // The config always goes over stdin: argv is world-readable through
+    // /proc/<pid>/cmdline, and it holds exactly what is being authorized
+    command.arg("--config-stdin").stdin(Stdio::piped());
+    let config_bytes = match options.format {
+        WireFormat::MsgPack => config.to_bytes(),
+        WireFormat::Json => {
+            command.arg("--json");
+            config.to_json().into_bytes()
+        }
+    };
+    if config_bytes.len() > MAX_CONFIG_LEN {
+        return Err(DialogError::ConfigTooLarge(config_bytes.len()));
+    }
+
+    let moves = match inherit {
+        Inherit::None => None,
+        Inherit::SecretFd(fd) => Some((fd, auth::SECRET_FD)),
+        Inherit::ResultFd(fd) => {
+            command.args(["--result-fd", &RESULT_FD.to_string()]);
+            Some((fd, RESULT_FD))
+        }
+    };
+    if let Some((fd, target)) = moves {
+        // SAFETY: dup2 and fcntl are async-signal-safe
+        unsafe {
+            command.pre_exec(move || {
+                // dup2 onto itself would keep close-on-exec set
+                let moved = if fd == target {
+                    libc::fcntl(fd, libc::F_SETFD, 0)
+                } else {
+                    libc::dup2(fd, target)
+                };
+                if moved < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    let mut child = options.retry.run(|| command.spawn())?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A child that dies before reading reports via its exit code;
+        // dropping `stdin` closes it so the child sees EOF
+        let _ = stdin.write_all(&config_bytes);
+    }
+    Ok(child)
+}
+
+// Read the child's stderr to EOF on a thread, so the child never blocks
+// on a full pipe while we wait on another of its descriptors
+pub(crate) fn drain_stderr(child: &mut Child) -> std::thread::JoinHandle<Vec<u8>> {
+    use std::io::Read;
+
+    let pipe = child.stderr.take();
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+// Interpret the dialog binary's exit status and captured stderr
+fn result_from_output(
+    status: ExitStatus,
+    stderr: &[u8],
+    compositor: Option<String>,
+) -> Result<DialogResult, DialogError> {
+    match status.code().and_then(DialogError::from_exit_code) {
+        Some(DialogError::LockUnavailable { .. }) => {
+            return Err(DialogError::LockUnavailable {
+                compositor,
+                stderr: String::from_utf8_lossy(stderr).trim().to_string(),
+            })
+        }
+        Some(err) => return Err(err),
+        None => {}
+    }
+    // Cancelled before the binary had installed its handler
+    if status.signal() == Some(CANCEL_SIGNAL) {
+        return Ok(DialogResult::Cancelled);
+    }
+    match status.code().map(result_from_exit_code) {
+        Some(DialogResult::Error) if status.code() != Some(3) => Err(DialogError::Protocol(
+            format!("unknown exit code {}", status.code().unwrap_or_default()),
+        )),
+        Some(DialogResult::Error) | None => Err(DialogError::Failed {
+            code: status.code(),
+            stderr: String::from_utf8_lossy(stderr).trim().to_string(),
+            compositor,
+        }),
+        Some(result) => Ok(result),
+    }
+}
 
+// Flatten errors the way `show_dialog` reports them
+fn flatten_result(result: Result<DialogResult, DialogError>) -> DialogResult {
     match result {
-        Ok(status) => match status.code() {
-            Some(0) => DialogResult::Confirmed,
-            Some(1) => DialogResult::Denied,
-            Some(2) => DialogResult::Timeout,
-            _ => DialogResult::Error,
-        },
+        Ok(result) => result,
+        Err(DialogError::RenderStalled) => DialogResult::Denied,
         Err(_) => DialogResult::Error,
     }
 }
 
+// Map dialog exit codes to results (render stalls fail closed as denied)
+pub(crate) fn result_from_exit_code(code: i32) -> DialogResult {
+    match code {
+        0 => DialogResult::Confirmed,
+        1 | 4 => DialogResult::Denied,
+        // 6 was a timeout after interaction in older binaries
+        2 | 6 => DialogResult::Timeout,
+        5 => DialogResult::Cancelled,
+        7 => DialogResult::ConfirmedRemember,
+        8 => DialogResult::DeniedRemember,
+        10..=12 => {
+            DialogResult::ConfirmedScope(ConnectionScope::ALL[(code - SCOPE_EXIT_BASE) as usize])
+        }
+        ACTION_EXIT_BASE..=255 => DialogResult::Action((code - ACTION_EXIT_BASE) as u8),
+        _ => DialogResult::Error,
+    }
+}
+
 /// Run the dialog UI (called by the binary, not by library users)
 ///
 /// This function takes over the process and displays the session-lock dialog.
-/// It exits with code 0 (confirmed), 1 (denied), 2 (timeout), or 3 (error).
+/// SIGTERM/SIGINT (e.g. the parent daemon dying) deny and release the lock;
+/// SIGUSR1 (`DialogFuture::cancel`) cancels it.
+/// It exits with code 0 (confirmed), 1 (denied), 2 (timeout), 3 (error),
+/// 4 (render stalled, see `DialogConfig::watchdog_secs`), 5 (cancelled),
+/// 7/8 (allowed/denied, remember),
+/// 9 (session lock unavailable), 10-12 (allowed for a `ConnectionScope`)
+/// or 16 + n for custom action n.
 pub fn run_dialog(config: DialogConfig) -> ! {
-    let exit_code = ui::run(config);
+    ui::install_signal_handlers();
+    let exit_code = ui::run(config).code;
     std::process::exit(exit_code);
 }
 
+/// Run the binary's `--stream` mode (called by the binary, not by library users)
+///
+/// Reads configs from stdin and writes a `DialogReport` for each to stdout,
+/// as frames (see `open_dialog_stream`), keeping the session locked from
+/// the first config until stdin closes. Configs that can't be shown are
+/// answered with `DialogResult::Error`. Exits 0 once stdin closes, 4 if
+/// rendering stalls or 9 if the session lock is unavailable.
+/// SIGTERM/SIGINT deny the prompt shown and end the stream; SIGUSR1 only
+/// cancels the prompt shown.
+pub fn run_dialog_stream() -> ! {
+    ui::install_signal_handlers();
+    std::process::exit(ui::run_stream());
+}
+
+/// Render the dialog as a PNG image without locking the session
+///
+/// Draws what `run_dialog` first shows on a `width` x `height` output, with
+/// the software renderer and no Wayland connection, for iterating on
+/// themes and snapshot-testing layouts. Uses the same process-wide state as
+/// inline dialogs, so don't call it while one is showing.
+#[cfg(feature = "preview")]
+pub fn render_preview(config: &DialogConfig, width: u32, height: u32) -> std::io::Result<Vec<u8>> {
+    ui::render_preview(config.clone(), width, height)
+}
+
+/// Same as `run_dialog`, but first writes the `DialogReport` as JSON to
+/// the inherited descriptor `result_fd` (the binary's `--result-fd`)
+///
+/// The descriptor is closed after writing. Exits the same way as
+/// `run_dialog`, so callers that ignore the fd still get the exit code.
+pub fn run_dialog_with_result_fd(config: DialogConfig, result_fd: RawFd) -> ! {
+    ui::install_signal_handlers();
+    let outcome = ui::run(config);
+    ui::write_report(result_fd, &outcome.report);
+    std::process::exit(outcome.code);
+}
+
 /// Show the dialog inline without forking
 ///
-/// Sets the necessary Wayland environment variables and runs the dialog
-/// in the current process. This blocks until the user responds.
+/// Runs the dialog in the current process with the Wayland variables from
+/// `env`. This blocks until the user responds.
 ///
 /// # Arguments
 /// * `config` - Dialog configuration
@@ -213,61 +2132,113 @@ pub fn run_dialog(config: DialogConfig) -> ! {
 /// # Returns
 /// DialogResult indicating user's choice
 ///
+/// # Thread safety
+/// The session-lock backend only reads the Wayland connection from the
+/// process environment, so any variable of `env` that differs from it is
+/// copied into the environment first. Changing the environment while
+/// other threads read it is undefined behaviour; nothing is changed when
+/// the process already runs in the target session (the usual case for
+/// inline dialogs). Multi-threaded daemons prompting other sessions should
+/// use `show_dialog`, which sets the variables on a child process only.
+///
 /// # Note
 /// This works even when running as root, as long as the Wayland env vars are correct.
 pub fn show_dialog_inline(
     config: DialogConfig,
     env: &std::collections::HashMap<String, String>,
 ) -> DialogResult {
-    // Force Wayland backend, skip X11 fallback
-    // SAFETY: We're single-threaded at this point or the caller ensures thread safety
-    unsafe { std::env::set_var("WINIT_UNIX_BACKEND", "wayland") };
+    show_dialog_inline_report(config, env).result
+}
 
-    // Set Wayland env vars
-    for key in WAYLAND_ENV_VARS {
-        if let Some(val) = env.get(*key) {
-            unsafe { std::env::set_var(key, val) };
-        }
-    }
+/// Same as `show_dialog_inline`, returning the full `DialogReport`
+///
+/// The report's `elapsed` is how long the dialog was up before the
+/// decision, measured by the dialog itself.
+pub fn show_dialog_inline_report(
+    config: DialogConfig,
+    env: &std::collections::HashMap<String, String>,
+) -> DialogReport {
+    apply_wayland_env(env);
+    ui::run(config).report
+}
 
-    let exit_code = ui::run(config);
-    match exit_code {
-        0 => DialogResult::Confirmed,
-        1 => DialogResult::Denied,
-        2 => DialogResult::Timeout,
-        _ => DialogResult::Error,
+// Copy the Wayland variables of `env` into the process environment, touching
+// only those that differ (see "Thread safety" on `show_dialog_inline`)
+fn apply_wayland_env(env: &std::collections::HashMap<String, String>) {
+    let wanted = std::iter::once(("WINIT_UNIX_BACKEND", "wayland")).chain(
+        WAYLAND_ENV_VARS
+            .iter()
+            .filter_map(|key| env.get(*key).map(|val| (*key, val.as_str()))),
+    );
+    for (key, val) in wanted {
+        if std::env::var_os(key).is_some_and(|current| current == val) {
+            continue;
+        }
+        // SAFETY: not guaranteed; the caller must not read the environment
+        // from other threads meanwhile, as documented on `show_dialog_inline`
+        unsafe { std::env::set_var(key, val) };
     }
 }
 
 /// Show the dialog in a separate thread
 ///
 /// Spawns a new thread to run the dialog, allowing the caller to continue
-/// other work. Returns a handle that can be joined to get the result.
+/// other work. Returns a handle that can be polled, cancelled or joined.
+/// The environment is set up before the thread starts, with the same
+/// caveats as `show_dialog_inline`.
 ///
 /// # Arguments
 /// * `config` - Dialog configuration
 /// * `env` - Environment variables (must include WAYLAND_DISPLAY, XDG_RUNTIME_DIR)
 ///
 /// # Returns
-/// JoinHandle that resolves to DialogResult
+/// DialogTask that resolves to a DialogReport (see `DialogTask::join_report`)
 pub fn show_dialog_async(
     config: DialogConfig,
     env: std::collections::HashMap<String, String>,
-) -> std::thread::JoinHandle<DialogResult> {
-    std::thread::spawn(move || show_dialog_inline(config, &env))
+) -> DialogTask {
+    ui::enable_cancel();
+    // On the caller's thread, so the dialog thread never changes the
+    // environment itself
+    apply_wayland_env(&env);
+    let request_id = config.request_id.clone();
+    let handle = std::thread::spawn(move || show_dialog_inline_report(config, &env));
+    DialogTask::new(handle, request_id)
+}
+
+// Length of padded base64 output for `len` input bytes
+fn base64_len(len: usize) -> usize {
+    len.div_ceil(3) * 4
 }
 
-// Simple base64 encoding (no external dependency)
-fn base64_encode(data: &[u8]) -> String {
-    use std::io::Write;
-    let mut buf = Vec::new();
-    let mut encoder = Base64Encoder::new(&mut buf);
-    encoder.write_all(data).unwrap();
-    drop(encoder);
-    String::from_utf8(buf).unwrap()
+/// Encode bytes as standard padded base64 (RFC 4648), as `--config` expects
+pub fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(base64_len(data.len()));
+    for chunk in data.chunks(3) {
+        // The group's bytes left-aligned in 24 bits; a short final group
+        // gives 2 or 3 characters and is padded to 4 with '='
+        let group = chunk
+            .iter()
+            .fold(0u32, |acc, &byte| (acc << 8) | byte as u32)
+            << (8 * (3 - chunk.len()));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((group >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
 }
 
 /// Decode base64 string to bytes
+///
+/// ASCII whitespace is ignored, so line-wrapped input decodes, and the
+/// trailing `=` padding is optional. Unused bits of a short final group
+/// must be zero, so every byte string has exactly one accepted encoding.
 pub fn base64_decode(s: &str) -> Result<Vec<u8>, &'static str> {
     fn decode_char(c: u8) -> Result<u8, &'static str> {
         match c {
@@ -276,29 +2247,51 @@ pub fn base64_decode(s: &str) -> Result<Vec<u8>, &'static str> {
             b'0'..=b'9' => Ok(c - b'0' + 52),
             b'+' => Ok(62),
             b'/' => Ok(63),
-            b'=' => Ok(0),
             _ => Err("invalid base64 character"),
         }
     }
 
-    let bytes = s.as_bytes();
+    let mut bytes: Vec<u8> = s.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+    // Padding only ever ends the input, so strip it and decode the short
+    // final group like unpadded input
+    let padding = bytes
+        .iter()
+        .rev()
+        .take(2)
+        .take_while(|&&c| c == b'=')
+        .count();
+    // Padded input must come in whole groups of four
+    if padding > 0 && bytes.len() % 4 != 0 {
+        return Err("invalid base64 padding");
+    }
+    bytes.truncate(bytes.len() - padding);
+    if bytes.len() % 4 == 1 {
+        return Err("invalid base64 length");
+    }
     let mut result = Vec::with_capacity(bytes.len() * 3 / 4);
 
     for chunk in bytes.chunks(4) {
-        if chunk.len() < 4 {
-            return Err("invalid base64 length");
+        let mut group = [0u8; 4];
+        for (slot, &c) in group.iter_mut().zip(chunk) {
+            *slot = decode_char(c)?;
+        }
+        let [a, b, c, d] = group;
+        // The bits a short final group leaves over must be zero (RFC 4648
+        // 3.5), or several inputs would decode to the same bytes
+        let leftover = match chunk.len() {
+            2 => b & 0x0f,
+            3 => c & 0x03,
+            _ => 0,
+        };
+        if leftover != 0 {
+            return Err("invalid base64 trailing bits");
         }
-
-        let a = decode_char(chunk[0])?;
-        let b = decode_char(chunk[1])?;
-        let c = decode_char(chunk[2])?;
-        let d = decode_char(chunk[3])?;
 
         result.push((a << 2) | (b >> 4));
-        if chunk[2] != b'=' {
+        if chunk.len() > 2 {
             result.push((b << 4) | (c >> 2));
         }
-        if chunk[3] != b'=' {
+        if chunk.len() > 3 {
             result.push((c << 6) | d);
         }
     }
@@ -306,58 +2299,654 @@ pub fn base64_decode(s: &str) -> Result<Vec<u8>, &'static str> {
     Ok(result)
 }
 
-struct Base64Encoder<'a> {
-    buf: &'a mut Vec<u8>,
-    pending: u32,
-    pending_bits: u8,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
 
-impl<'a> Base64Encoder<'a> {
-    fn new(buf: &'a mut Vec<u8>) -> Self {
-        Self {
-            buf,
-            pending: 0,
-            pending_bits: 0,
+    fn escalation(command: &str) -> DialogConfig {
+        DialogConfig::new(DialogKind::PrivilegeEscalation {
+            command: command.to_string(),
+        })
+    }
+
+    fn generic() -> DialogConfig {
+        DialogConfig::new(DialogKind::Generic {
+            title: "Lock screen".to_string(),
+            message: "Locking the screen".to_string(),
+            detail: "Esc to stop".into(),
+        })
+    }
+
+    fn network(port: u16, protocol: &str) -> DialogConfig {
+        DialogConfig::new(DialogKind::NetworkConnection {
+            process: "curl".to_string(),
+            process_path: PathBuf::from("/usr/bin/curl"),
+            destination: "example.org".to_string(),
+            port,
+            protocol: protocol.into(),
+            pid: None,
+            exe_hash: None,
+            cmdline: None,
+            parent_process: None,
+            ancestry: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn out_of_range_actions_are_never_spawned() {
+        let mut config = escalation("/usr/bin/pacman -Syu");
+        // 16 + 240 would exit 0, read back as Confirmed
+        config.actions = vec![DialogAction::new("Wrap", "w", DialogResult::Action(240))];
+        let result = try_show_dialog(&config, 0, 0, &HashMap::new());
+        assert!(
+            matches!(
+                result,
+                Err(DialogError::InvalidConfig(ConfigError::ActionOutOfRange(
+                    240
+                )))
+            ),
+            "{:?}",
+            result
+        );
+        assert_eq!(
+            show_dialog(&config, 0, 0, &HashMap::new()),
+            DialogResult::Error
+        );
+    }
+
+    #[test]
+    fn validate_rejects_confirm_on_timeout_where_unsafe() {
+        let mut config = escalation("/usr/bin/pacman -Syu");
+        config.timeout_action = TimeoutAction::Confirm;
+        assert_eq!(config.validate(), Err(ConfigError::ConfirmOnTimeout));
+
+        let mut config = generic();
+        config.timeout_action = TimeoutAction::Confirm;
+        assert_eq!(config.validate(), Ok(()));
+        config.secret_prompt = Some("Password".to_string());
+        assert_eq!(config.validate(), Err(ConfigError::ConfirmOnTimeout));
+    }
+
+    #[test]
+    fn truncated_or_corrupt_msgpack_is_rejected() {
+        let bytes = escalation("/usr/bin/pacman -Syu").to_bytes();
+        for len in [0, 1, bytes.len() / 2, bytes.len() - 1] {
+            assert!(
+                DialogConfig::from_bytes_limited(&bytes[..len], MAX_CONFIG_LEN).is_err(),
+                "decoded {} of {} bytes",
+                len,
+                bytes.len()
+            );
+        }
+        for corrupt in [&b"\xc1"[..], b"\xff\xff\xff\xff", b"{\"kind\":1}"] {
+            assert!(DialogConfig::from_bytes_limited(corrupt, MAX_CONFIG_LEN).is_err());
         }
     }
 
-    fn flush_pending(&mut self) {
-        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
-        while self.pending_bits >= 6 {
-            self.pending_bits -= 6;
-            let idx = ((self.pending >> self.pending_bits) & 0x3F) as usize;
-            self.buf.push(ALPHABET[idx]);
+    #[test]
+    fn truncated_or_corrupt_json_is_rejected() {
+        let json = escalation("/usr/bin/pacman -Syu").to_json();
+        for len in [0, 1, json.len() / 2, json.len() - 1] {
+            assert!(
+                DialogConfig::from_json(&json[..len]).is_err(),
+                "{:?}",
+                &json[..len]
+            );
+        }
+        for corrupt in [
+            "null",
+            "[]",
+            "{\"kind\":\"Shutdown\"}",
+            "{\"timeout_secs\":-1}",
+        ] {
+            assert!(DialogConfig::from_json(corrupt).is_err(), "{:?}", corrupt);
         }
     }
-}
 
-impl std::io::Write for Base64Encoder<'_> {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        for &byte in buf {
-            self.pending = (self.pending << 8) | byte as u32;
-            self.pending_bits += 8;
-            self.flush_pending();
+    #[test]
+    fn base64_rfc4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (plain, encoded) in vectors {
+            assert_eq!(base64_encode(plain.as_bytes()), encoded);
+            assert_eq!(base64_decode(encoded).unwrap(), plain.as_bytes());
         }
-        Ok(buf.len())
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        Ok(())
+    #[test]
+    fn base64_round_trips_every_length() {
+        for len in 0..=16 {
+            // Covers every alphabet character across the lengths
+            let data: Vec<u8> = (0..len)
+                .map(|i| (i * 37 + len * 101) as u8 ^ 0xA5)
+                .collect();
+            let padded = base64_encode(&data);
+            assert_eq!(padded.len(), base64_len(len), "length {}", len);
+            assert_eq!(
+                base64_decode(&padded).unwrap(),
+                data,
+                "padded, length {}",
+                len
+            );
+
+            let unpadded = padded.trim_end_matches('=');
+            assert_eq!(
+                base64_decode(unpadded).unwrap(),
+                data,
+                "unpadded, length {}",
+                len
+            );
+
+            let wrapped: String = padded
+                .chars()
+                .enumerate()
+                .flat_map(|(i, c)| (i % 4 == 3).then_some('\n').into_iter().chain([c]))
+                .collect();
+            assert_eq!(
+                base64_decode(&wrapped).unwrap(),
+                data,
+                "wrapped, length {}",
+                len
+            );
+        }
     }
-}
 
-impl Drop for Base64Encoder<'_> {
-    fn drop(&mut self) {
-        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
-        if self.pending_bits > 0 {
-            let shift = 6 - self.pending_bits;
-            let idx = ((self.pending << shift) & 0x3F) as usize;
-            self.buf.push(ALPHABET[idx]);
-            // Padding
-            let padding = (3 - (self.pending_bits / 8) % 3) % 3;
-            for _ in 0..padding {
-                self.buf.push(b'=');
+    #[test]
+    fn base64_rejects_invalid_characters() {
+        for input in ["Zm9v!A", "Zm-v", "Zm_v", "Zm9vé", "Zm9=vA", "=Zg=", "Zg=A"] {
+            assert_eq!(
+                base64_decode(input),
+                Err("invalid base64 character"),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn base64_rejects_bad_padding() {
+        for input in ["Zg=", "Zg===", "Zm9vYg=", "Zm8==="] {
+            assert!(base64_decode(input).is_err(), "{:?}", input);
+        }
+        // One leftover character can't encode a byte
+        for input in ["Z", "Zm9vY", "Z==="] {
+            assert!(base64_decode(input).is_err(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn base64_rejects_non_zero_trailing_bits() {
+        // "Zg==" and "Zm8=" are the only encodings of "f" and "fo"
+        for input in ["Zh==", "Zv==", "Zh", "Zm9=", "Zm9", "Zm9vYh==", "Zm9vYmF="] {
+            assert_eq!(
+                base64_decode(input),
+                Err("invalid base64 trailing bits"),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn oversized_payload_is_rejected_before_decoding() {
+        // A str32 header claiming 4 GiB, then 1 MiB of filler: decoding it
+        // would fail differently, so TooLarge shows nothing was decoded
+        let mut bytes = vec![0xdb, 0xff, 0xff, 0xff, 0xff];
+        bytes.resize(1024 * 1024 + bytes.len(), b'a');
+        assert!(matches!(
+            DialogConfig::from_bytes_limited(&bytes, MAX_CONFIG_LEN),
+            Err(DecodeError::TooLarge(len)) if len == bytes.len()
+        ));
+    }
+
+    #[test]
+    fn length_bombs_fail_cleanly() {
+        let bombs: [&[u8]; 5] = [
+            // Array and map of 4G elements, with none present
+            &[0xdd, 0xff, 0xff, 0xff, 0xff],
+            &[0xdf, 0xff, 0xff, 0xff, 0xff],
+            // A config whose first field is a 4 GiB string or binary
+            &[0x91, 0xdb, 0xff, 0xff, 0xff, 0xff, b'a'],
+            &[0x91, 0xc6, 0xff, 0xff, 0xff, 0xff, 0],
+            // Lengths nested inside lengths
+            &[0xdd, 0xff, 0xff, 0xff, 0xff, 0xdd, 0xff, 0xff, 0xff, 0xff],
+        ];
+        for bomb in bombs {
+            assert!(DialogConfig::from_bytes_limited(bomb, MAX_CONFIG_LEN).is_err());
+        }
+
+        // Deep nesting must be refused, not overflow the stack
+        let nested = vec![0x91; MAX_CONFIG_LEN];
+        assert!(DialogConfig::from_bytes_limited(&nested, MAX_CONFIG_LEN).is_err());
+    }
+
+    #[test]
+    fn encoded_arg_len_matches_the_encoding() {
+        let mut configs = vec![generic(), escalation("/usr/bin/true")];
+        // Cover every remainder of the msgpack length mod 3
+        for n in 0..3 {
+            configs.push(escalation(&format!(
+                "/usr/bin/rm -rf /tmp/ü{}",
+                "x".repeat(n)
+            )));
+        }
+        configs.push(escalation(&"a".repeat(100_000)));
+        for config in configs {
+            let encoded = base64_encode(&config.to_bytes());
+            assert_eq!(config.encoded_arg_len(), encoded.len());
+        }
+    }
+
+    #[test]
+    fn layout_defaults_to_the_builtin_spacing() {
+        let layout = generic().layout;
+        assert_eq!(layout, LayoutConfig::default());
+        assert_eq!(
+            (layout.padding, layout.spacing, layout.action_spacing),
+            (32.0, 22.0, 16.0)
+        );
+    }
+
+    #[test]
+    fn validate_rejects_negative_or_non_finite_layout() {
+        let setters: [fn(&mut LayoutConfig, f32); 3] = [
+            |layout, v| layout.padding = v,
+            |layout, v| layout.spacing = v,
+            |layout, v| layout.action_spacing = v,
+        ];
+        for bad in [-1.0, f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            for set in setters {
+                let mut config = generic();
+                set(&mut config.layout, bad);
+                assert_eq!(config.validate(), Err(ConfigError::InvalidLayout));
+            }
+        }
+
+        let mut config = generic();
+        config.layout = LayoutConfig {
+            padding: 0.0,
+            spacing: 0.0,
+            action_spacing: 120.0,
+        };
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn protocol_names_are_normalized() {
+        for (name, protocol) in [
+            ("tcp", Protocol::Tcp),
+            ("TCP", Protocol::Tcp),
+            ("Tcp", Protocol::Tcp),
+            ("udp", Protocol::Udp),
+            ("UDP", Protocol::Udp),
+        ] {
+            assert_eq!(Protocol::from(name), protocol);
+        }
+        assert_eq!(Protocol::Tcp.to_string(), "TCP");
+        assert_eq!(Protocol::Udp.to_string(), "UDP");
+        assert_eq!(
+            network(443, "tcp").detail(),
+            "curl → example.org:443 (TCP, HTTPS)"
+        );
+    }
+
+    #[test]
+    fn unknown_protocols_are_kept_verbatim() {
+        for name in ["sctp", "QUIC", "", " tcp"] {
+            let protocol = Protocol::from(name);
+            assert_eq!(protocol, Protocol::Other(name.to_string()));
+            assert_eq!(protocol.to_string(), name);
+        }
+        assert_eq!(network(9, "sctp").detail(), "curl → example.org:9 (sctp)");
+    }
+
+    #[test]
+    fn protocol_strings_from_old_payloads_parse() {
+        for (name, protocol) in [
+            ("tcp", Protocol::Tcp),
+            ("Udp", Protocol::Udp),
+            ("dccp", Protocol::Other("dccp".to_string())),
+        ] {
+            let json = serde_json::to_string(name).unwrap();
+            assert_eq!(serde_json::from_str::<Protocol>(&json).unwrap(), protocol);
+            let bytes = rmp_serde::to_vec(name).unwrap();
+            assert_eq!(rmp_serde::from_slice::<Protocol>(&bytes).unwrap(), protocol);
+        }
+
+        let config = DialogConfig::from_bytes(&network(53, "udp").to_bytes()).unwrap();
+        assert!(matches!(
+            config.kind,
+            DialogKind::NetworkConnection {
+                protocol: Protocol::Udp,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn batch_results_keep_input_order() {
+        use DialogResult::{Confirmed, Denied, Timeout};
+
+        let report = |result, batch: Vec<DialogResult>| DialogReport {
+            result,
+            elapsed: Duration::from_secs(2),
+            request_id: None,
+            batch,
+            warnings: Vec::new(),
+            source: DecisionSource::User,
+            interacted: true,
+        };
+        let per_item = vec![Denied, Confirmed, Denied];
+        assert_eq!(
+            batch_results(Ok(report(Denied, per_item.clone())), 3),
+            per_item
+        );
+        // A dialog that didn't list the items answered for all of them
+        assert_eq!(
+            batch_results(Ok(report(Timeout, Vec::new())), 3),
+            [Timeout; 3]
+        );
+        assert_eq!(
+            batch_results(Ok(report(Confirmed, vec![Confirmed])), 2),
+            [Confirmed; 2]
+        );
+        assert_eq!(
+            batch_results(Err(DialogError::RenderStalled), 2),
+            [Denied; 2]
+        );
+        assert_eq!(
+            batch_results(Err(DialogError::BinaryNotFound(PathBuf::new())), 2),
+            [DialogResult::Error; 2]
+        );
+    }
+
+    fn quick_retry(attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            ..RetryPolicy::NONE
+        }
+    }
+
+    /// A spawn that fails with each of `errors` in turn, then succeeds
+    fn failing_spawn(errors: Vec<i32>) -> (impl FnMut() -> std::io::Result<i32>, Rc<Cell<usize>>) {
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        let spawn = move || {
+            let call = counter.get();
+            counter.set(call + 1);
+            match errors.get(call) {
+                Some(&errno) => Err(std::io::Error::from_raw_os_error(errno)),
+                None => Ok(0),
+            }
+        };
+        (spawn, calls)
+    }
+
+    #[test]
+    fn retry_recovers_from_transient_spawn_errors() {
+        let (spawn, calls) = failing_spawn(vec![libc::EAGAIN, libc::ENOMEM]);
+        assert_eq!(quick_retry(3).run(spawn).unwrap(), 0);
+        assert_eq!(calls.get(), 3);
+
+        let (spawn, calls) = failing_spawn(vec![libc::EINTR]);
+        assert_eq!(quick_retry(5).run(spawn).unwrap(), 0);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn retry_gives_up_after_its_attempts() {
+        let (spawn, calls) = failing_spawn(vec![libc::EAGAIN; 5]);
+        let error = quick_retry(3).run(spawn).unwrap_err();
+        assert_eq!(error.raw_os_error(), Some(libc::EAGAIN));
+        assert_eq!(calls.get(), 3);
+
+        for attempts in [0, 1] {
+            let (spawn, calls) = failing_spawn(vec![libc::EAGAIN]);
+            assert!(quick_retry(attempts).run(spawn).is_err());
+            assert_eq!(calls.get(), 1);
+        }
+    }
+
+    #[test]
+    fn permanent_spawn_errors_are_not_retried() {
+        for errno in [libc::ENOENT, libc::EACCES, libc::ENOEXEC] {
+            let (spawn, calls) = failing_spawn(vec![errno]);
+            let error = quick_retry(3).run(spawn).unwrap_err();
+            assert_eq!(error.raw_os_error(), Some(errno));
+            assert_eq!(calls.get(), 1);
+        }
+    }
+
+    #[test]
+    fn a_dialog_that_ran_is_never_retried() {
+        // Whatever the dialog exited with, the spawn itself worked
+        for code in [0, 1, 2, 3, 4] {
+            let calls = Cell::new(0);
+            let result = quick_retry(3).run(|| {
+                calls.set(calls.get() + 1);
+                Ok(code)
+            });
+            assert_eq!(result.unwrap(), code);
+            assert_eq!(calls.get(), 1);
+        }
+    }
+
+    #[test]
+    fn exit_codes_map_to_results() {
+        use ConnectionScope::{Exact, Host, Process};
+        use DialogResult::*;
+
+        let table = [
+            (0, Confirmed),
+            (1, Denied),
+            (2, Timeout),
+            (3, Error),
+            // A render stall fails closed
+            (4, Denied),
+            (5, Cancelled),
+            // Older binaries' timeout after interaction
+            (6, Timeout),
+            (7, ConfirmedRemember),
+            (8, DeniedRemember),
+            (9, Error),
+            (10, ConfirmedScope(Exact)),
+            (11, ConfirmedScope(Host)),
+            (12, ConfirmedScope(Process)),
+            (13, Error),
+            (15, Error),
+            (16, Action(0)),
+            (17, Action(1)),
+            (16 + 42, Action(42)),
+            (255, Action(239)),
+            (256, Error),
+            (-1, Error),
+        ];
+        for (code, result) in table {
+            assert_eq!(result_from_exit_code(code), result, "exit code {}", code);
+        }
+    }
+
+    #[test]
+    fn results_round_trip_through_exit_codes() {
+        use DialogResult::*;
+
+        let mut results = vec![
+            Confirmed,
+            Denied,
+            Timeout,
+            Error,
+            Cancelled,
+            ConfirmedRemember,
+            DeniedRemember,
+        ];
+        results.extend(ConnectionScope::ALL.map(ConfirmedScope));
+        results.extend([0, 1, 7, 100, 239].map(Action));
+        for result in results {
+            assert_eq!(result_from_exit_code(result.exit_code()), result);
+        }
+    }
+
+    #[test]
+    fn only_stalls_and_missing_locks_are_errors() {
+        assert!(matches!(
+            DialogError::from_exit_code(4),
+            Some(DialogError::RenderStalled)
+        ));
+        assert!(matches!(
+            DialogError::from_exit_code(LOCK_UNAVAILABLE_EXIT),
+            Some(DialogError::LockUnavailable { .. })
+        ));
+        for code in [0, 1, 2, 3, 5, 6, 7, 8, 10, 16, 255] {
+            assert!(DialogError::from_exit_code(code).is_none(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn request_id_survives_both_wire_formats() {
+        for request_id in [None, Some(String::new()), Some("fwd-1234 ü".to_string())] {
+            let mut config = generic();
+            config.request_id = request_id.clone();
+            let decoded = DialogConfig::from_bytes(&config.to_bytes()).unwrap();
+            assert_eq!(decoded.request_id, request_id);
+            let decoded = DialogConfig::from_json(&config.to_json()).unwrap();
+            assert_eq!(decoded.request_id, request_id);
+        }
+    }
+
+    #[test]
+    fn reports_without_request_id_still_parse() {
+        let json = r#"{"result":"Confirmed","elapsed":{"secs":1,"nanos":0}}"#;
+        let report: DialogReport = serde_json::from_str(json).unwrap();
+        assert_eq!(report.request_id, None);
+        assert_eq!(report.result, DialogResult::Confirmed);
+        assert_eq!(report.source, DecisionSource::default());
+    }
+
+    #[test]
+    fn source_is_inferred_from_the_result_alone() {
+        use DialogResult::*;
+
+        let table = [
+            (Confirmed, DecisionSource::User),
+            (Denied, DecisionSource::User),
+            (Timeout, DecisionSource::Timeout),
+            (Error, DecisionSource::User),
+            (Cancelled, DecisionSource::User),
+            (ConfirmedRemember, DecisionSource::User),
+            (DeniedRemember, DecisionSource::User),
+            (ConfirmedScope(ConnectionScope::Host), DecisionSource::User),
+            (Action(2), DecisionSource::User),
+        ];
+        for (result, source) in table {
+            assert_eq!(DecisionSource::inferred(result), source, "{:?}", result);
+        }
+    }
+
+    #[test]
+    fn exit_status_maps_to_cancel_and_errors() {
+        let exited = |code: i32| ExitStatus::from_raw(code << 8);
+        let killed = |signal: i32| ExitStatus::from_raw(signal);
+
+        assert!(matches!(
+            result_from_output(exited(5), b"", None),
+            Ok(DialogResult::Cancelled)
+        ));
+        // Killed by the cancel signal before its handler was installed
+        assert!(matches!(
+            result_from_output(killed(CANCEL_SIGNAL), b"", None),
+            Ok(DialogResult::Cancelled)
+        ));
+        assert!(matches!(
+            result_from_output(killed(libc::SIGKILL), b"", None),
+            Err(DialogError::Failed { code: None, .. })
+        ));
+        match result_from_output(exited(3), b"failed to parse config\n", None) {
+            Err(DialogError::Failed { code, stderr, .. }) => {
+                assert_eq!(code, Some(3));
+                assert_eq!(stderr, "failed to parse config");
             }
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(
+            result_from_output(exited(13), b"", None),
+            Err(DialogError::Protocol(_))
+        ));
+        assert!(matches!(
+            result_from_output(exited(4), b"", None),
+            Err(DialogError::RenderStalled)
+        ));
+        let compositor = Some("gnome".to_string());
+        match result_from_output(exited(LOCK_UNAVAILABLE_EXIT), b"no lock\n", compositor) {
+            Err(DialogError::LockUnavailable { compositor, stderr }) => {
+                assert_eq!(compositor.as_deref(), Some("gnome"));
+                assert_eq!(stderr, "no lock");
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn polkit_icon_names_map_to_builtins() {
+        for (name, icon) in [
+            ("dialog-password", DialogIcon::Shield),
+            ("security-high", DialogIcon::Shield),
+            ("network-wireless", DialogIcon::Network),
+            ("dialog-warning", DialogIcon::Warning),
+        ] {
+            assert_eq!(polkit_icon(name), Some(icon), "{}", name);
+        }
+        assert_eq!(polkit_icon(""), None);
+
+        let details = HashMap::from([("command_line".to_string(), "/usr/bin/true".to_string())]);
+        let config = DialogConfig::from_polkit(
+            "org.freedesktop.policykit.exec",
+            "Run a program as root",
+            "dialog-password",
+            &details,
+        );
+        assert_eq!(config.icon, Some(DialogIcon::Shield));
+        let config = DialogConfig::from_polkit("org.example.mount", "Mount", "", &HashMap::new());
+        assert_eq!(config.icon, None);
+    }
+
+    #[test]
+    fn polkit_icon_names_are_looked_up_as_pngs() {
+        let dir = std::env::temp_dir().join(format!("polkit-icon-test-{}", std::process::id()));
+        let (small, large) = (dir.join("small"), dir.join("large"));
+        std::fs::create_dir_all(&small).unwrap();
+        std::fs::create_dir_all(&large).unwrap();
+        std::fs::write(large.join("drive-harddisk.png"), b"large").unwrap();
+        std::fs::write(small.join("drive-harddisk.png"), b"small").unwrap();
+        std::fs::write(small.join("huge.png"), vec![0; MAX_ICON_LEN + 1]).unwrap();
+        std::fs::create_dir_all(small.join("folder.png")).unwrap();
+        let dirs = [large.to_str().unwrap(), small.to_str().unwrap()];
+
+        let found = themed_icon("drive-harddisk", &dirs);
+        assert_eq!(found, Some(DialogIcon::Png(b"large".to_vec())));
+        let found = themed_icon("drive-harddisk", &dirs[1..]);
+        assert_eq!(found, Some(DialogIcon::Png(b"small".to_vec())));
+        for name in ["missing", "huge", "folder"] {
+            assert_eq!(themed_icon(name, &dirs), None, "{}", name);
         }
+        // Names never escape the icon directories
+        for name in ["../small/drive-harddisk", "/etc/shadow", ".hidden"] {
+            assert_eq!(
+                themed_icon(name, &[dir.to_str().unwrap()]),
+                None,
+                "{}",
+                name
+            );
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
@@ -0,0 +1,177 @@
+//! Markdown-lite formatting for generic dialog text
+//!
+//! Supports `**bold**`, `` `code` `` spans and `-`/`*` bullet lines. Links,
+//! images and everything else stay literal text.
+
+/// A line of formatted text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    /// A plain line
+    Paragraph(Vec<Span>),
+    /// A bullet list item (marker stripped)
+    Bullet(Vec<Span>),
+}
+
+/// A run of text with a single style
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+/// Inline text style
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    Bold,
+    Code,
+}
+
+/// Parse text into blocks, one per non-empty line
+pub fn parse(input: &str) -> Vec<Block> {
+    input
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let trimmed = line.trim_start();
+            match trimmed
+                .strip_prefix("- ")
+                .or_else(|| trimmed.strip_prefix("* "))
+            {
+                Some(item) => Block::Bullet(parse_inline(item)),
+                None => Block::Paragraph(parse_inline(line)),
+            }
+        })
+        .collect()
+}
+
+// Split a line into styled spans; unclosed markers stay literal
+fn parse_inline(line: &str) -> Vec<Span> {
+    const MARKERS: [(&str, Style); 2] = [("**", Style::Bold), ("`", Style::Code)];
+
+    let mut spans = Vec::new();
+    let mut rest = line;
+
+    while !rest.is_empty() {
+        let next = MARKERS
+            .iter()
+            .filter_map(|&(marker, style)| {
+                let start = rest.find(marker)?;
+                let inner_start = start + marker.len();
+                let len = rest[inner_start..].find(marker)?;
+                Some((start, inner_start, len, marker, style))
+            })
+            .min_by_key(|&(start, ..)| start);
+
+        let Some((start, inner_start, len, marker, style)) = next else {
+            push_span(&mut spans, rest, Style::Plain);
+            break;
+        };
+
+        push_span(&mut spans, &rest[..start], Style::Plain);
+        push_span(&mut spans, &rest[inner_start..inner_start + len], style);
+        rest = &rest[inner_start + len + marker.len()..];
+    }
+
+    spans
+}
+
+fn push_span(spans: &mut Vec<Span>, text: &str, style: Style) {
+    if !text.is_empty() {
+        spans.push(Span {
+            text: text.to_string(),
+            style,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(text: &str) -> Span {
+        Span {
+            text: text.to_string(),
+            style: Style::Plain,
+        }
+    }
+
+    fn bold(text: &str) -> Span {
+        Span {
+            text: text.to_string(),
+            style: Style::Bold,
+        }
+    }
+
+    fn code(text: &str) -> Span {
+        Span {
+            text: text.to_string(),
+            style: Style::Code,
+        }
+    }
+
+    #[test]
+    fn inline_styles() {
+        let cases = [
+            ("plain text", vec![plain("plain text")]),
+            (
+                "a **bold** word",
+                vec![plain("a "), bold("bold"), plain(" word")],
+            ),
+            (
+                "run `ls -l` now",
+                vec![plain("run "), code("ls -l"), plain(" now")],
+            ),
+            ("**a**`b`", vec![bold("a"), code("b")]),
+            // Whichever marker opens first wins
+            ("`**x**`", vec![code("**x**")]),
+            ("**`x`**", vec![bold("`x`")]),
+        ];
+        for (input, spans) in cases {
+            assert_eq!(parse_inline(input), spans, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn unterminated_markers_stay_literal() {
+        let cases = [
+            ("**open", vec![plain("**open")]),
+            ("`open", vec![plain("`open")]),
+            (
+                "a **b** c **d",
+                vec![plain("a "), bold("b"), plain(" c **d")],
+            ),
+            ("***", vec![plain("***")]),
+            // An empty bold run renders nothing
+            ("****", vec![]),
+            ("x****y", vec![plain("x"), plain("y")]),
+            ("``", vec![]),
+        ];
+        for (input, spans) in cases {
+            assert_eq!(parse_inline(input), spans, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn bullets_and_paragraphs() {
+        let blocks = parse("Intro\n\n- one\n  * **two**\n*not a bullet*\n-also not");
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Paragraph(vec![plain("Intro")]),
+                Block::Bullet(vec![plain("one")]),
+                Block::Bullet(vec![bold("two")]),
+                Block::Paragraph(vec![plain("*not a bullet*")]),
+                Block::Paragraph(vec![plain("-also not")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn multibyte_text_is_kept_whole() {
+        assert_eq!(
+            parse_inline("Größe **über** `ß`"),
+            vec![plain("Größe "), bold("über"), plain(" "), code("ß")]
+        );
+    }
+}
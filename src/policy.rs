@@ -0,0 +1,296 @@
+//! Pre-approval policy for privilege-escalation prompts
+//!
+//! Daemons consult `should_prompt` before `show_dialog`, so commands that
+//! policy already allows or denies are decided explicitly without any UI.
+
+use crate::{DecisionSource, DialogConfig, DialogKind, DialogReport, DialogResult};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Command patterns that are decided without asking the user
+///
+/// Patterns match the whole command line, with runs of whitespace treated
+/// as a single space. `*` matches any characters and `?` a single
+/// character, but neither matches a space or `/`, so a wildcard never
+/// spans arguments or directories: `/usr/bin/*` allows any program
+/// directly in /usr/bin, but only when run without arguments. Commands
+/// with a `..` path component are never allowed, only denied or prompted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Policy {
+    /// Commands confirmed without a prompt
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Commands denied without a prompt (checked before `allow`)
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// What to do with a request according to a `Policy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Not covered by policy, show the dialog
+    Prompt,
+    /// Pre-approved
+    Allow,
+    /// Forbidden
+    Deny,
+}
+
+impl Decision {
+    /// The result to report when no dialog is shown, or None to prompt
+    pub fn result(self) -> Option<DialogResult> {
+        match self {
+            Decision::Prompt => None,
+            Decision::Allow => Some(DialogResult::Confirmed),
+            Decision::Deny => Some(DialogResult::Denied),
+        }
+    }
+
+    /// The report for a request decided without a dialog, or None to prompt
+    ///
+    /// Its source is `DecisionSource::Policy`, so callers logging reports
+    /// can tell it apart from a user's answer.
+    pub fn report(self, config: &DialogConfig) -> Option<DialogReport> {
+        self.result().map(|result| DialogReport {
+            result,
+            elapsed: Duration::ZERO,
+            request_id: config.request_id.clone(),
+            batch: Vec::new(),
+            warnings: Vec::new(),
+            source: DecisionSource::Policy,
+            interacted: false,
+        })
+    }
+}
+
+/// Decide whether `config` needs a prompt under `policy`
+///
+/// Only privilege-escalation commands are matched; other kinds always
+/// prompt.
+pub fn should_prompt(config: &DialogConfig, policy: &Policy) -> Decision {
+    let DialogKind::PrivilegeEscalation { command } = &config.kind else {
+        return Decision::Prompt;
+    };
+
+    let command = normalize(command);
+    let matches = |patterns: &[String]| {
+        patterns
+            .iter()
+            .any(|pattern| glob_match(&normalize(pattern), &command))
+    };
+
+    if matches(&policy.deny) {
+        Decision::Deny
+    } else if has_parent_component(&command) {
+        // `/usr/bin/../../tmp/x` must not pass as something under /usr/bin
+        Decision::Prompt
+    } else if matches(&policy.allow) {
+        Decision::Allow
+    } else {
+        Decision::Prompt
+    }
+}
+
+fn normalize(s: &str) -> Vec<char> {
+    s.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .collect()
+}
+
+// Whether a command names a path through a `..` component
+fn has_parent_component(command: &[char]) -> bool {
+    command
+        .split(|&c| is_separator(c))
+        .any(|component| component == ['.', '.'])
+}
+
+fn is_separator(c: char) -> bool {
+    c == ' ' || c == '/'
+}
+
+// Whole-string glob match where wildcards never match a space or `/`
+//
+// Since neither wildcard crosses a separator, the separators of `pattern`
+// and `text` must line up one to one, and each component between them is
+// matched on its own.
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    let separators = |s: &[char]| {
+        s.iter()
+            .copied()
+            .filter(|&c| is_separator(c))
+            .collect::<Vec<_>>()
+    };
+    if separators(pattern) != separators(text) {
+        return false;
+    }
+    pattern
+        .split(|&c| is_separator(c))
+        .zip(text.split(|&c| is_separator(c)))
+        .all(|(pattern, text)| component_match(pattern, text))
+}
+
+// Glob match within one component, backtracking only to the last `*`
+fn component_match(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*`, and the text it was matched up to
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                star = Some((p, t));
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((after, matched)) => {
+                    // Let the `*` take one more character and retry
+                    p = after;
+                    t = matched + 1;
+                    star = Some((after, t));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decide(command: &str, allow: &[&str], deny: &[&str]) -> Decision {
+        let config = DialogConfig::new(DialogKind::PrivilegeEscalation {
+            command: command.to_string(),
+        });
+        let policy = Policy {
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+            deny: deny.iter().map(|s| s.to_string()).collect(),
+        };
+        should_prompt(&config, &policy)
+    }
+
+    #[test]
+    fn wildcards_stay_within_one_component() {
+        let cases = [
+            ("/usr/bin/*", "/usr/bin/pacman", Decision::Allow),
+            ("/usr/bin/*", "/usr/bin/pacman -Syu", Decision::Prompt),
+            ("/usr/bin/*", "/usr/bin/sub/tool", Decision::Prompt),
+            ("/usr/bin/*", "/usr/bin//tmp/evil", Decision::Prompt),
+            (
+                "/usr/bin/pacman -S *",
+                "/usr/bin/pacman -S vim",
+                Decision::Allow,
+            ),
+            (
+                "/usr/bin/pacman -S *",
+                "/usr/bin/pacman -S a b",
+                Decision::Prompt,
+            ),
+            (
+                "/usr/bin/pacman  -S?",
+                "/usr/bin/pacman   -Sy",
+                Decision::Allow,
+            ),
+            ("/usr/bin/?", "/usr/bin//", Decision::Prompt),
+            ("/usr/*/pacman", "/usr/bin/pacman", Decision::Allow),
+            ("*", "/usr/bin/pacman", Decision::Prompt),
+        ];
+        for (pattern, command, expected) in cases {
+            assert_eq!(
+                decide(command, &[pattern], &[]),
+                expected,
+                "{pattern:?} vs {command:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parent_components_are_never_allowed() {
+        for command in [
+            "/usr/bin/../../tmp/evil",
+            "/usr/bin/..",
+            "/usr/bin/pacman -U ../pkg.tar.zst",
+        ] {
+            assert_eq!(
+                decide(command, &["/usr/bin/*", "*", "/usr/bin/pacman -U *"], &[]),
+                Decision::Prompt,
+                "{command:?}"
+            );
+        }
+        // `..` inside a name is not a parent component
+        assert_eq!(
+            decide("/usr/bin/a..b", &["/usr/bin/*"], &[]),
+            Decision::Allow
+        );
+        // Deny still applies
+        assert_eq!(
+            decide("/usr/bin/../x", &[], &["/usr/*/../x"]),
+            Decision::Deny
+        );
+    }
+
+    #[test]
+    fn deny_takes_precedence_over_allow() {
+        assert_eq!(
+            decide("/usr/bin/rm -rf", &["/usr/bin/rm *"], &["/usr/bin/rm -rf"]),
+            Decision::Deny
+        );
+        assert_eq!(
+            decide("/usr/bin/rm -rf", &["/usr/bin/rm -rf"], &["/usr/bin/rm -*"]),
+            Decision::Deny
+        );
+    }
+
+    #[test]
+    fn empty_lists_prompt() {
+        assert_eq!(decide("/usr/bin/pacman -Syu", &[], &[]), Decision::Prompt);
+        assert_eq!(decide("", &[], &[]), Decision::Prompt);
+    }
+
+    #[test]
+    fn other_kinds_always_prompt() {
+        let config = DialogConfig::new(DialogKind::Generic {
+            title: "t".to_string(),
+            message: "m".to_string(),
+            detail: "d".into(),
+        });
+        let policy = Policy {
+            allow: vec!["*".to_string()],
+            deny: Vec::new(),
+        };
+        assert_eq!(should_prompt(&config, &policy), Decision::Prompt);
+    }
+
+    #[test]
+    fn many_stars_do_not_blow_up() {
+        let pattern: Vec<char> = "*a".repeat(30).chars().collect();
+        let text: Vec<char> = "a".repeat(60).chars().chain(['b']).collect();
+        assert!(!component_match(&pattern, &text));
+        assert!(component_match(&pattern, &text[..60]));
+    }
+
+    #[test]
+    fn policy_reports_say_policy_decided() {
+        let mut config = DialogConfig::new(DialogKind::PrivilegeEscalation {
+            command: "/usr/bin/true".to_string(),
+        });
+        config.request_id = Some("req-3".to_string());
+        for (decision, result) in [
+            (Decision::Allow, DialogResult::Confirmed),
+            (Decision::Deny, DialogResult::Denied),
+        ] {
+            let report = decision.report(&config).unwrap();
+            assert_eq!(report.result, result);
+            assert_eq!(report.source, DecisionSource::Policy);
+            assert_eq!(report.request_id.as_deref(), Some("req-3"));
+            assert!(!report.timed_out());
+        }
+        assert_eq!(Decision::Prompt.report(&config), None);
+    }
+}
@@ -0,0 +1,206 @@
+//! Re-checking the process behind a network request
+//!
+//! The daemon inspects the process when the connection is intercepted, but
+//! by the time the prompt renders that PID may have exited and been reused,
+//! or exec'd something else. `show_dialog` re-checks it just before
+//! spawning the dialog, while still privileged: the dialog itself runs as
+//! the session user and usually can't read another user's
+//! `/proc/<pid>/exe`. The checks only produce warnings; they can't make a
+//! stale request safe.
+
+use crate::{DialogKind, ProcessCheck};
+use sha2::{Digest, Sha256};
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// Whether the process behind `kind` still looks like the one requested
+///
+/// None when the request carries no PID to check.
+pub(crate) fn check(kind: &DialogKind) -> Option<ProcessCheck> {
+    let DialogKind::NetworkConnection {
+        process_path,
+        pid: Some(pid),
+        exe_hash,
+        ..
+    } = kind
+    else {
+        return None;
+    };
+
+    let exe = Path::new("/proc").join(pid.to_string()).join("exe");
+    let running = match std::fs::read_link(&exe) {
+        Ok(running) => running,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Some(ProcessCheck::Mismatch(format!(
+                "Process {} has exited",
+                pid
+            )))
+        }
+        Err(e) => return Some(unverified(*pid, &e)),
+    };
+    // The kernel marks a binary unlinked since exec, e.g. by an upgrade
+    if running.to_string_lossy().ends_with(" (deleted)") {
+        return Some(ProcessCheck::Mismatch(format!(
+            "The binary of process {} was deleted or replaced since it started",
+            pid
+        )));
+    }
+    let expected = std::fs::canonicalize(process_path).unwrap_or_else(|_| process_path.clone());
+    if running != expected {
+        return Some(ProcessCheck::Mismatch(format!(
+            "Process {} now runs {}, not {}",
+            pid,
+            running.display(),
+            process_path.display()
+        )));
+    }
+
+    // Hash through /proc so we read the binary the process actually runs,
+    // even if the file at `process_path` was replaced since
+    let Some(expected_hash) = exe_hash else {
+        return Some(ProcessCheck::Matches);
+    };
+    Some(match sha256(&exe) {
+        Ok(hash) if &hash == expected_hash => ProcessCheck::Matches,
+        Ok(_) => ProcessCheck::Mismatch(format!(
+            "The binary of process {} changed since the request",
+            pid
+        )),
+        Err(e) => unverified(*pid, &e),
+    })
+}
+
+// No permission (or another failure) is not evidence of a swap
+fn unverified(pid: u32, e: &std::io::Error) -> ProcessCheck {
+    if e.kind() == ErrorKind::PermissionDenied {
+        ProcessCheck::Unverified(format!(
+            "Process {} can't be inspected without privileges",
+            pid
+        ))
+    } else {
+        ProcessCheck::Unverified(format!("Process {} can't be verified: {}", pid, e))
+    }
+}
+
+fn sha256(path: &Path) -> std::io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Protocol;
+    use std::path::PathBuf;
+    use std::process::{Command, Stdio};
+
+    fn connection(pid: u32, process_path: PathBuf, exe_hash: Option<[u8; 32]>) -> DialogKind {
+        DialogKind::NetworkConnection {
+            process: "test".to_string(),
+            process_path,
+            destination: "example.com".to_string(),
+            port: 443,
+            protocol: Protocol::Tcp,
+            pid: Some(pid),
+            exe_hash,
+            cmdline: None,
+            parent_process: None,
+            ancestry: Vec::new(),
+        }
+    }
+
+    fn own_exe() -> PathBuf {
+        std::fs::read_link("/proc/self/exe").unwrap()
+    }
+
+    #[test]
+    fn same_binary_matches() {
+        let hash = sha256(Path::new("/proc/self/exe")).unwrap();
+        let kind = connection(std::process::id(), own_exe(), Some(hash));
+        assert_eq!(check(&kind), Some(ProcessCheck::Matches));
+        let kind = connection(std::process::id(), own_exe(), None);
+        assert_eq!(check(&kind), Some(ProcessCheck::Matches));
+    }
+
+    #[test]
+    fn other_binary_is_a_mismatch() {
+        let kind = connection(std::process::id(), "/usr/bin/true".into(), None);
+        assert!(
+            matches!(check(&kind), Some(ProcessCheck::Mismatch(reason)) if reason.contains("now runs"))
+        );
+    }
+
+    #[test]
+    fn changed_hash_is_a_mismatch() {
+        let kind = connection(std::process::id(), own_exe(), Some([0; 32]));
+        assert!(
+            matches!(check(&kind), Some(ProcessCheck::Mismatch(reason)) if reason.contains("changed"))
+        );
+    }
+
+    #[test]
+    fn deleted_binary_is_a_mismatch() {
+        let dir = std::env::temp_dir().join(format!("procinfo-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let copy = dir.join("sleep");
+        std::fs::copy("/bin/sleep", &copy).unwrap();
+        let mut child = Command::new(&copy)
+            .arg("10")
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        std::fs::remove_file(&copy).unwrap();
+
+        let result = check(&connection(child.id(), copy, None));
+        let _ = child.kill();
+        let _ = child.wait();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(
+            matches!(&result, Some(ProcessCheck::Mismatch(reason)) if reason.contains("deleted")),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn exited_process_is_a_mismatch() {
+        let mut child = Command::new("/bin/true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        let result = check(&connection(pid, "/bin/true".into(), None));
+        assert!(
+            matches!(result, Some(ProcessCheck::Mismatch(_))),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn unreadable_process_is_unverified() {
+        use std::os::unix::fs::MetadataExt;
+
+        // Only meaningful where init belongs to someone else and we aren't root
+        // SAFETY: geteuid has no preconditions
+        let euid = unsafe { libc::geteuid() };
+        let owner = std::fs::metadata("/proc/1").map(|m| m.uid());
+        if euid == 0 || !owner.is_ok_and(|owner| owner != euid) {
+            return;
+        }
+        let result = check(&connection(1, "/sbin/init".into(), None));
+        assert!(
+            matches!(result, Some(ProcessCheck::Unverified(_))),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn no_pid_is_not_checked() {
+        let mut kind = connection(1, "/sbin/init".into(), None);
+        if let DialogKind::NetworkConnection { pid, .. } = &mut kind {
+            *pid = None;
+        }
+        assert_eq!(check(&kind), None);
+    }
+}
@@ -0,0 +1,90 @@
+//! Redaction of sensitive text before it reaches logs
+//!
+//! Only logged and audited strings are redacted; the dialog itself always
+//! shows the full detail, since the user must see what they approve.
+
+use crate::RedactRule;
+use regex::Regex;
+
+/// Replacement used when a rule doesn't specify one
+pub const DEFAULT_REPLACEMENT: &str = "[REDACTED]";
+
+/// Apply every rule to `text`, in order
+///
+/// A rule whose pattern doesn't compile redacts the whole string, so a
+/// typo in the config can't leak what it was meant to hide.
+pub fn redact(text: &str, rules: &[RedactRule]) -> String {
+    let mut redacted = text.to_string();
+    for rule in rules {
+        let Ok(pattern) = Regex::new(&rule.pattern) else {
+            return replacement(rule).to_string();
+        };
+        redacted = pattern
+            .replace_all(&redacted, regex::NoExpand(replacement(rule)))
+            .into_owned();
+    }
+    redacted
+}
+
+fn replacement(rule: &RedactRule) -> &str {
+    rule.replacement.as_deref().unwrap_or(DEFAULT_REPLACEMENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, replacement: Option<&str>) -> RedactRule {
+        RedactRule {
+            pattern: pattern.to_string(),
+            replacement: replacement.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn rules_redact_matches() {
+        let cases: &[(&str, &[RedactRule], &str)] = &[
+            ("curl -H token", &[], "curl -H token"),
+            (
+                "mysql --password=hunter2 db",
+                &[rule(r"--password=\S+", None)],
+                "mysql [REDACTED] db",
+            ),
+            (
+                "ghp_abc ghp_def",
+                &[rule(r"ghp_\w+", Some("<token>"))],
+                "<token> <token>",
+            ),
+            (
+                "no secrets here",
+                &[rule(r"ghp_\w+", None)],
+                "no secrets here",
+            ),
+            // Rules apply in order, each to the previous output
+            (
+                "key=abc",
+                &[rule("abc", Some("xyz")), rule("xyz", Some("***"))],
+                "key=***",
+            ),
+            // Replacements are literal, not capture references
+            ("user=alice", &[rule(r"user=(\w+)", Some("$1"))], "$1"),
+            // Multibyte text around a match survives intact
+            (
+                "Passwort: geheim über",
+                &[rule("geheim", None)],
+                "Passwort: [REDACTED] über",
+            ),
+        ];
+        for (text, rules, expected) in cases {
+            assert_eq!(redact(text, rules), *expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn invalid_pattern_redacts_everything() {
+        let rules = [rule("ok", Some("fine")), rule("(unclosed", None)];
+        assert_eq!(redact("secret ok", &rules), DEFAULT_REPLACEMENT);
+        let rules = [rule("[", Some("<hidden>"))];
+        assert_eq!(redact("secret", &rules), "<hidden>");
+    }
+}
@@ -0,0 +1,192 @@
+//! Several prompts under one session lock (`open_dialog_stream`)
+//!
+//! The binary's `--stream` mode reads configs on stdin and writes one
+//! `DialogReport` per config on stdout, both as frames: a 4-byte
+//! big-endian length, then that many bytes of msgpack. It takes the lock
+//! for the first config and keeps it until stdin closes, showing each
+//! config once the one before is answered.
+
+use crate::{
+    drain_stderr, result_from_output, with_process_check, DialogConfig, DialogError, DialogReport,
+    MAX_CONFIG_LEN,
+};
+use std::io::{self, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout};
+use std::thread::JoinHandle;
+
+/// Write `bytes` as one frame
+pub(crate) fn write_frame(mut out: impl Write, bytes: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(bytes.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame too large"))?;
+    let mut frame = Vec::with_capacity(4 + bytes.len());
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(bytes);
+    out.write_all(&frame)?;
+    out.flush()
+}
+
+/// Read one frame, or None if the input ends between frames
+///
+/// A frame over `max_len` is skipped and reported as `InvalidData`, so the
+/// next read still starts at a frame.
+pub(crate) fn read_frame(mut input: impl Read, max_len: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0; 4];
+    let mut filled = 0;
+    while filled < header.len() {
+        match input.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let len = u32::from_be_bytes(header) as usize;
+    if len > max_len {
+        let skipped = io::copy(&mut input.by_ref().take(len as u64), &mut io::sink())?;
+        if skipped < len as u64 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame too large ({} bytes, limit {})", len, max_len),
+        ));
+    }
+    let mut bytes = vec![0; len];
+    input.read_exact(&mut bytes)?;
+    Ok(Some(bytes))
+}
+
+/// A dialog binary that keeps the session locked between prompts
+///
+/// Returned by `open_dialog_stream`. Nothing is shown before the first
+/// `show`; `close` (or dropping this) releases the lock.
+pub struct DialogStream {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: ChildStdout,
+    stderr: Option<JoinHandle<Vec<u8>>>,
+    compositor: Option<String>,
+}
+
+impl DialogStream {
+    pub(crate) fn new(mut child: Child, compositor: Option<String>) -> Self {
+        Self {
+            stdin: child.stdin.take(),
+            stdout: child.stdout.take().expect("stdout is piped"),
+            stderr: Some(drain_stderr(&mut child)),
+            child,
+            compositor,
+        }
+    }
+
+    /// Show `config` and wait for the answer
+    ///
+    /// Validated and process-checked like `show_dialog`. Secret entry is
+    /// left to `show_auth_dialog`: a config with `secret_fd` set is
+    /// answered with `DialogResult::Error` unshown. Once the binary is
+    /// gone (e.g. the compositor refused the lock), this returns why, here
+    /// and on every later call.
+    pub fn show(&mut self, config: &DialogConfig) -> Result<DialogReport, DialogError> {
+        config.validate()?;
+        let bytes = with_process_check(config).to_bytes();
+        if bytes.len() > MAX_CONFIG_LEN {
+            return Err(DialogError::ConfigTooLarge(bytes.len()));
+        }
+
+        let written = match self.stdin.as_mut() {
+            Some(stdin) => write_frame(stdin, &bytes).is_ok(),
+            None => false,
+        };
+        if !written {
+            return Err(self.failure());
+        }
+        match read_frame(&mut self.stdout, MAX_CONFIG_LEN) {
+            Ok(Some(bytes)) => rmp_serde::from_slice(&bytes)
+                .map_err(|e| DialogError::Protocol(format!("malformed result report: {}", e))),
+            Ok(None) | Err(_) => Err(self.failure()),
+        }
+    }
+
+    /// Release the session lock and wait for the binary to exit
+    pub fn close(mut self) -> Result<(), DialogError> {
+        drop(self.stdin.take());
+        let status = self.child.wait()?;
+        let stderr = self.stderr_output();
+        result_from_output(status, &stderr, self.compositor.clone()).map(|_| ())
+    }
+
+    // Why the binary stopped answering, once it has exited
+    fn failure(&mut self) -> DialogError {
+        drop(self.stdin.take());
+        let status = match self.child.wait() {
+            Ok(status) => status,
+            Err(e) => return e.into(),
+        };
+        let stderr = self.stderr_output();
+        match result_from_output(status, &stderr, self.compositor.clone()) {
+            Err(e) => e,
+            Ok(_) => DialogError::Protocol("dialog stream ended without an answer".to_string()),
+        }
+    }
+
+    // Captured stderr, the first time it is asked for
+    fn stderr_output(&mut self) -> Vec<u8> {
+        self.stderr
+            .take()
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for DialogStream {
+    fn drop(&mut self) {
+        // The binary unlocks and exits once stdin closes
+        drop(self.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn framed(payloads: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for payload in payloads {
+            write_frame(&mut bytes, payload).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let bytes = framed(&[b"first", b"", b"third"]);
+        assert_eq!(&bytes[..4], &5u32.to_be_bytes());
+
+        let mut input = bytes.as_slice();
+        assert_eq!(read_frame(&mut input, 16).unwrap().unwrap(), b"first");
+        assert_eq!(read_frame(&mut input, 16).unwrap().unwrap(), b"");
+        assert_eq!(read_frame(&mut input, 16).unwrap().unwrap(), b"third");
+        assert!(read_frame(&mut input, 16).unwrap().is_none());
+    }
+
+    #[test]
+    fn oversized_frames_are_skipped() {
+        let bytes = framed(&[&[7; 32], b"next"]);
+        let mut input = bytes.as_slice();
+        let err = read_frame(&mut input, 16).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(read_frame(&mut input, 16).unwrap().unwrap(), b"next");
+    }
+
+    #[test]
+    fn truncated_frames_are_errors() {
+        let bytes = framed(&[b"complete"]);
+        for cut in [2, 6] {
+            let err = read_frame(&bytes[..cut], 16).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Handle to an inline dialog running on its own thread
+
+use crate::{ui, DecisionSource, DialogReport, DialogResult};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+/// A dialog shown by `show_dialog_async`
+///
+/// Dropping the handle does not cancel the dialog: it stays up until the
+/// user answers or it times out. Call `cancel` to dismiss it.
+///
+/// Inline dialogs share process-wide UI state, so only one can run at a
+/// time and `cancel` applies to whichever is showing.
+pub struct DialogTask {
+    handle: Option<JoinHandle<DialogReport>>,
+    report: Option<DialogReport>,
+    request_id: Option<String>,
+    started: Instant,
+}
+
+impl DialogTask {
+    pub(crate) fn new(handle: JoinHandle<DialogReport>, request_id: Option<String>) -> Self {
+        Self {
+            handle: Some(handle),
+            report: None,
+            request_id,
+            started: Instant::now(),
+        }
+    }
+
+    /// Dismiss the dialog; it unlocks and reports `DialogResult::Cancelled`
+    ///
+    /// Has no effect once the user has answered.
+    pub fn cancel(&self) {
+        if !self.is_finished() {
+            ui::request_cancel();
+        }
+    }
+
+    /// Whether the dialog has closed (never blocks)
+    pub fn is_finished(&self) -> bool {
+        self.handle
+            .as_ref()
+            .is_none_or(|handle| handle.is_finished())
+    }
+
+    /// The result if the dialog has closed, without blocking
+    pub fn try_result(&mut self) -> Option<DialogResult> {
+        self.try_report().map(|report| report.result)
+    }
+
+    /// The full report if the dialog has closed, without blocking
+    pub fn try_report(&mut self) -> Option<&DialogReport> {
+        if self.report.is_none() && self.is_finished() {
+            self.report = self.handle.take().map(|handle| self.join_handle(handle));
+        }
+        self.report.as_ref()
+    }
+
+    /// Wait for the dialog to close
+    pub fn join(self) -> DialogResult {
+        self.join_report().result
+    }
+
+    /// Wait for the dialog to close and return its full report, including
+    /// how long the user took
+    pub fn join_report(mut self) -> DialogReport {
+        match (self.handle.take(), self.report.take()) {
+            (Some(handle), _) => self.join_handle(handle),
+            (None, Some(report)) => report,
+            (None, None) => self.failed(),
+        }
+    }
+
+    // A panicked dialog thread is reported like any other failure
+    fn join_handle(&self, handle: JoinHandle<DialogReport>) -> DialogReport {
+        handle.join().unwrap_or_else(|_| self.failed())
+    }
+
+    // Report for a dialog that died without deciding
+    fn failed(&self) -> DialogReport {
+        DialogReport {
+            result: DialogResult::Error,
+            elapsed: self.started.elapsed(),
+            request_id: self.request_id.clone(),
+            batch: Vec::new(),
+            warnings: Vec::new(),
+            source: DecisionSource::inferred(DialogResult::Error),
+            interacted: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    fn report(result: DialogResult) -> DialogReport {
+        DialogReport {
+            result,
+            elapsed: Duration::from_millis(1200),
+            request_id: Some("req-1".to_string()),
+            batch: Vec::new(),
+            warnings: Vec::new(),
+            source: DecisionSource::User,
+            interacted: true,
+        }
+    }
+
+    /// A task whose dialog answers `result` once the sender is used
+    fn pending(result: DialogResult) -> (DialogTask, mpsc::Sender<()>) {
+        let (answer, answered) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let _ = answered.recv();
+            report(result)
+        });
+        (DialogTask::new(handle, Some("req-1".to_string())), answer)
+    }
+
+    fn wait_until_finished(task: &DialogTask) {
+        while !task.is_finished() {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn join_waits_for_the_answer() {
+        let (task, answer) = pending(DialogResult::Confirmed);
+        answer.send(()).unwrap();
+        assert_eq!(task.join(), DialogResult::Confirmed);
+    }
+
+    #[test]
+    fn polling_never_blocks() {
+        let (mut task, answer) = pending(DialogResult::Denied);
+        assert!(!task.is_finished());
+        assert_eq!(task.try_result(), None);
+
+        answer.send(()).unwrap();
+        wait_until_finished(&task);
+        assert_eq!(task.try_result(), Some(DialogResult::Denied));
+        // Polling again, or joining after polling, gives the same report
+        assert_eq!(task.try_result(), Some(DialogResult::Denied));
+        let report = task.join_report();
+        assert_eq!(report.result, DialogResult::Denied);
+        assert_eq!(report.request_id.as_deref(), Some("req-1"));
+        assert!(report.interacted);
+    }
+
+    #[test]
+    fn a_panicked_dialog_is_an_error() {
+        let handle = thread::spawn(|| -> DialogReport { panic!("dialog thread died") });
+        let mut task = DialogTask::new(handle, Some("req-2".to_string()));
+        wait_until_finished(&task);
+        assert_eq!(task.try_result(), Some(DialogResult::Error));
+
+        let handle = thread::spawn(|| -> DialogReport { panic!("dialog thread died") });
+        let report = DialogTask::new(handle, Some("req-2".to_string())).join_report();
+        assert_eq!(report.result, DialogResult::Error);
+        assert_eq!(report.request_id.as_deref(), Some("req-2"));
+        assert!(!report.interacted);
+    }
+
+    #[test]
+    fn cancel_dismisses_a_showing_dialog() {
+        let _guard = ui::TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        ui::enable_cancel();
+        let handle = thread::spawn(|| {
+            while !ui::cancel_requested() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            report(DialogResult::Cancelled)
+        });
+        let task = DialogTask::new(handle, None);
+        task.cancel();
+        assert_eq!(task.join(), DialogResult::Cancelled);
+    }
+
+    #[test]
+    fn cancel_after_the_answer_does_nothing() {
+        let _guard = ui::TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        ui::enable_cancel();
+        let (task, answer) = pending(DialogResult::Confirmed);
+        answer.send(()).unwrap();
+        wait_until_finished(&task);
+        task.cancel();
+        assert!(!ui::cancel_requested());
+        assert_eq!(task.join(), DialogResult::Confirmed);
+    }
+
+    #[test]
+    fn dropping_the_handle_does_not_cancel() {
+        let _guard = ui::TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        ui::enable_cancel();
+        let (task, answer) = pending(DialogResult::Confirmed);
+        drop(task);
+        assert!(!ui::cancel_requested());
+        let _ = answer.send(());
+    }
+}
@@ -0,0 +1,297 @@
+//! Dialog color palettes and legibility checks
+//!
+//! Public so embedders can match the dialog's look in their own UI.
+
+use crate::{ThemeConfig, ThemeSelection};
+use iced::Color;
+
+/// Resolved colors used by every widget in the dialog
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub scrim: Color,
+    pub card_bg: Color,
+    pub card_border: Color,
+    pub inset_bg: Color,
+    pub keycap_bg: Color,
+    pub keycap_border: Color,
+    pub text_primary: Color,
+    pub text_body: Color,
+    pub text_muted: Color,
+    pub accent: Color,
+    pub success: Color,
+    pub danger: Color,
+    /// Width of the card and detail borders
+    pub border_width: f32,
+}
+
+/// Ayu Dark palette (the default)
+pub const AYU_DARK: Palette = Palette {
+    scrim: Color::from_rgb8(0x05, 0x08, 0x0D),
+    card_bg: Color::from_rgb8(0x0F, 0x13, 0x1A),
+    card_border: Color::from_rgb8(0x1C, 0x22, 0x2C),
+    inset_bg: Color::from_rgb8(0x0A, 0x0D, 0x12),
+    keycap_bg: Color::from_rgb8(0x1A, 0x20, 0x2A),
+    keycap_border: Color::from_rgb8(0x2B, 0x33, 0x40),
+    text_primary: Color::from_rgb8(0xE6, 0xE1, 0xCF),
+    text_body: Color::from_rgb8(0xBF, 0xBD, 0xB6),
+    text_muted: Color::from_rgb8(0x8A, 0x91, 0x99),
+    accent: Color::from_rgb8(0xE6, 0xB4, 0x50),
+    success: Color::from_rgb8(0xAA, 0xD9, 0x4C),
+    danger: Color::from_rgb8(0xD9, 0x57, 0x57),
+    border_width: 1.0,
+};
+
+/// Light palette for light desktops
+pub const LIGHT: Palette = Palette {
+    scrim: Color::from_rgb8(0xC9, 0xCE, 0xD6),
+    card_bg: Color::from_rgb8(0xFA, 0xFA, 0xFA),
+    card_border: Color::from_rgb8(0xD5, 0xD9, 0xDE),
+    inset_bg: Color::from_rgb8(0xF0, 0xF2, 0xF4),
+    keycap_bg: Color::from_rgb8(0xEC, 0xEE, 0xF0),
+    keycap_border: Color::from_rgb8(0xC8, 0xCD, 0xD3),
+    text_primary: Color::from_rgb8(0x1F, 0x23, 0x28),
+    text_body: Color::from_rgb8(0x3B, 0x40, 0x48),
+    text_muted: Color::from_rgb8(0x5C, 0x63, 0x6B),
+    accent: Color::from_rgb8(0x8A, 0x5A, 0x00),
+    success: Color::from_rgb8(0x2E, 0x7D, 0x32),
+    danger: Color::from_rgb8(0xC6, 0x28, 0x28),
+    border_width: 1.0,
+};
+
+/// Black and white with thick borders, for low-vision users
+pub const HIGH_CONTRAST: Palette = Palette {
+    scrim: Color::BLACK,
+    card_bg: Color::BLACK,
+    card_border: Color::WHITE,
+    inset_bg: Color::BLACK,
+    keycap_bg: Color::BLACK,
+    keycap_border: Color::WHITE,
+    text_primary: Color::WHITE,
+    text_body: Color::WHITE,
+    text_muted: Color::WHITE,
+    accent: Color::from_rgb8(0xFF, 0xFF, 0x00),
+    success: Color::from_rgb8(0x00, 0xFF, 0x00),
+    danger: Color::from_rgb8(0xFF, 0x40, 0x40),
+    border_width: 3.0,
+};
+
+/// Nord (polar night background, snow storm text)
+const NORD: ThemeConfig = ThemeConfig {
+    background: [0x2E, 0x34, 0x40],
+    text: [0xEC, 0xEF, 0xF4],
+    accent: [0x88, 0xC0, 0xD0],
+    success: [0xA3, 0xBE, 0x8C],
+    danger: [0xBF, 0x61, 0x6A],
+};
+
+/// Gruvbox dark, medium contrast
+const GRUVBOX_DARK: ThemeConfig = ThemeConfig {
+    background: [0x28, 0x28, 0x28],
+    text: [0xEB, 0xDB, 0xB2],
+    accent: [0xFA, 0xBD, 0x2F],
+    success: [0xB8, 0xBB, 0x26],
+    danger: [0xFB, 0x49, 0x34],
+};
+
+/// Look up a built-in palette by name ("ayu-dark", "light",
+/// "high-contrast", "nord", "gruvbox")
+pub fn preset(name: &str) -> Option<Palette> {
+    match name.to_ascii_lowercase().as_str() {
+        "ayu-dark" => Some(AYU_DARK),
+        "light" => Some(LIGHT),
+        "high-contrast" => Some(HIGH_CONTRAST),
+        "nord" => Some(Palette::from_theme(&NORD)),
+        "gruvbox" | "gruvbox-dark" => Some(Palette::from_theme(&GRUVBOX_DARK)),
+        _ => None,
+    }
+}
+
+// WCAG AA minimum for normal-size text
+const MIN_CONTRAST: f32 = 4.5;
+// WCAG AA minimum for UI components, for the confirm and deny colors,
+// which only ever repeat what a label says
+const MIN_STATUS_CONTRAST: f32 = 3.0;
+
+impl Palette {
+    /// Derive a full palette from the five theme colors
+    pub fn from_theme(theme: &ThemeConfig) -> Self {
+        let background = rgb(theme.background);
+        let text = rgb(theme.text);
+        Self {
+            scrim: mix(background, Color::BLACK, 0.6),
+            card_bg: background,
+            card_border: mix(background, text, 0.12),
+            inset_bg: mix(background, Color::BLACK, 0.3),
+            keycap_bg: mix(background, text, 0.08),
+            keycap_border: mix(background, text, 0.2),
+            text_primary: text,
+            text_body: mix(background, text, 0.85),
+            text_muted: mix(background, text, 0.6),
+            accent: rgb(theme.accent),
+            success: rgb(theme.success),
+            danger: rgb(theme.danger),
+            border_width: 1.0,
+        }
+    }
+
+    /// Whether the text the user must read stands out from its background
+    pub fn is_legible(&self) -> bool {
+        let text = [
+            (self.text_primary, self.card_bg),
+            (self.text_body, self.card_bg),
+            (self.accent, self.inset_bg),
+        ];
+        let status = [(self.success, self.card_bg), (self.danger, self.card_bg)];
+        text.iter()
+            .all(|&(fg, bg)| contrast_ratio(fg, bg) >= MIN_CONTRAST)
+            && status
+                .iter()
+                .all(|&(fg, bg)| contrast_ratio(fg, bg) >= MIN_STATUS_CONTRAST)
+    }
+}
+
+/// Resolve the palette for a config, refusing illegible custom themes
+///
+/// An unreadable prompt is a security risk (the user can't see what they
+/// approve), so failing themes fall back to the default palette, as do
+/// unknown preset names.
+pub fn resolve(theme: Option<&ThemeSelection>) -> Palette {
+    checked(theme).unwrap_or_else(|warning| {
+        eprintln!("session-dialog: {}", warning);
+        AYU_DARK
+    })
+}
+
+// The palette for `theme`, or why it must fall back to the default
+fn checked(theme: Option<&ThemeSelection>) -> Result<Palette, String> {
+    match theme {
+        None => Ok(AYU_DARK),
+        Some(ThemeSelection::Named(name)) => {
+            preset(name).ok_or_else(|| format!("unknown theme {:?}, using ayu-dark", name))
+        }
+        Some(ThemeSelection::Custom(theme)) => Some(Palette::from_theme(theme))
+            .filter(Palette::is_legible)
+            .ok_or_else(|| "custom theme fails contrast checks, using ayu-dark".to_string()),
+    }
+}
+
+/// An iced `Theme` with the dialog's colors, for embedders' own widgets
+///
+/// Resolves `selection` exactly as the dialog does, including the
+/// fallback for illegible or unknown themes.
+pub fn theme(selection: ThemeSelection) -> iced::Theme {
+    resolve(Some(&selection)).to_iced_theme()
+}
+
+impl Palette {
+    /// The iced `Theme` built from this palette (used by the dialog itself)
+    pub fn to_iced_theme(&self) -> iced::Theme {
+        iced::Theme::custom(
+            "session-dialog".to_string(),
+            iced::theme::Palette {
+                background: self.card_bg,
+                text: self.text_primary,
+                primary: self.accent,
+                success: self.success,
+                warning: self.accent,
+                danger: self.danger,
+            },
+        )
+    }
+}
+
+/// WCAG relative luminance of an sRGB color
+pub fn relative_luminance(color: Color) -> f32 {
+    fn channel(v: f32) -> f32 {
+        if v <= 0.03928 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        }
+    }
+    0.2126 * channel(color.r) + 0.7152 * channel(color.g) + 0.0722 * channel(color.b)
+}
+
+/// WCAG contrast ratio between two colors (1.0 to 21.0)
+pub fn contrast_ratio(a: Color, b: Color) -> f32 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    let (lighter, darker) = if la > lb { (la, lb) } else { (lb, la) };
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+fn rgb([r, g, b]: [u8; 3]) -> Color {
+    Color::from_rgb8(r, g, b)
+}
+
+// Linear blend from `a` (t = 0) to `b` (t = 1)
+pub(crate) fn mix(a: Color, b: Color, t: f32) -> Color {
+    Color::from_rgb(
+        a.r + (b.r - a.r) * t,
+        a.g + (b.g - a.g) * t,
+        a.b + (b.b - a.b) * t,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRESETS: [&str; 5] = ["ayu-dark", "light", "high-contrast", "nord", "gruvbox"];
+
+    fn custom(background: [u8; 3], text: [u8; 3], status: [u8; 3]) -> ThemeSelection {
+        ThemeSelection::Custom(ThemeConfig {
+            background,
+            text,
+            accent: text,
+            success: status,
+            danger: status,
+        })
+    }
+
+    #[test]
+    fn presets_are_legible() {
+        for name in PRESETS {
+            let palette = preset(name).unwrap();
+            assert!(palette.is_legible(), "{} is not legible", name);
+        }
+    }
+
+    #[test]
+    fn preset_names_resolve() {
+        let named = |name: &str| checked(Some(&ThemeSelection::Named(name.to_string())));
+        assert_eq!(named("ayu-dark"), Ok(AYU_DARK));
+        assert_eq!(named("light"), Ok(LIGHT));
+        assert_eq!(named("High-Contrast"), Ok(HIGH_CONTRAST));
+        assert_eq!(named("nord"), Ok(Palette::from_theme(&NORD)));
+        assert_eq!(named("gruvbox"), named("gruvbox-dark"));
+        assert_eq!(checked(None), Ok(AYU_DARK));
+    }
+
+    #[test]
+    fn unknown_preset_falls_back_with_warning() {
+        let unknown = ThemeSelection::Named("solarized".to_string());
+        let warning = checked(Some(&unknown)).unwrap_err();
+        assert!(warning.contains("\"solarized\""), "{}", warning);
+        assert_eq!(resolve(Some(&unknown)), AYU_DARK);
+    }
+
+    #[test]
+    fn identical_foreground_and_background_falls_back() {
+        let gray = [0x80, 0x80, 0x80];
+        assert_eq!(resolve(Some(&custom(gray, gray, gray))), AYU_DARK);
+    }
+
+    #[test]
+    fn invisible_status_colors_fall_back() {
+        let (black, white) = ([0, 0, 0], [0xFF, 0xFF, 0xFF]);
+        assert_ne!(resolve(Some(&custom(black, white, white))), AYU_DARK);
+        // Readable text, but Allow/Deny hints drawn in the background color
+        assert_eq!(resolve(Some(&custom(black, white, black))), AYU_DARK);
+    }
+
+    #[test]
+    fn contrast_ratio_bounds() {
+        assert!((contrast_ratio(Color::BLACK, Color::WHITE) - 21.0).abs() < 0.01);
+        assert!((contrast_ratio(Color::WHITE, Color::WHITE) - 1.0).abs() < 0.01);
+    }
+}
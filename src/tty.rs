@@ -0,0 +1,196 @@
+//! Terminal prompt for when there is no Wayland session
+//!
+//! Asks on the controlling terminal (`/dev/tty`), not stdin, so it works
+//! for daemons whose stdin is a pipe or /dev/null and never reads input
+//! meant for something else. The terminal is switched to non-canonical,
+//! non-echoing mode for single-key answers and restored before returning.
+
+use crate::{audit, DecisionSource, DialogConfig, DialogResult, TimeoutAction};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+
+/// Ask for a y/n decision on the controlling terminal
+///
+/// Shows the title, subtitle and detail and waits for a key: `y` confirms;
+/// `n`, Esc, Ctrl+C and Ctrl+D deny. `timeout_secs` and `timeout_action`
+/// behave as in the graphical dialog. Custom actions aren't offered.
+/// Returns `Error` if there is no controlling terminal.
+pub fn show_dialog_tty(config: &DialogConfig) -> DialogResult {
+    let start = Instant::now();
+    let (result, source) = match prompt(config) {
+        Ok(decision) => decision,
+        Err(e) => {
+            eprintln!("session-dialog: terminal prompt failed: {}", e);
+            return DialogResult::Error;
+        }
+    };
+    audit::record(config, result, result.exit_code(), start.elapsed(), source);
+    result
+}
+
+fn prompt(config: &DialogConfig) -> std::io::Result<(DialogResult, DecisionSource)> {
+    let mut tty = File::options().read(true).write(true).open("/dev/tty")?;
+
+    write!(
+        tty,
+        "\n{}\n{}\n\n    {}\n\n",
+        printable(config.title()),
+        printable(config.subtitle()),
+        printable(&config.detail()).replace('\n', "\n    ")
+    )?;
+    match config.timeout_secs {
+        Some(secs) => write!(tty, "Allow? [y/n] ({}s) ", secs)?,
+        None => write!(tty, "Allow? [y/n] ")?,
+    }
+    tty.flush()?;
+
+    let deadline = config
+        .timeout_secs
+        .map(|secs| Instant::now() + Duration::from_secs(secs as u64));
+    let answer = {
+        let _raw = RawMode::enable(&tty)?;
+        // Typeahead must not answer a prompt the user hasn't seen
+        // SAFETY: the descriptor is open for the lifetime of `tty`
+        unsafe { libc::tcflush(tty.as_raw_fd(), libc::TCIFLUSH) };
+        read_answer(&mut tty, deadline)?
+    };
+
+    let decision = match answer {
+        Some(result) => (result, DecisionSource::User),
+        None => (timeout_result(config), DecisionSource::Timeout),
+    };
+    let word = match decision.0 {
+        DialogResult::Confirmed => "allowed",
+        DialogResult::Timeout => "timed out",
+        _ => "denied",
+    };
+    writeln!(tty, "{}", word)?;
+    Ok(decision)
+}
+
+// What an unanswered prompt decides
+fn timeout_result(config: &DialogConfig) -> DialogResult {
+    match config.effective_timeout_action() {
+        TimeoutAction::Confirm => DialogResult::Confirmed,
+        TimeoutAction::Deny | TimeoutAction::ReturnTimeout => DialogResult::Timeout,
+    }
+}
+
+// The first y/n-style key, or None once `deadline` passes
+fn read_answer(tty: &mut File, deadline: Option<Instant>) -> std::io::Result<Option<DialogResult>> {
+    loop {
+        let timeout_ms = match deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return Ok(None);
+                }
+                left.as_millis().min(i32::MAX as u128) as i32
+            }
+            None => -1,
+        };
+        let mut fds = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `fds` is a valid pollfd for the duration of the call
+        match unsafe { libc::poll(&mut fds, 1, timeout_ms) } {
+            -1 => {
+                let e = std::io::Error::last_os_error();
+                if e.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+            0 => continue,
+            _ => {}
+        }
+
+        let mut byte = [0u8; 1];
+        if tty.read(&mut byte)? == 0 {
+            // Hangup: nobody is left to answer
+            return Ok(Some(DialogResult::Denied));
+        }
+        match byte[0] {
+            b'y' | b'Y' => return Ok(Some(DialogResult::Confirmed)),
+            // n, Esc, Ctrl+C, Ctrl+D
+            b'n' | b'N' | 0x1b | 0x03 | 0x04 => return Ok(Some(DialogResult::Denied)),
+            _ => {}
+        }
+    }
+}
+
+// Control characters shown escaped, so a request can't send terminal
+// escape sequences that redraw or hide the prompt
+fn printable(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\n' => c.to_string(),
+            c if c.is_control() => c.escape_default().to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Single-key input without echo or signals, restored on drop
+struct RawMode {
+    fd: i32,
+    saved: libc::termios,
+}
+
+impl RawMode {
+    fn enable(tty: &File) -> std::io::Result<Self> {
+        let fd = tty.as_raw_fd();
+        // SAFETY: termios is plain data, filled in by tcgetattr
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: `fd` is open and `saved` is a valid termios
+        if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let mut raw = saved;
+        // No ISIG: Ctrl+C must deny, not kill the daemon
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: as above
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self { fd, saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: `fd` outlives the guard and `saved` came from tcgetattr
+        unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DialogKind;
+
+    #[test]
+    fn timeout_never_confirms_escalation_or_secrets() {
+        let mut config = DialogConfig::new(DialogKind::PrivilegeEscalation {
+            command: "/usr/bin/pacman -Syu".to_string(),
+        });
+        config.timeout_action = TimeoutAction::Confirm;
+        assert_eq!(timeout_result(&config), DialogResult::Timeout);
+
+        let mut config = DialogConfig::new(DialogKind::Generic {
+            title: "Lock screen".to_string(),
+            message: "Locking the screen".to_string(),
+            detail: "Esc to stop".into(),
+        });
+        config.timeout_action = TimeoutAction::Confirm;
+        assert_eq!(timeout_result(&config), DialogResult::Confirmed);
+        config.secret_prompt = Some("PIN".to_string());
+        assert_eq!(timeout_result(&config), DialogResult::Timeout);
+    }
+}
@@ -1,35 +1,105 @@
 //! Iced session-lock dialog UI
 
-use crate::DialogConfig;
+use crate::audit;
+use crate::cmdline::{self, Part};
+use crate::i18n::{self, tr};
+use crate::markup::{self, Block};
+use crate::procinfo;
+use crate::stream::{read_frame, write_frame};
+use crate::theming::{self, Palette};
+use crate::{
+    detect_compositor, result_from_exit_code, BackdropConfig, ConnectionScope, DecisionSource,
+    DetailSource, DialogConfig, DialogIcon, DialogKind, DialogPriority, DialogReport, DialogResult,
+    LayoutConfig, ProcessCheck, RequestWarning, SecureToken, TimeoutAction, AUTO_RESULT_ENV,
+    CANCEL_SIGNAL, FORCE_DENY_ENV, LOCK_UNAVAILABLE_EXIT, MAX_CONFIG_LEN, MAX_DETAIL_FILE_LEN,
+    MAX_TOKEN_IMAGE_LEN, RESULT_FD, SECRET_FD, TEST_MODE_ENV,
+};
 use iced::border::Radius;
 use iced::font::Weight;
 use iced::keyboard::{self, Key};
-use iced::widget::{column, container, row, text, Space};
+use iced::mouse;
+use iced::widget::{
+    button, column, container, image, progress_bar, responsive, rich_text, row, scrollable, span,
+    stack, text, text_input, Column, Space,
+};
 use iced::window::Id;
-use iced::{Alignment, Color, Element, Event, Font, Length, Subscription, Task};
+use iced::{touch, Alignment, Color, Element, Event, Font, Length, Padding, Point, Size};
+use iced::{Subscription, Task};
 use iced_sessionlock::actions::UnLockAction;
 use iced_sessionlock::application;
-use std::sync::atomic::{AtomicI32, Ordering};
-use std::sync::Mutex;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::os::fd::RawFd;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
 
 static CONFIG: Mutex<Option<DialogConfig>> = Mutex::new(None);
 static EXIT_CODE: AtomicI32 = AtomicI32::new(1); // Default: denied
-
-// Ayu Dark palette
-const SCRIM: Color = Color::from_rgb8(0x05, 0x08, 0x0D);
-const CARD_BG: Color = Color::from_rgb8(0x0F, 0x13, 0x1A);
-const CARD_BORDER: Color = Color::from_rgb8(0x1C, 0x22, 0x2C);
-const INSET_BG: Color = Color::from_rgb8(0x0A, 0x0D, 0x12);
-const KEYCAP_BG: Color = Color::from_rgb8(0x1A, 0x20, 0x2A);
-const KEYCAP_BORDER: Color = Color::from_rgb8(0x2B, 0x33, 0x40);
-const TEXT_PRIMARY: Color = Color::from_rgb8(0xE6, 0xE1, 0xCF);
-const TEXT_BODY: Color = Color::from_rgb8(0xBF, 0xBD, 0xB6);
-const TEXT_MUTED: Color = Color::from_rgb8(0x8A, 0x91, 0x99);
-const ACCENT: Color = Color::from_rgb8(0xE6, 0xB4, 0x50);
-const SUCCESS: Color = Color::from_rgb8(0xAA, 0xD9, 0x4C);
-const DANGER: Color = Color::from_rgb8(0xD9, 0x57, 0x57);
+static ELAPSED_MS: AtomicU64 = AtomicU64::new(0);
+static SCALE: AtomicU32 = AtomicU32::new(1.0f32.to_bits()); // DialogConfig::scale
+static FRAME_SEEN: AtomicBool = AtomicBool::new(false);
+// Counts shown dialogs, so a watchdog only judges the dialog it was started for
+static RUN: AtomicU64 = AtomicU64::new(0);
+static SIGNALS_INSTALLED: AtomicBool = AtomicBool::new(false);
+static TERMINATE: AtomicBool = AtomicBool::new(false);
+static CANCELABLE: AtomicBool = AtomicBool::new(false);
+static CANCEL_REQUESTED: AtomicBool = AtomicBool::new(false);
+// Per-item results of a batch dialog, sent with the report
+static BATCH_RESULTS: Mutex<Vec<DialogResult>> = Mutex::new(Vec::new());
+// Red flags shown with the request, including checks done at startup
+static WARNINGS: Mutex<Vec<RequestWarning>> = Mutex::new(Vec::new());
+// What decided the result, sent with the report
+static SOURCE: Mutex<DecisionSource> = Mutex::new(DecisionSource::User);
+// Whether the user pressed a key or clicked, sent with the report
+static INTERACTED: AtomicBool = AtomicBool::new(false);
+// Secret typed into a secret-entry field, sent over `secret_fd` on exit
+static SECRET: Mutex<Option<Zeroizing<String>>> = Mutex::new(None);
+// Configs of `run_stream` waiting to be shown, set only in stream mode
+static STREAM: Mutex<Option<Stream>> = Mutex::new(None);
+// Signalled whenever `STREAM` gains a config or closes
+static STREAM_READY: Condvar = Condvar::new();
 
 const CARD_WIDTH: f32 = 700.0;
+/// Bounds of `DialogConfig::scale`
+const MIN_SCALE: f32 = 0.5;
+const MAX_SCALE: f32 = 3.0;
+/// Tallest the detail block grows before it scrolls
+const DETAIL_MAX_HEIGHT: f32 = 360.0;
+/// Characters of detail shown before the rest is elided (it is still
+/// copied in full); privilege-escalation commands are never elided
+const DETAIL_MAX_CHARS: usize = 4000;
+/// Characters of title and subtitle shown; they don't scroll, so a huge
+/// one would push the actions off screen
+const TITLE_MAX_CHARS: usize = 120;
+const SUBTITLE_MAX_CHARS: usize = 600;
+/// Characters of action labels and key names shown, which share one row
+const LABEL_MAX_CHARS: usize = 40;
+
+/// Widget id of the `secret_prompt` field, focused when the dialog opens
+const SECRET_INPUT: &str = "secret-input";
+
+/// Key that removes the timeout when `allow_timeout_cancel` is set
+const TIMEOUT_CANCEL_KEY: &str = "t";
+
+/// Key that expands and collapses `extra_details` (Tab already moves the
+/// copy focus)
+const DETAILS_KEY: &str = "i";
+
+/// How long a touch must be held to open the context menu
+const LONG_PRESS: Duration = Duration::from_millis(500);
+
+// How long confirming is ignored after the prompt appears when the config
+// doesn't say (see `DialogConfig::min_visible_ms`)
+const DEFAULT_MIN_VISIBLE: Duration = Duration::from_millis(500);
+
+// Tick rate for countdowns when the config doesn't set one
+// (10Hz, so the countdown's tenths of a second move smoothly)
+const DEFAULT_TICK_INTERVAL: Duration = Duration::from_millis(100);
 
 /// Run the dialog UI and return exit code
 ///
@@ -38,30 +108,773 @@ const CARD_WIDTH: f32 = 700.0;
 /// - 1: Denied
 /// - 2: Timeout
 /// - 3: Error
-pub fn run(config: DialogConfig) -> i32 {
-    *CONFIG.lock().unwrap() = Some(config);
+/// - 4: Render stalled (watchdog fired)
+/// - 5: Cancelled
+/// - 7: Confirmed, remember the decision
+/// - 8: Denied, remember the decision
+/// - 9: The session lock couldn't be taken
+/// - 10 + n: Confirmed for `ConnectionScope::ALL[n]`
+/// - 16 + n: Custom action n
+///
+/// The returned report is the one sent to `result_socket`, if any, and
+/// written to `--result-fd` by `run_dialog_with_result_fd`.
+pub fn run(config: DialogConfig) -> RunOutcome {
+    if let Some(result) = decided_unshown(&config) {
+        return report_unshown(&config, result);
+    }
+    let (config, warnings) = prepare(config);
+
+    configure_cursor();
+    let run = start_run();
+    if let Some(secs) = config.watchdog_secs {
+        spawn_watchdog(secs, run);
+    }
+    let fonts = fallback_fonts(&config.fallback_fonts);
+    install(config, warnings);
+
+    match run_app(fonts) {
+        Ok(()) => conclude(),
+        Err(e) => lock_unavailable(&e),
+    }
+}
+
+/// Lock the session for the configs framed on stdin, writing a report for
+/// each to stdout, until stdin closes (the binary's `--stream`)
+///
+/// Returns the exit code: 0 once stdin closes, or `LOCK_UNAVAILABLE_EXIT`.
+pub fn run_stream() -> i32 {
+    *STREAM.lock().unwrap() = Some(Stream {
+        queue: VecDeque::new(),
+        closed: false,
+        out: Box::new(std::io::stdout()),
+    });
+    std::thread::spawn(|| read_stream(std::io::stdin().lock()));
+
+    // Nothing is locked until there is something to show
+    let Next::Show(config, warnings) = next_prompt(true) else {
+        return 0;
+    };
+    configure_cursor();
+    let run = start_run();
+    if let Some(secs) = config.watchdog_secs {
+        spawn_watchdog(secs, run);
+    }
+    // Fonts can only be added before the UI starts: the first config's apply
+    // to the whole stream
+    let fonts = fallback_fonts(&config.fallback_fonts);
+    install(config, warnings);
+
+    match run_app(fonts) {
+        Ok(()) => 0,
+        Err(e) => {
+            // No report: the parent learns why from the exit code and stderr
+            lock_unavailable(&e);
+            LOCK_UNAVAILABLE_EXIT
+        }
+    }
+}
+
+/// Tag for log lines, so daemons can match them to their request
+fn log_tag(request_id: Option<&str>) -> String {
+    request_id
+        .map(|id| format!(" [{}]", id))
+        .unwrap_or_default()
+}
+
+/// The result of a dialog decided before anything is shown, if it is
+fn decided_unshown(config: &DialogConfig) -> Option<DialogResult> {
+    // Incident-response kill switch, checked before anything is shown
+    if std::env::var_os(FORCE_DENY_ENV).is_some_and(|v| v == "1") {
+        eprintln!(
+            "session-dialog{}: denied {:?} by {}=1",
+            log_tag(config.request_id.as_deref()),
+            audit::clip(&config.log_detail()),
+            FORCE_DENY_ENV
+        );
+        return Some(DialogResult::Denied);
+    }
+
+    if let Some(code) = auto_result() {
+        return Some(result_from_exit_code(code));
+    }
+
+    // Cancelled before the lock was even taken
+    if CANCEL_REQUESTED.load(Ordering::SeqCst) {
+        return Some(DialogResult::Cancelled);
+    }
+    None
+}
+
+/// Read the detail file and check the requesting process, returning the
+/// config to show and the warnings to show with it
+fn prepare(mut config: DialogConfig) -> (DialogConfig, Vec<RequestWarning>) {
+    if let DialogKind::Generic { detail, .. } = &mut config.kind {
+        if let DetailSource::File(path) = detail {
+            *detail = DetailSource::Inline(read_detail_file(path));
+        }
+    }
+
+    let tag = log_tag(config.request_id.as_deref());
+    let mut warnings = config.warnings();
+    // Checked by `show_dialog` if it spawned us, else here
+    let check = config.process_check.clone();
+    match check.or_else(|| procinfo::check(&config.kind)) {
+        Some(ProcessCheck::Mismatch(reason)) => {
+            eprintln!("session-dialog{}: {}", tag, reason);
+            warnings.push(RequestWarning::ProcessMismatch(reason));
+        }
+        Some(ProcessCheck::Unverified(reason)) => {
+            eprintln!("session-dialog{}: {}", tag, reason);
+        }
+        Some(ProcessCheck::Matches) | None => {}
+    }
+    (config, warnings)
+}
 
-    let result = application(App::new, App::update, App::view)
+/// Take the session lock and run `App` until it unlocks
+fn run_app(fonts: Vec<Vec<u8>>) -> Result<(), String> {
+    let mut app = application(App::new, App::update, App::view)
         .subscription(App::subscription)
-        .run();
+        .theme(App::theme);
+    for font in fonts {
+        app = app.font(font);
+    }
+    app.run().map_err(|e| e.to_string())
+}
+
+/// Report the decision `App` made on the installed config
+fn conclude() -> RunOutcome {
+    let code = EXIT_CODE.load(Ordering::SeqCst);
+    let result = result_from_exit_code(code);
+    let elapsed = Duration::from_millis(ELAPSED_MS.load(Ordering::SeqCst));
+    let source = *SOURCE.lock().unwrap();
+    let (request_id, secret_fd, report_socket) = {
+        let guard = CONFIG.lock().unwrap();
+        let config = guard.as_ref().expect("config not set");
+        audit::record(config, result, code, elapsed, source);
+        (
+            config.request_id.clone(),
+            config.secret_fd,
+            config.result_socket.clone(),
+        )
+    };
+    match secret_fd {
+        Some(fd) => send_secret(fd, code),
+        // Nobody to hand it to; dropping wipes it
+        None => drop(SECRET.lock().unwrap().take()),
+    }
+    let report = DialogReport {
+        result,
+        elapsed,
+        request_id,
+        batch: std::mem::take(&mut *BATCH_RESULTS.lock().unwrap()),
+        warnings: WARNINGS.lock().unwrap().clone(),
+        source,
+        interacted: INTERACTED.load(Ordering::SeqCst),
+    };
+    if let Some(path) = report_socket {
+        send_report(&path, &report);
+    }
+    RunOutcome { code, report }
+}
+
+/// Log that the session lock couldn't be taken for the installed config,
+/// and report it as an error
+fn lock_unavailable(error: &str) -> RunOutcome {
+    let env = std::env::vars().collect();
+    let compositor = detect_compositor(&env);
+    let (tag, request_id) = {
+        let guard = CONFIG.lock().unwrap();
+        let config = guard.as_ref().expect("config not set");
+        (
+            log_tag(config.request_id.as_deref()),
+            config.request_id.clone(),
+        )
+    };
+    eprintln!(
+        "session-dialog{}: session lock unavailable (compositor: {}), it may not implement ext-session-lock-v1: {}",
+        tag,
+        compositor.as_deref().unwrap_or("unknown"),
+        error
+    );
+    RunOutcome {
+        code: LOCK_UNAVAILABLE_EXIT,
+        report: DialogReport {
+            result: DialogResult::Error,
+            elapsed: Duration::ZERO,
+            request_id,
+            batch: Vec::new(),
+            warnings: WARNINGS.lock().unwrap().clone(),
+            source: DecisionSource::inferred(DialogResult::Error),
+            interacted: false,
+        },
+    }
+}
+
+/// Make `config` the one `App` shows, with its locale and scale
+fn install(config: DialogConfig, warnings: Vec<RequestWarning>) {
+    *WARNINGS.lock().unwrap() = warnings;
+    *SOURCE.lock().unwrap() = DecisionSource::User;
+    INTERACTED.store(false, Ordering::SeqCst);
+    i18n::select(config.locale.as_deref());
+    let scale = config.scale.filter(|s| s.is_finite()).unwrap_or(1.0);
+    SCALE.store(
+        scale.clamp(MIN_SCALE, MAX_SCALE).to_bits(),
+        Ordering::SeqCst,
+    );
+    *CONFIG.lock().unwrap() = Some(config);
+}
+
+/// Draw the first frame `run` would show on a `width` x `height` output,
+/// as PNG, without taking the session lock
+#[cfg(feature = "preview")]
+pub(crate) fn render_preview(
+    config: DialogConfig,
+    width: u32,
+    height: u32,
+) -> std::io::Result<Vec<u8>> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    let warnings = config.warnings();
+    install(config, warnings);
+    let (app, _) = App::new();
+    let theme = app.theme();
+    let mut simulator = iced_test::Simulator::with_size(
+        iced::Settings::default(),
+        Size::new(width as f32, height as f32),
+        app.view(Id::unique()),
+    );
+    let snapshot = simulator
+        .snapshot(&theme)
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+    // Snapshots are only written out as files: `matches_image` saves one
+    // to a path that doesn't exist yet. A fresh private directory keeps
+    // a planted file from being compared against and read back instead.
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "session-dialog-preview-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let path = dir.join("preview.png");
+    let png = snapshot
+        .matches_image(&path)
+        .map_err(|e| std::io::Error::other(e.to_string()))
+        .and_then(|_| std::fs::read(&path));
+    let _ = std::fs::remove_dir_all(&dir);
+    png
+}
+
+/// How `run` ended: the exit code and the report behind it
+pub(crate) struct RunOutcome {
+    pub(crate) code: i32,
+    pub(crate) report: DialogReport,
+}
+
+/// Report a result decided before any UI was shown
+fn report_unshown(config: &DialogConfig, result: DialogResult) -> RunOutcome {
+    audit::record(
+        config,
+        result,
+        result.exit_code(),
+        Duration::ZERO,
+        DecisionSource::Forced,
+    );
+    let report = DialogReport {
+        result,
+        elapsed: Duration::ZERO,
+        request_id: config.request_id.clone(),
+        batch: Vec::new(),
+        warnings: config.warnings(),
+        source: DecisionSource::Forced,
+        interacted: false,
+    };
+    if let Some(path) = &config.result_socket {
+        send_report(path, &report);
+    }
+    RunOutcome {
+        code: result.exit_code(),
+        report,
+    }
+}
+
+/// State of `run_stream`
+struct Stream {
+    /// Configs read and not yet shown, or reports for frames that can't be
+    queue: VecDeque<Result<DialogConfig, DialogReport>>,
+    /// Stdin has closed (or the parent is gone): nothing more is queued
+    closed: bool,
+    /// Where reports go, stdout for the binary
+    out: Box<dyn Write + Send>,
+}
+
+/// What `run_stream` shows next
+enum Next {
+    Show(DialogConfig, Vec<RequestWarning>),
+    /// Nothing is queued yet
+    Wait,
+    /// Stdin has closed and every config is answered
+    Done,
+}
+
+/// Whether `run_stream` is running
+fn streaming() -> bool {
+    STREAM.lock().unwrap().is_some()
+}
+
+/// Queue each config framed on `input` for `run_stream`, until it closes
+fn read_stream(mut input: impl Read) {
+    loop {
+        let item = match read_frame(&mut input, MAX_CONFIG_LEN) {
+            Ok(Some(bytes)) => parse_streamed(&bytes),
+            Ok(None) => break,
+            // Skipped, so the next frame still lines up
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => Err(refused(None, e)),
+            Err(e) => {
+                eprintln!("session-dialog: failed to read config stream: {}", e);
+                break;
+            }
+        };
+        if let Some(stream) = STREAM.lock().unwrap().as_mut() {
+            stream.queue.push_back(item);
+        }
+        STREAM_READY.notify_all();
+    }
+    if let Some(stream) = STREAM.lock().unwrap().as_mut() {
+        stream.closed = true;
+    }
+    STREAM_READY.notify_all();
+}
+
+/// A streamed config to show, or the report refusing it
+fn parse_streamed(bytes: &[u8]) -> Result<DialogConfig, DialogReport> {
+    let config = DialogConfig::from_bytes(bytes)
+        .map_err(|e| refused(None, format!("failed to parse config: {}", e)))?;
+    let request_id = config.request_id.clone();
+    if let Err(e) = config.validate() {
+        return Err(refused(request_id, format!("invalid config: {}", e)));
+    }
+    // The secret descriptor belongs to a single dialog (`show_auth_dialog`)
+    if config.secret_fd.is_some() {
+        return Err(refused(request_id, "secret entry needs its own dialog"));
+    }
+    Ok(config)
+}
+
+/// Log why a streamed config isn't shown and report it as an error
+fn refused(request_id: Option<String>, reason: impl std::fmt::Display) -> DialogReport {
+    eprintln!(
+        "session-dialog{}: not shown: {}",
+        log_tag(request_id.as_deref()),
+        reason
+    );
+    DialogReport {
+        result: DialogResult::Error,
+        elapsed: Duration::ZERO,
+        request_id,
+        batch: Vec::new(),
+        warnings: Vec::new(),
+        source: DecisionSource::inferred(DialogResult::Error),
+        interacted: false,
+    }
+}
+
+/// Take the next config to show, answering those that aren't shown on
+/// the way; `block` waits for one instead of returning `Next::Wait`
+fn next_prompt(block: bool) -> Next {
+    loop {
+        let item = {
+            let mut guard = STREAM.lock().unwrap();
+            loop {
+                let Some(stream) = guard.as_mut() else {
+                    return Next::Done;
+                };
+                if let Some(item) = stream.queue.pop_front() {
+                    break item;
+                }
+                if stream.closed || TERMINATE.load(Ordering::SeqCst) {
+                    return Next::Done;
+                }
+                if !block {
+                    return Next::Wait;
+                }
+                // Woken early by new configs, late only to notice a signal
+                guard = STREAM_READY
+                    .wait_timeout(guard, Duration::from_millis(200))
+                    .unwrap()
+                    .0;
+            }
+        };
+        let config = match item {
+            Ok(config) => config,
+            Err(report) => {
+                write_stream_report(&report);
+                continue;
+            }
+        };
+        if let Some(result) = decided_unshown(&config) {
+            // A cancel only ever applies to one prompt
+            CANCEL_REQUESTED.store(false, Ordering::SeqCst);
+            write_stream_report(&report_unshown(&config, result).report);
+            continue;
+        }
+        let (config, warnings) = prepare(config);
+        return Next::Show(config, warnings);
+    }
+}
+
+/// Send `report` to the parent of `run_stream`
+///
+/// If the parent is gone nothing more can be answered, so the stream ends.
+fn write_stream_report(report: &DialogReport) {
+    let bytes = rmp_serde::to_vec(report).expect("serialize report");
+    let mut guard = STREAM.lock().unwrap();
+    let Some(stream) = guard.as_mut() else {
+        return;
+    };
+    if let Err(e) = write_frame(&mut stream.out, &bytes) {
+        eprintln!("session-dialog: failed to write report to stream: {}", e);
+        stream.queue.clear();
+        stream.closed = true;
+    }
+}
+
+/// Contents of a detail file, or an explanation of why it can't be shown
+fn read_detail_file(path: &Path) -> String {
+    use std::io::Read;
+
+    let mut bytes = Vec::new();
+    let read = std::fs::File::open(path)
+        .and_then(|file| file.take(MAX_DETAIL_FILE_LEN + 1).read_to_end(&mut bytes));
+    match read {
+        Err(e) => {
+            eprintln!(
+                "session-dialog: failed to read detail file {}: {}",
+                path.display(),
+                e
+            );
+            format!("⚠ Details could not be read from {}: {}", path.display(), e)
+        }
+        Ok(_) if bytes.len() as u64 > MAX_DETAIL_FILE_LEN => format!(
+            "⚠ Details in {} are too large to show (over {} KiB)",
+            path.display(),
+            MAX_DETAIL_FILE_LEN / 1024
+        ),
+        Ok(_) => String::from_utf8_lossy(&bytes).into_owned(),
+    }
+}
+
+/// Write the captured secret (if confirmed) to the caller's pipe and close it
+fn send_secret(fd: i32, code: i32) {
+    use std::os::fd::FromRawFd;
+
+    let secret = SECRET.lock().unwrap().take();
+    // SAFETY: `fd` was inherited from `show_auth_dialog` for this purpose
+    // and nothing else in this process uses it
+    let mut pipe = unsafe { std::fs::File::from_raw_fd(fd) };
+    if let Some(secret) = secret.filter(|_| result_from_exit_code(code).is_confirmed()) {
+        if let Err(e) = pipe.write_all(secret.as_bytes()) {
+            eprintln!("session-dialog: failed to send secret: {}", e);
+        }
+    }
+}
+
+/// Hand the structured report to the caller listening on `path`
+fn send_report(path: &Path, report: &DialogReport) {
+    let sent = UnixStream::connect(path).and_then(|mut stream| {
+        let bytes = rmp_serde::to_vec(report).expect("serialize report");
+        stream.write_all(&bytes)
+    });
+    if let Err(e) = sent {
+        eprintln!(
+            "session-dialog: failed to send report to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+/// Write the report as JSON to the caller's pipe (`--result-fd`) and close it
+pub(crate) fn write_report(fd: i32, report: &DialogReport) {
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: `fd` was inherited for this purpose and nothing else in this
+    // process uses it
+    let mut pipe = unsafe { std::fs::File::from_raw_fd(fd) };
+    let bytes = serde_json::to_vec(report).expect("serialize report");
+    if let Err(e) = pipe.write_all(&bytes) {
+        eprintln!("session-dialog: failed to write report to fd {}: {}", fd, e);
+    }
+}
+
+/// Pre-supplied result for integration tests, as an exit code
+///
+/// `SESSION_DIALOG_AUTO_RESULT` only takes effect together with
+/// `SESSION_DIALOG_TEST=1`, so a stray variable can't approve prompts in
+/// production. `SESSION_DIALOG_AUTO_DELAY_MS` optionally delays the answer.
+fn auto_result() -> Option<i32> {
+    let value = std::env::var(AUTO_RESULT_ENV).ok()?;
+    if std::env::var_os(TEST_MODE_ENV).is_none_or(|v| v != "1") {
+        eprintln!(
+            "session-dialog: ignoring {} without {}=1",
+            AUTO_RESULT_ENV, TEST_MODE_ENV
+        );
+        return None;
+    }
+
+    let result = match value.to_ascii_lowercase().as_str() {
+        "confirmed" => DialogResult::Confirmed,
+        "denied" => DialogResult::Denied,
+        "timeout" => DialogResult::Timeout,
+        other => {
+            eprintln!(
+                "session-dialog: unknown {} value: {}",
+                AUTO_RESULT_ENV, other
+            );
+            return None;
+        }
+    };
+
+    let delay_ms = std::env::var("SESSION_DIALOG_AUTO_DELAY_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    std::thread::sleep(Duration::from_millis(delay_ms));
 
-    match result {
-        Ok(()) => EXIT_CODE.load(Ordering::SeqCst),
-        Err(_) => 3, // Error
+    eprintln!("session-dialog: test mode, auto result {:?}", result);
+    Some(result.exit_code())
+}
+
+/// Forget the frames of earlier dialogs in this process, returning the
+/// new dialog's number for `stalled`
+fn start_run() -> u64 {
+    FRAME_SEEN.store(false, Ordering::SeqCst);
+    RUN.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+/// Whether dialog `run` is still up without a frame or tick so far
+fn stalled(run: u64) -> bool {
+    RUN.load(Ordering::SeqCst) == run && !FRAME_SEEN.load(Ordering::SeqCst)
+}
+
+/// Exit the process if no frame or tick arrives within `secs`
+///
+/// A hung event loop (GPU driver, compositor stall) would otherwise leave
+/// the calling daemon blocked forever. Only the dialog binary exits (with
+/// code 4); an inline dialog runs in the daemon itself, so it only logs.
+fn spawn_watchdog(secs: u32, run: u64) {
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_secs(secs as u64));
+        if !stalled(run) {
+            return;
+        }
+        // Only the binary installs signal handlers (`run_dialog`)
+        if SIGNALS_INSTALLED.load(Ordering::SeqCst) {
+            eprintln!(
+                "session-dialog: no frame rendered within {}s, denying",
+                secs
+            );
+            std::process::exit(4);
+        }
+        eprintln!(
+            "session-dialog: no frame rendered within {}s; the inline dialog can't be stopped",
+            secs
+        );
+    });
+}
+
+/// Deny and unlock on SIGTERM/SIGINT instead of dying with the lock held
+///
+/// Only the standalone binary installs these; inline callers keep their
+/// own signal handling.
+pub fn install_signal_handlers() {
+    extern "C" fn handle_signal(_signal: libc::c_int) {
+        TERMINATE.store(true, Ordering::SeqCst);
+    }
+    extern "C" fn handle_cancel(_signal: libc::c_int) {
+        CANCEL_REQUESTED.store(true, Ordering::SeqCst);
+    }
+
+    // SAFETY: the handlers only store to an atomic, which is signal-safe
+    unsafe {
+        libc::signal(libc::SIGTERM, handle_signal as libc::sighandler_t);
+        libc::signal(libc::SIGINT, handle_signal as libc::sighandler_t);
+        libc::signal(CANCEL_SIGNAL, handle_cancel as libc::sighandler_t);
+    }
+    SIGNALS_INSTALLED.store(true, Ordering::SeqCst);
+}
+
+/// Let the next inline dialog be cancelled through `request_cancel`
+pub(crate) fn enable_cancel() {
+    CANCEL_REQUESTED.store(false, Ordering::SeqCst);
+    CANCELABLE.store(true, Ordering::SeqCst);
+}
+
+/// Dismiss the inline dialog in this process, reporting `Cancelled`
+pub(crate) fn request_cancel() {
+    CANCEL_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+pub(crate) fn cancel_requested() -> bool {
+    CANCEL_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Held by tests that touch the process-wide state above, so they take turns
+#[cfg(test)]
+pub(crate) static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+// Common locations of broad-coverage fonts (CJK, emoji) across distros
+const SYSTEM_FALLBACK_FONTS: &[&str] = &[
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/truetype/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/google-noto-emoji/NotoColorEmoji.ttf",
+];
+
+/// Load fonts for glyphs the bundled font lacks, so non-Latin paths and
+/// commands don't render as boxes
+///
+/// Configured fonts come first; missing or unreadable files are skipped
+/// (and reported, for configured ones).
+fn fallback_fonts(configured: &[PathBuf]) -> Vec<Vec<u8>> {
+    let configured = configured.iter().filter_map(|path| {
+        std::fs::read(path)
+            .inspect_err(|e| {
+                eprintln!(
+                    "session-dialog: failed to load font {}: {}",
+                    path.display(),
+                    e
+                )
+            })
+            .ok()
+    });
+    let system = SYSTEM_FALLBACK_FONTS
+        .iter()
+        .filter_map(|path| std::fs::read(path).ok());
+    configured.chain(system).collect()
+}
+
+/// Make the user's XDG cursor theme apply to the lock surface
+///
+/// The Wayland backend loads the pointer theme from `XCURSOR_THEME` and
+/// `XCURSOR_SIZE` (forwarded from the caller's session). Unusable values are
+/// dropped so the toolkit falls back to its default theme and size instead
+/// of failing to load a cursor at all.
+fn configure_cursor() {
+    let theme_ok = std::env::var("XCURSOR_THEME").is_ok_and(|theme| !theme.trim().is_empty());
+    let size_ok = std::env::var("XCURSOR_SIZE")
+        .is_ok_and(|size| size.trim().parse::<u32>().is_ok_and(|px| px > 0));
+
+    // SAFETY: Called before the watchdog and UI threads are spawned
+    if !theme_ok {
+        unsafe { std::env::remove_var("XCURSOR_THEME") };
+    }
+    if !size_ok {
+        unsafe { std::env::remove_var("XCURSOR_SIZE") };
     }
 }
 
+/// Subscription tick rate, or None when nothing time-based is shown
+fn tick_interval(config: &DialogConfig) -> Option<Duration> {
+    let time_based = config.timeout_secs.is_some()
+        || config.nag_after.is_some()
+        || config.confirm_delay_secs.is_some()
+        || config.priority == DialogPriority::Urgent;
+    time_based.then(|| config.tick_interval.unwrap_or(DEFAULT_TICK_INTERVAL))
+}
+
 struct App {
-    start_time: std::time::Instant,
+    start_time: Instant,
+    /// When the prompt's surface opened, for `min_visible_ms`
+    visible_since: Instant,
+    palette: Palette,
+    /// Whether a key other than a modifier was pressed, or a button
+    /// clicked, since the dialog appeared
+    interacted: bool,
+    /// Last keypress or pointer movement, for inactivity timeouts
+    last_activity: Instant,
+    /// When confirm was pressed, while the safety countdown runs
+    confirming: Option<(Instant, DialogResult)>,
+    /// The user pressed T to keep the prompt open without a timeout
+    timeout_disabled: bool,
+    /// The timeout has fired; it never fires again, so Escape can abort
+    /// the confirm countdown it started
+    timed_out: bool,
+    /// Field selected for copying with Tab (see `copy_fields`)
+    focused_field: Option<usize>,
+    /// What was last copied: a field, or Some(None) for the whole detail
+    copied: Option<Option<usize>>,
+    /// A lock surface has opened, so the prompt is on screen
+    surface_opened: bool,
+    /// Loaded `DialogConfig::secure_token`
+    token: Option<Token>,
+    /// Decoded `DialogConfig::icon`
+    icon: Option<Icon>,
+    /// Decisions so far for each `DialogConfig::batch` item
+    batch: Vec<Option<DialogResult>>,
+    /// Batch item the per-item keys apply to
+    batch_selected: usize,
+    /// Last pointer position, where a right-click opens the menu
+    cursor: Point,
+    /// Start of the current touch, for long-press detection
+    touch_start: Option<(Instant, Point)>,
+    /// Where the context menu is open, if it is
+    menu: Option<Point>,
+    /// Answer from the last menu item (full path, explanation, ...)
+    menu_note: Option<String>,
+    /// Text typed into the `secret_prompt` field, if the dialog has one
+    secret: Option<Zeroizing<String>>,
+    /// The `extra_details` panel is expanded
+    show_details: bool,
+    /// Scope picked for allowing, when `offer_scope` applies
+    scope: Option<ConnectionScope>,
+    /// Stream mode: the last prompt is answered and no other is queued, so
+    /// only the empty lock screen shows
+    waiting: bool,
 }
 
 #[derive(Debug, Clone)]
 enum Message {
     Event(Event),
+    SurfaceOpened(Id),
     Tick,
+    CheckSignal,
+    /// An action button was clicked or tapped
+    Decide(DialogResult),
+    Menu(MenuItem),
+    /// The secret field was edited (the whole new contents), wiped when
+    /// dropped like every other copy the dialog keeps
+    SecretInput(Zeroizing<String>),
+    /// Stream mode: look for a queued config while waiting
+    CheckQueue,
     UnLock,
 }
 
+/// Entries of the optional context menu (`DialogConfig::context_menu`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuItem {
+    Copy,
+    ShowFullPath,
+    Explain,
+    Report,
+}
+
+impl MenuItem {
+    fn label(self) -> &'static str {
+        match self {
+            MenuItem::Copy => tr("Copy"),
+            MenuItem::ShowFullPath => tr("Show full path"),
+            MenuItem::Explain => tr("Why am I seeing this?"),
+            MenuItem::Report => tr("Report"),
+        }
+    }
+}
+
 impl TryInto<UnLockAction> for Message {
     type Error = Self;
     fn try_into(self) -> Result<UnLockAction, Self::Error> {
@@ -76,191 +889,1971 @@ impl App {
     fn new() -> (Self, Task<Message>) {
         (
             Self {
-                start_time: std::time::Instant::now(),
+                start_time: Instant::now(),
+                visible_since: Instant::now(),
+                palette: theming::resolve(
+                    CONFIG
+                        .lock()
+                        .unwrap()
+                        .as_ref()
+                        .and_then(|c| c.theme.as_ref()),
+                ),
+                interacted: false,
+                last_activity: Instant::now(),
+                confirming: None,
+                timeout_disabled: false,
+                timed_out: false,
+                focused_field: None,
+                copied: None,
+                surface_opened: false,
+                batch: vec![None; CONFIG.lock().unwrap().as_ref().map_or(0, |c| c.batch.len())],
+                batch_selected: 0,
+                cursor: Point::ORIGIN,
+                touch_start: None,
+                menu: None,
+                menu_note: None,
+                show_details: false,
+                scope: CONFIG
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .filter(|c| scope_offered(c))
+                    .map(|_| ConnectionScope::default()),
+                secret: CONFIG
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .filter(|c| c.secret_prompt.is_some() && c.batch.is_empty())
+                    .map(|_| Zeroizing::default()),
+                token: CONFIG
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .and_then(|c| c.secure_token.as_ref())
+                    .and_then(load_token),
+                icon: CONFIG
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .and_then(|c| c.icon.as_ref())
+                    .map(load_icon),
+                waiting: false,
             },
             Task::none(),
         )
     }
 
-    fn subscription(&self) -> Subscription<Message> {
-        let events = iced::event::listen().map(Message::Event);
+    fn handle_key(&mut self, key: &Key) -> Task<Message> {
+        let (actions, confirm_delay, timeout_cancelable, has_details) = {
+            let guard = CONFIG.lock().unwrap();
+            let config = guard.as_ref().expect("config not set");
+            (
+                config.actions(),
+                config.confirm_delay_secs,
+                config.allow_timeout_cancel && config.timeout_secs.is_some(),
+                !extra_details(config).is_empty(),
+            )
+        };
+
+        // An open menu takes Escape for itself
+        if self.menu.is_some() {
+            if let Key::Named(keyboard::key::Named::Escape) = key {
+                self.menu = None;
+                return Task::none();
+            }
+        }
+
+        // During the confirm countdown only Escape (abort) does anything
+        if self.confirming.is_some() {
+            if let Key::Named(keyboard::key::Named::Escape) = key {
+                self.confirming = None;
+            }
+            return Task::none();
+        }
+
+        if !self.batch.is_empty() {
+            return self.handle_batch_key(key);
+        }
+
+        // Custom actions win, so T, I, 1-3 and Tab only apply when they're unbound
+        let Some(action) = actions.iter().find(|a| key_matches(&a.key, key)) else {
+            if timeout_cancelable && key_matches(TIMEOUT_CANCEL_KEY, key) {
+                self.timeout_disabled = true;
+            } else if has_details && key_matches(DETAILS_KEY, key) {
+                self.show_details = !self.show_details;
+            } else if let Some(scope) = scope_for_key(key).filter(|_| self.scope.is_some()) {
+                self.scope = Some(scope);
+            } else if key_matches("Tab", key) {
+                self.focus_next_field();
+            }
+            return Task::none();
+        };
+        if self.too_early(action.result) {
+            return Task::none();
+        }
+        self.decide(action.result, confirm_delay)
+    }
+
+    /// Whether `result` comes within `min_visible_ms` of the prompt
+    /// appearing and must be ignored
+    fn too_early(&self, result: DialogResult) -> bool {
+        let guard = CONFIG.lock().unwrap();
+        let config = guard.as_ref().expect("config not set");
+        let min_visible = config
+            .min_visible_ms
+            .map_or(DEFAULT_MIN_VISIBLE, |ms| Duration::from_millis(ms as u64));
+        let gated = result.is_confirmed() || config.min_visible_gates_deny;
+        gated && self.visible_since.elapsed() < min_visible
+    }
+
+    /// Act on a chosen result, starting the confirm countdown if configured
+    fn decide(&mut self, result: DialogResult, confirm_delay: Option<u32>) -> Task<Message> {
+        // Confirming needs the secret, denying never does
+        if result.is_confirmed() && self.secret.as_ref().is_some_and(|s| s.is_empty()) {
+            return Task::none();
+        }
+        let result = match (result, self.scope) {
+            (DialogResult::Confirmed, Some(scope)) => DialogResult::ConfirmedScope(scope),
+            _ => result,
+        };
+        if result.is_confirmed() && confirm_delay.is_some() {
+            self.confirming = Some((Instant::now(), result));
+            return Task::none();
+        }
+        self.finish(result)
+    }
 
-        // Check timeout if configured
-        let has_timeout = CONFIG
+    /// Same as pressing the key of the clicked action button
+    fn handle_button(&mut self, result: DialogResult) -> Task<Message> {
+        if self.confirming.is_some() || self.too_early(result) {
+            return Task::none();
+        }
+        if !self.batch.is_empty() {
+            return self.decide_batch_item(result);
+        }
+        let confirm_delay = CONFIG
             .lock()
             .unwrap()
             .as_ref()
-            .and_then(|c| c.timeout_secs)
-            .is_some();
+            .and_then(|c| c.confirm_delay_secs);
+        self.decide(result, confirm_delay)
+    }
 
-        if has_timeout {
-            let tick = iced::time::every(std::time::Duration::from_secs(1)).map(|_| Message::Tick);
-            return Subscription::batch([events, tick]);
-        }
+    /// Up/Down pick an item, A/D decide it, Enter/Esc decide all the rest
+    fn handle_batch_key(&mut self, key: &Key) -> Task<Message> {
+        use keyboard::key::Named;
 
-        events
+        match key.as_ref() {
+            Key::Named(Named::ArrowUp) => {
+                self.batch_selected = self.batch_selected.saturating_sub(1);
+            }
+            Key::Named(Named::ArrowDown) => {
+                self.batch_selected = (self.batch_selected + 1).min(self.batch.len() - 1);
+            }
+            Key::Character("a") if !self.too_early(DialogResult::Confirmed) => {
+                return self.decide_batch_item(DialogResult::Confirmed)
+            }
+            Key::Character("d") if !self.too_early(DialogResult::Denied) => {
+                return self.decide_batch_item(DialogResult::Denied)
+            }
+            Key::Named(Named::Enter) if !self.too_early(DialogResult::Confirmed) => {
+                return self.finish_batch(DialogResult::Confirmed)
+            }
+            Key::Named(Named::Escape) if !self.too_early(DialogResult::Denied) => {
+                return self.finish_batch(DialogResult::Denied)
+            }
+            _ => {}
+        }
+        Task::none()
     }
 
-    fn update(&mut self, message: Message) -> Task<Message> {
-        match message {
-            Message::Event(Event::Keyboard(keyboard::Event::KeyPressed { key, .. })) => {
-                match key {
-                    Key::Named(keyboard::key::Named::Enter) => {
-                        EXIT_CODE.store(0, Ordering::SeqCst); // Confirmed
-                        Task::done(Message::UnLock)
-                    }
-                    Key::Named(keyboard::key::Named::Escape) => {
-                        EXIT_CODE.store(1, Ordering::SeqCst); // Denied
-                        Task::done(Message::UnLock)
-                    }
-                    _ => Task::none(),
-                }
-            }
-            Message::Tick => {
-                let timeout = CONFIG.lock().unwrap().as_ref().and_then(|c| c.timeout_secs);
-                if let Some(timeout) = timeout {
-                    if self.start_time.elapsed().as_secs() >= timeout as u64 {
-                        EXIT_CODE.store(2, Ordering::SeqCst); // Timeout
-                        return Task::done(Message::UnLock);
-                    }
-                }
+    /// Record a choice for the selected item and move to the next open one
+    fn decide_batch_item(&mut self, result: DialogResult) -> Task<Message> {
+        self.batch[self.batch_selected] = Some(result);
+        match next_pending(&self.batch, self.batch_selected) {
+            Some(next) => {
+                self.batch_selected = next;
                 Task::none()
             }
-            Message::UnLock => Task::done(Message::UnLock),
-            _ => Task::none(),
+            None => self.finish_batch(result),
         }
     }
 
-    fn view(&self, _id: Id) -> Element<'_, Message> {
-        let (title, subtitle, detail, timeout_secs) = {
+    /// Give every undecided item `pending` and close the dialog
+    ///
+    /// The exit code is Confirmed only if every item was confirmed.
+    fn finish_batch(&mut self, pending: DialogResult) -> Task<Message> {
+        let results: Vec<_> = self.batch.iter().map(|r| r.unwrap_or(pending)).collect();
+        let overall = if results.iter().all(|&r| r == DialogResult::Confirmed) {
+            DialogResult::Confirmed
+        } else {
+            DialogResult::Denied
+        };
+        *BATCH_RESULTS.lock().unwrap() = results;
+        self.finish(overall)
+    }
+
+    /// Ctrl+C copies the focused field (or the whole detail), Ctrl+N field N
+    fn handle_copy(&mut self, key: &Key) -> Task<Message> {
+        if !self.batch.is_empty() {
+            return Task::none();
+        }
+
+        let guard = CONFIG.lock().unwrap();
+        let config = guard.as_ref().expect("config not set");
+
+        let index = match key.as_ref() {
+            Key::Character("c") => self.focused_field,
+            Key::Character(c) => match c.parse::<usize>() {
+                Ok(n @ 1..=9) => Some(n - 1),
+                _ => return Task::none(),
+            },
+            _ => return Task::none(),
+        };
+        let Some(value) = copy_value(config, index) else {
+            return Task::none();
+        };
+        self.focused_field = index;
+        self.copied = Some(index);
+        iced::clipboard::write(value)
+    }
+
+    /// Open the context menu at `at`, if the config offers one
+    fn open_menu(&mut self, at: Point) {
+        let enabled = {
             let guard = CONFIG.lock().unwrap();
             let config = guard.as_ref().expect("config not set");
-            (
-                config.title().to_string(),
-                config.subtitle().to_string(),
-                config.detail(),
-                config.timeout_secs,
-            )
+            config.context_menu && config.batch.is_empty()
         };
+        if enabled && self.confirming.is_none() {
+            self.menu = Some(at);
+        }
+    }
 
-        let elapsed_secs = self.start_time.elapsed().as_secs() as u32;
+    /// Carry out a context menu choice and close the menu
+    fn handle_menu(&mut self, item: MenuItem) -> Task<Message> {
+        self.menu = None;
+        let guard = CONFIG.lock().unwrap();
+        let config = guard.as_ref().expect("config not set");
 
-        let card = container(
-            column![
-                header(&title, &subtitle),
-                command_block(&detail),
-                divider(),
-                footer(timeout_secs, elapsed_secs),
-            ]
-            .spacing(22),
-        )
-        .width(Length::Fixed(CARD_WIDTH))
-        .padding(32)
-        .style(card_style);
-
-        container(card)
-            .center_x(Length::Fill)
-            .center_y(Length::Fill)
-            .style(|_theme| container::Style {
-                background: Some(SCRIM.into()),
-                ..Default::default()
-            })
-            .into()
+        match item {
+            MenuItem::Copy => {
+                let Some(value) = copy_value(config, None) else {
+                    return Task::none();
+                };
+                self.copied = Some(None);
+                return iced::clipboard::write(value);
+            }
+            MenuItem::ShowFullPath => {
+                self.menu_note = full_path(config).map(|path| path.display().to_string());
+            }
+            MenuItem::Explain => self.menu_note = Some(tr(explanation(config)).to_string()),
+            MenuItem::Report => {
+                report(config);
+                self.menu_note = Some(tr("Reported. You still need to allow or deny.").to_string());
+            }
+        }
+        Task::none()
     }
-}
 
-fn header<'a>(title: &str, subtitle: &str) -> Element<'a, Message> {
-    column![
-        text(title.to_string())
-            .size(30)
-            .font(Font {
-                weight: Weight::Bold,
-                ..Font::DEFAULT
-            })
-            .color(TEXT_PRIMARY),
-        text(subtitle.to_string()).size(18).color(TEXT_BODY),
-    ]
-    .spacing(8)
-    .into()
+    /// Move the copy focus to the next field, wrapping back to none
+    fn focus_next_field(&mut self) {
+        let count = {
+            let guard = CONFIG.lock().unwrap();
+            copy_fields(guard.as_ref().expect("config not set")).len()
+        };
+        self.focused_field = match self.focused_field {
+            None if count > 0 => Some(0),
+            Some(i) if i + 1 < count => Some(i + 1),
+            _ => None,
+        };
+        self.copied = None;
+    }
+
+    /// Record the decision and release the lock (or, streaming, move on)
+    fn finish(&mut self, result: DialogResult) -> Task<Message> {
+        // Only a confirmed secret is kept for `send_secret`; dropping the
+        // others wipes them
+        if let Some(secret) = self.secret.take().filter(|_| result.is_confirmed()) {
+            *SECRET.lock().unwrap() = Some(secret);
+        }
+        EXIT_CODE.store(result.exit_code(), Ordering::SeqCst);
+        INTERACTED.store(self.interacted, Ordering::SeqCst);
+        ELAPSED_MS.store(
+            self.start_time.elapsed().as_millis() as u64,
+            Ordering::SeqCst,
+        );
+        if streaming() {
+            return self.next_in_stream();
+        }
+        Task::done(Message::UnLock)
+    }
+
+    /// Stream mode: report the decision just made and show the next prompt
+    fn next_in_stream(&mut self) -> Task<Message> {
+        write_stream_report(&conclude().report);
+        // A signal ends the stream; a cancel only ends the prompt it hit
+        if TERMINATE.load(Ordering::SeqCst) {
+            return Task::done(Message::UnLock);
+        }
+        CANCEL_REQUESTED.store(false, Ordering::SeqCst);
+        self.show_next()
+    }
+
+    /// Stream mode: swap in the next queued config, or wait for one
+    fn show_next(&mut self) -> Task<Message> {
+        match next_prompt(false) {
+            Next::Show(config, warnings) => {
+                install(config, warnings);
+                // Fresh state, with `min_visible_ms` counted from now
+                *self = Self {
+                    surface_opened: self.surface_opened,
+                    ..Self::new().0
+                };
+                if self.secret.is_some() {
+                    return iced::widget::operation::focus(SECRET_INPUT);
+                }
+                Task::none()
+            }
+            Next::Wait => {
+                self.waiting = true;
+                Task::none()
+            }
+            Next::Done => Task::done(Message::UnLock),
+        }
+    }
+
+    /// Time counted toward the timeout (since last activity if configured)
+    fn timeout_elapsed(&self, config: &DialogConfig) -> Duration {
+        if config.timeout_resets_on_activity {
+            self.last_activity.elapsed()
+        } else {
+            self.start_time.elapsed()
+        }
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let mut events = Subscription::batch([
+            iced::event::listen().map(Message::Event),
+            iced::window::open_events().map(Message::SurfaceOpened),
+        ]);
+
+        // Poll for SIGTERM/SIGINT recorded by the signal handler, or a
+        // cancel from a `DialogTask`
+        if SIGNALS_INSTALLED.load(Ordering::SeqCst) || CANCELABLE.load(Ordering::SeqCst) {
+            let signals =
+                iced::time::every(Duration::from_millis(200)).map(|_| Message::CheckSignal);
+            events = Subscription::batch([events, signals]);
+        }
+
+        // Pick up streamed configs that arrive while nothing is shown
+        if streaming() {
+            let queue = iced::time::every(Duration::from_millis(100)).map(|_| Message::CheckQueue);
+            events = Subscription::batch([events, queue]);
+        }
+
+        // Tick only while a time-based feature needs it
+        let interval = CONFIG.lock().unwrap().as_ref().and_then(tick_interval);
+        if let Some(interval) = interval {
+            let tick = iced::time::every(interval).map(|_| Message::Tick);
+            return Subscription::batch([events, tick]);
+        }
+
+        events
+    }
+
+    fn update(&mut self, message: Message) -> Task<Message> {
+        // Between streamed prompts there is nothing to answer
+        if self.waiting {
+            return match message {
+                Message::CheckQueue => {
+                    self.waiting = false;
+                    self.show_next()
+                }
+                Message::CheckSignal => {
+                    CANCEL_REQUESTED.store(false, Ordering::SeqCst);
+                    if TERMINATE.load(Ordering::SeqCst) {
+                        return Task::done(Message::UnLock);
+                    }
+                    Task::none()
+                }
+                Message::UnLock => Task::done(Message::UnLock),
+                _ => Task::none(),
+            };
+        }
+
+        match message {
+            Message::Event(Event::Keyboard(keyboard::Event::KeyPressed {
+                key, modifiers, ..
+            })) => {
+                self.interacted |= !is_modifier(&key);
+                self.last_activity = Instant::now();
+                if modifiers.control() {
+                    return self.handle_copy(&key);
+                }
+                self.handle_key(&key)
+            }
+            Message::SurfaceOpened(_) => {
+                // Every output shows the prompt; it is visible from the first
+                if !self.surface_opened {
+                    self.visible_since = Instant::now();
+                }
+                self.surface_opened = true;
+                if self.secret.is_some() {
+                    return iced::widget::operation::focus(SECRET_INPUT);
+                }
+                Task::none()
+            }
+            Message::Event(Event::Mouse(mouse::Event::CursorMoved { position })) => {
+                self.cursor = position;
+                self.last_activity = Instant::now();
+                Task::none()
+            }
+            Message::Event(Event::Mouse(mouse::Event::ButtonPressed(pressed))) => {
+                // Widgets capture clicks on menu items, so this is elsewhere
+                match pressed {
+                    mouse::Button::Right => self.open_menu(self.cursor),
+                    _ => self.menu = None,
+                }
+                Task::none()
+            }
+            Message::Event(Event::Touch(touch::Event::FingerPressed { position, .. })) => {
+                self.touch_start = Some((Instant::now(), position));
+                self.menu = None;
+                Task::none()
+            }
+            Message::Event(Event::Touch(touch::Event::FingerLifted { .. })) => {
+                if let Some((since, at)) = self.touch_start.take() {
+                    if since.elapsed() >= LONG_PRESS {
+                        self.open_menu(at);
+                    }
+                }
+                Task::none()
+            }
+            Message::Decide(result) => {
+                self.interacted = true;
+                self.last_activity = Instant::now();
+                self.menu = None;
+                self.handle_button(result)
+            }
+            Message::Menu(item) => {
+                self.interacted = true;
+                self.last_activity = Instant::now();
+                self.handle_menu(item)
+            }
+            Message::SecretInput(value) => {
+                self.interacted = true;
+                self.last_activity = Instant::now();
+                if let Some(secret) = &mut self.secret {
+                    // Replacing the buffer wipes the previous contents
+                    *secret = value;
+                }
+                Task::none()
+            }
+            Message::Tick => {
+                FRAME_SEEN.store(true, Ordering::SeqCst);
+                let (timeout, elapsed, confirm_delay, timeout_action) = {
+                    let guard = CONFIG.lock().unwrap();
+                    let config = guard.as_ref().expect("config not set");
+                    (
+                        config.timeout_secs,
+                        self.timeout_elapsed(config),
+                        config.confirm_delay_secs,
+                        config.effective_timeout_action(),
+                    )
+                };
+                if let (Some((since, result)), Some(delay)) = (self.confirming, confirm_delay) {
+                    if since.elapsed() >= Duration::from_secs(delay as u64) {
+                        return self.finish(result);
+                    }
+                    // The user has decided; the timeout no longer applies
+                    return Task::none();
+                }
+                if let Some(timeout) = timeout.filter(|_| !self.timeout_disabled && !self.timed_out)
+                {
+                    // Compare against the deadline so the tick rate can't skew it
+                    if elapsed >= Duration::from_secs(timeout as u64) {
+                        self.timed_out = true;
+                        let result = DialogResult::Timeout;
+                        *SOURCE.lock().unwrap() = DecisionSource::Timeout;
+                        // Never approve a whole batch unseen
+                        if !self.batch.is_empty() {
+                            return self.finish_batch(result);
+                        }
+                        // Escalation and secret prompts never get Confirm here
+                        if timeout_action == TimeoutAction::Confirm {
+                            return self.decide(DialogResult::Confirmed, confirm_delay);
+                        }
+                        return self.finish(result);
+                    }
+                }
+                Task::none()
+            }
+            Message::CheckSignal => {
+                if TERMINATE.load(Ordering::SeqCst) {
+                    *SOURCE.lock().unwrap() = DecisionSource::Forced;
+                    return self.finish(DialogResult::Denied);
+                }
+                if CANCEL_REQUESTED.load(Ordering::SeqCst) {
+                    *SOURCE.lock().unwrap() = DecisionSource::Forced;
+                    return self.finish(DialogResult::Cancelled);
+                }
+                Task::none()
+            }
+            Message::UnLock => Task::done(Message::UnLock),
+            _ => Task::none(),
+        }
+    }
+
+    fn theme(&self) -> iced::Theme {
+        self.palette.to_iced_theme()
+    }
+
+    fn view(&self, _id: Id) -> Element<'_, Message> {
+        FRAME_SEEN.store(true, Ordering::SeqCst);
+
+        let scrim = self.palette.scrim;
+        if self.waiting {
+            return container(Space::new().width(Length::Fill).height(Length::Fill))
+                .style(move |_theme| container::Style {
+                    background: Some(scrim.into()),
+                    ..Default::default()
+                })
+                .into();
+        }
+
+        let model = {
+            let guard = CONFIG.lock().unwrap();
+            let config = guard.as_ref().expect("config not set");
+            ViewModel {
+                token: self.token.clone(),
+                icon: self.icon.clone(),
+                warnings: WARNINGS.lock().unwrap().clone(),
+                focused_field: self.focused_field,
+                copied: self.copied,
+                batch: batch_rows(config, &self.batch, self.batch_selected),
+                menu: self.menu.map(|at| (at, menu_items(config))),
+                menu_note: self.menu_note.clone(),
+                secret: config
+                    .secret_prompt
+                    .clone()
+                    .map(|label| clip(label, LABEL_MAX_CHARS))
+                    .zip(self.secret.clone()),
+                scope: self.scope,
+                urgency: (config.priority == DialogPriority::Urgent && !self.interacted)
+                    .then(|| urgent_pulse(self.start_time.elapsed())),
+                details: if self.show_details {
+                    extra_details(config)
+                } else {
+                    Vec::new()
+                },
+                ..ViewModel::new(
+                    config,
+                    self.start_time.elapsed(),
+                    self.timeout_elapsed(config),
+                    self.confirming.map(|(since, _)| since.elapsed()),
+                    self.timeout_disabled || self.timed_out,
+                )
+            }
+        };
+
+        let p = self.palette;
+        container(responsive(move |size| {
+            screen(&p, &model, layout_mode(size))
+        }))
+        .style(move |_theme| container::Style {
+            background: Some(scrim.into()),
+            ..Default::default()
+        })
+        .into()
+    }
 }
 
-fn command_block<'a>(detail: &str) -> Element<'a, Message> {
-    container(
+/// `size` (a font size or length) times `DialogConfig::scale`
+fn scaled(size: f32) -> f32 {
+    size * f32::from_bits(SCALE.load(Ordering::Relaxed))
+}
+
+/// How the card is laid out for the size of the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LayoutMode {
+    /// Fixed-width card centered on screen, secure badge in the corner
+    Regular,
+    /// Full-screen card for tiny outputs: the action row is pinned to the
+    /// bottom and everything above it scrolls
+    Compact,
+}
+
+// Below this the regular card would clip (card plus a little margin)
+const MIN_REGULAR_WIDTH: f32 = CARD_WIDTH + 48.0;
+const MIN_REGULAR_HEIGHT: f32 = 480.0;
+
+fn layout_mode(size: Size) -> LayoutMode {
+    if size.width < scaled(MIN_REGULAR_WIDTH) || size.height < scaled(MIN_REGULAR_HEIGHT) {
+        LayoutMode::Compact
+    } else {
+        LayoutMode::Regular
+    }
+}
+
+/// The whole lock surface for one layout mode
+fn screen<'a>(p: &Palette, model: &ViewModel, mode: LayoutMode) -> Element<'a, Message> {
+    let card = card(p, model, mode);
+    let card = match model.backdrop {
+        Some(backdrop) => stack![dim_layer(backdrop.dim), card].into(),
+        None => card,
+    };
+    match &model.menu {
+        Some((at, items)) => stack![card, context_menu(p, *at, items)].into(),
+        None => card,
+    }
+}
+
+/// Full-screen black layer of opacity `dim`, under the card
+fn dim_layer<'a>(dim: f32) -> Element<'a, Message> {
+    container(Space::new().width(Length::Fill).height(Length::Fill))
+        .style(move |_theme| container::Style {
+            background: Some(
+                Color {
+                    a: dim,
+                    ..Color::BLACK
+                }
+                .into(),
+            ),
+            ..Default::default()
+        })
+        .into()
+}
+
+/// The dialog card (with the secure badge in regular mode)
+fn card<'a>(p: &Palette, model: &ViewModel, mode: LayoutMode) -> Element<'a, Message> {
+    let mut body = column![header(
+        p,
+        model.icon.as_ref(),
+        &model.title,
+        &model.subtitle,
+        model.rich_text
+    )]
+    .spacing(model.layout.spacing);
+    body = if model.batch.is_empty() {
+        body.push(command_block(p, model))
+    } else {
+        body.push(batch_list(p, &model.batch))
+    };
+    if let Some(scope) = model.scope {
+        body = body.push(scope_picker(p, scope, &model.scope_labels));
+    }
+    if !model.details.is_empty() {
+        body = body.push(detail_grid(p, &model.details));
+    }
+    if !model.detail_rows.is_empty() {
+        body = body.push(detail_grid(p, &model.detail_rows));
+    }
+    if let Some(note) = &model.menu_note {
+        body = body.push(text(note.clone()).size(scaled(16.0)).color(p.text_body));
+    }
+    if let Some((label, value)) = &model.secret {
+        body = body.push(secret_field(p, label, value));
+    }
+    for warning in &model.warnings {
+        body = body.push(request_warning(p, warning));
+    }
+    if model.copy_labels.len() > 1 {
+        body = body.push(copy_legend(p, model));
+    }
+
+    if mode == LayoutMode::Compact {
+        // Responding must stay possible however little room there is
+        let card = column![
+            scrollable(body).height(Length::Fill),
+            divider(p),
+            footer(p, model),
+        ]
+        .spacing(10);
+        return container(card)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .padding(12)
+            .style(card_style(p, model.card_opacity(), model.urgency))
+            .into();
+    }
+
+    let card = container(body.push(divider(p)).push(footer(p, model)))
+        .width(Length::Fixed(scaled(CARD_WIDTH)))
+        .padding(model.layout.padding)
+        .style(card_style(p, model.card_opacity(), model.urgency));
+
+    let centered = container(card)
+        .center_x(Length::Fill)
+        .center_y(Length::Fill);
+    let corner = container(secure_badge(p, model.token.as_ref()))
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .padding(24)
+        .align_left(Length::Fill)
+        .align_bottom(Length::Fill);
+
+    stack![centered, corner].into()
+}
+
+/// Everything the dialog shows, resolved from config and elapsed time
+///
+/// Keeping this separate from the widget builders lets layout decisions be
+/// inspected without a renderer.
+#[derive(Debug, Clone, PartialEq)]
+struct ViewModel {
+    title: String,
+    subtitle: String,
+    detail: String,
+    /// Keys that do something right now, in footer order
+    hints: Vec<KeyHint>,
+    /// Time left before the timeout fires (None = no timeout)
+    remaining: Option<Duration>,
+    /// `remaining` as a share of the whole timeout, for the drain bar
+    remaining_fraction: Option<f32>,
+    /// What the countdown leads to, for its wording
+    timeout_action: TimeoutAction,
+    /// Subtitle and detail carry markdown-lite formatting
+    rich_text: bool,
+    /// Detail is a command line, colored by `cmdline::segments`
+    highlight_command: bool,
+    layout: LayoutConfig,
+    /// Dimmed backdrop, values clamped to 0.0-1.0
+    backdrop: Option<BackdropConfig>,
+    /// Phase (0.0-1.0) of the urgent border pulse while it runs (set from
+    /// `App`)
+    urgency: Option<f32>,
+    /// How the deny hint is drawn (see `DialogConfig::nag_after`)
+    nag: Nag,
+    /// Seconds left in the confirm safety countdown, while it runs
+    confirming_in: Option<u32>,
+    /// Red flags in the request (see `heuristics`)
+    warnings: Vec<RequestWarning>,
+    /// The user's anti-spoofing token for the secure badge (set from `App`)
+    token: Option<Token>,
+    /// Picture above the title (set from `App`)
+    icon: Option<Icon>,
+    /// Names of the fields that can be copied one by one
+    copy_labels: Vec<&'static str>,
+    /// Field selected for copying (set from `App`)
+    focused_field: Option<usize>,
+    /// Last copied field, Some(None) for the whole detail (set from `App`)
+    copied: Option<Option<usize>>,
+    /// Items of a batch dialog, shown instead of the detail (set from `App`)
+    batch: Vec<BatchRow>,
+    /// Open context menu position and entries (set from `App`)
+    menu: Option<(Point, Vec<MenuItem>)>,
+    /// Answer from the last menu item (set from `App`)
+    menu_note: Option<String>,
+    /// Key/value facts under the detail (`DialogConfig::detail_rows`)
+    detail_rows: Vec<(String, String)>,
+    /// Expanded `extra_details`, empty while collapsed (set from `App`)
+    details: Vec<(String, String)>,
+    /// Picked scope, when the dialog offers one (set from `App`)
+    scope: Option<ConnectionScope>,
+    /// What each of `ConnectionScope::ALL` covers, e.g. "Only example.com:443"
+    scope_labels: Vec<String>,
+    /// Label and contents of the secret field (set from `App`)
+    secret: Option<(String, Zeroizing<String>)>,
+}
+
+/// One request in a batch dialog
+#[derive(Debug, Clone, PartialEq)]
+struct BatchRow {
+    title: String,
+    detail: String,
+    decision: Option<DialogResult>,
+    selected: bool,
+}
+
+fn batch_rows(
+    config: &DialogConfig,
+    decisions: &[Option<DialogResult>],
+    selected: usize,
+) -> Vec<BatchRow> {
+    config
+        .batch
+        .iter()
+        .zip(decisions)
+        .enumerate()
+        .map(|(i, (kind, &decision))| {
+            let item = DialogConfig::new(kind.clone());
+            BatchRow {
+                title: clip(item.title().to_string(), TITLE_MAX_CHARS),
+                detail: shown_detail(&item),
+                decision,
+                selected: i == selected,
+            }
+        })
+        .collect()
+}
+
+/// First undecided item after `from`, wrapping around
+fn next_pending(decisions: &[Option<DialogResult>], from: usize) -> Option<usize> {
+    (1..=decisions.len())
+        .map(|offset| (from + offset) % decisions.len())
+        .find(|&i| decisions[i].is_none())
+}
+
+/// Emphasis of the deny hint once the user has been idle too long
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Nag {
+    Off,
+    Bright,
+    Dim,
+}
+
+/// Pick the nag phase: off before `nag_after`, then pulsing every 500ms
+fn nag_state(nag_after: Option<Duration>, shown: Duration) -> Nag {
+    match nag_after {
+        Some(after) if shown >= after => {
+            if (shown.as_millis() / 500) % 2 == 0 {
+                Nag::Bright
+            } else {
+                Nag::Dim
+            }
+        }
+        _ => Nag::Off,
+    }
+}
+
+/// Phase of the urgent border pulse: 0.0 to 1.0 and back every 1.6s
+fn urgent_pulse(shown: Duration) -> f32 {
+    let turn = (shown.as_millis() % 1600) as f32 / 1600.0;
+    (1.0 - (turn * std::f32::consts::TAU).cos()) / 2.0
+}
+
+impl ViewModel {
+    /// `shown` counts from when the dialog appeared, `timeout_elapsed`
+    /// toward the timeout (which may restart on activity), and `confirming`
+    /// since confirm was pressed during a safety countdown
+    fn new(
+        config: &DialogConfig,
+        shown: Duration,
+        timeout_elapsed: Duration,
+        confirming: Option<Duration>,
+        timeout_disabled: bool,
+    ) -> Self {
+        // A generic dialog's text is the caller's, already in their language
+        let (title, subtitle) = match config.batch.len() {
+            0 if matches!(config.kind, DialogKind::Generic { .. }) => {
+                (config.title().to_string(), config.subtitle().to_string())
+            }
+            0 => (
+                tr(config.title()).to_string(),
+                tr(config.subtitle()).to_string(),
+            ),
+            n => (
+                tr("{} Requests Pending").replace("{}", &n.to_string()),
+                tr("Review each request, or decide all at once:").to_string(),
+            ),
+        };
+        Self {
+            title: clip(title, TITLE_MAX_CHARS),
+            subtitle: clip(subtitle, SUBTITLE_MAX_CHARS),
+            detail: shown_detail(config),
+            hints: key_hints(config, timeout_disabled),
+            remaining: config
+                .timeout_secs
+                .filter(|_| !timeout_disabled)
+                .map(|timeout| Duration::from_secs(timeout as u64).saturating_sub(timeout_elapsed)),
+            remaining_fraction: config
+                .timeout_secs
+                .filter(|&timeout| !timeout_disabled && timeout > 0)
+                .map(|timeout| {
+                    let total = Duration::from_secs(timeout as u64);
+                    total.saturating_sub(timeout_elapsed).as_secs_f32() / total.as_secs_f32()
+                }),
+            timeout_action: config.effective_timeout_action(),
+            rich_text: renders_markup(config),
+            highlight_command: matches!(config.kind, DialogKind::PrivilegeEscalation { .. }),
+            layout: LayoutConfig {
+                padding: scaled(config.layout.padding),
+                spacing: scaled(config.layout.spacing),
+                action_spacing: scaled(config.layout.action_spacing),
+            },
+            backdrop: config.backdrop.map(|b| BackdropConfig {
+                dim: b.dim.clamp(0.0, 1.0),
+                card_opacity: b.card_opacity.clamp(0.0, 1.0),
+            }),
+            nag: nag_state(config.nag_after, shown),
+            confirming_in: confirming
+                .zip(config.confirm_delay_secs)
+                .map(|(since, delay)| delay.saturating_sub(since.as_secs() as u32)),
+            warnings: config.warnings(),
+            token: None,
+            icon: None,
+            copy_labels: copy_fields(config)
+                .into_iter()
+                .filter(|_| config.batch.is_empty())
+                .map(|(label, _)| label)
+                .collect(),
+            focused_field: None,
+            copied: None,
+            batch: Vec::new(),
+            menu: None,
+            menu_note: None,
+            secret: None,
+            details: Vec::new(),
+            scope: None,
+            scope_labels: scope_labels(config),
+            urgency: None,
+            detail_rows: config
+                .detail_rows
+                .iter()
+                .filter(|_| config.batch.is_empty())
+                .map(|(key, value)| {
+                    (
+                        clip(key.clone(), TITLE_MAX_CHARS),
+                        elide_detail(value.clone()),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Opacity of the card background: opaque without a backdrop
+    fn card_opacity(&self) -> f32 {
+        self.backdrop.map_or(1.0, |b| b.card_opacity)
+    }
+}
+
+fn header<'a>(
+    p: &Palette,
+    icon: Option<&Icon>,
+    title: &str,
+    subtitle: &str,
+    rich: bool,
+) -> Element<'a, Message> {
+    let subtitle = if rich {
+        formatted(p, subtitle, 18, p.text_body, Font::DEFAULT)
+    } else {
+        text(subtitle.to_string())
+            .size(scaled(18.0))
+            .color(p.text_body)
+            .into()
+    };
+
+    let title = text(title.to_string())
+        .size(scaled(30.0))
+        .font(Font {
+            weight: Weight::Bold,
+            ..Font::DEFAULT
+        })
+        .color(p.text_primary);
+    let mut header = column![].spacing(8);
+    match icon {
+        Some(Icon::Glyph(glyph)) => header = header.push(text(*glyph).size(scaled(40.0))),
+        Some(Icon::Image(handle)) => header = header.push(image(handle.clone()).height(48)),
+        None => {}
+    }
+    header.push(title).push(subtitle).into()
+}
+
+fn command_block<'a>(p: &Palette, model: &ViewModel) -> Element<'a, Message> {
+    let detail = &model.detail;
+    let content: Element<'a, Message> = if model.rich_text {
+        formatted(p, detail, 19, p.accent, Font::MONOSPACE)
+    } else if model.highlight_command {
+        highlighted_command(p, detail)
+    } else {
         text(detail.to_string())
-            .size(19)
+            .size(scaled(19.0))
             .font(Font::MONOSPACE)
-            .color(ACCENT)
-            .wrapping(text::Wrapping::WordOrGlyph),
-    )
-    .width(Length::Fill)
-    .padding([16, 18])
-    .style(|_theme| container::Style {
-        background: Some(INSET_BG.into()),
-        border: iced::Border {
-            color: CARD_BORDER,
-            width: 1.0,
-            radius: Radius::from(8.0),
-        },
-        ..Default::default()
-    })
+            .color(p.accent)
+            .wrapping(text::Wrapping::WordOrGlyph)
+            .into()
+    };
+
+    let p = *p;
+    container(scrollable(content))
+        .width(Length::Fill)
+        .max_height(scaled(DETAIL_MAX_HEIGHT))
+        .padding([16, 18])
+        .style(move |_theme| container::Style {
+            background: Some(p.inset_bg.into()),
+            border: iced::Border {
+                color: p.card_border,
+                width: p.border_width,
+                radius: Radius::from(8.0),
+            },
+            ..Default::default()
+        })
+        .into()
+}
+
+/// Masked entry for `DialogConfig::secret_prompt`; Enter confirms
+fn secret_field<'a>(p: &Palette, label: &str, value: &str) -> Element<'a, Message> {
+    let p = *p;
+    let input = text_input("", value)
+        .id(SECRET_INPUT)
+        .secure(true)
+        .on_input(|value| Message::SecretInput(Zeroizing::new(value)))
+        .on_submit(Message::Decide(DialogResult::Confirmed))
+        .size(scaled(19.0))
+        .padding([12, 16])
+        .style(move |_theme, _status| text_input::Style {
+            background: p.inset_bg.into(),
+            border: iced::Border {
+                color: p.accent,
+                width: p.border_width,
+                radius: Radius::from(8.0),
+            },
+            icon: p.text_muted,
+            placeholder: p.text_muted,
+            value: p.text_primary,
+            selection: Color { a: 0.4, ..p.accent },
+        });
+
+    column![
+        text(label.to_string())
+            .size(scaled(16.0))
+            .color(p.text_body),
+        input
+    ]
+    .spacing(8)
     .into()
 }
 
-fn divider<'a>() -> Element<'a, Message> {
+/// Whether the title and detail are parsed as markup rather than shown
+/// as given (only generic dialogs, and only when asked for)
+fn renders_markup(config: &DialogConfig) -> bool {
+    config.rich_text && matches!(config.kind, DialogKind::Generic { .. })
+}
+
+/// The detail as shown: elided when very long, except for a command to be
+/// run, which is shown whole however long (the detail block scrolls)
+fn shown_detail(config: &DialogConfig) -> String {
+    match config.kind {
+        DialogKind::PrivilegeEscalation { .. } => config.detail(),
+        _ => elide_detail(config.detail()),
+    }
+}
+
+/// Cut very long detail at `DETAIL_MAX_CHARS`, saying how much is hidden
+fn elide_detail(detail: String) -> String {
+    let Some((cut, _)) = detail.char_indices().nth(DETAIL_MAX_CHARS) else {
+        return detail;
+    };
+    let hidden = detail[cut..].chars().count();
+    let note = tr("({} more characters, Ctrl+C copies all)").replace("{}", &hidden.to_string());
+    format!("{}… {}", &detail[..cut], note)
+}
+
+/// Cut `text` at `max` characters, marking the cut with an ellipsis
+fn clip(text: String, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text,
+    }
+}
+
+/// A command line with program, flags and paths told apart by color
+///
+/// Display only: the spans are plain text with no link or click action.
+fn highlighted_command<'a>(p: &Palette, command: &str) -> Element<'a, Message> {
+    let spans: Vec<text::Span<'a, (), Font>> = cmdline::segments(command)
+        .into_iter()
+        .map(|segment| {
+            let word = command[segment.range].to_string();
+            match segment.part {
+                Part::Program => span(word).color(p.text_primary).font(Font {
+                    weight: Weight::Bold,
+                    ..Font::MONOSPACE
+                }),
+                Part::Flag => span(word).color(p.text_muted).font(Font::MONOSPACE),
+                Part::Path => span(word).color(p.accent).font(Font::MONOSPACE),
+                Part::Argument | Part::Space => span(word).color(p.text_body).font(Font::MONOSPACE),
+            }
+        })
+        .collect();
+    rich_text(spans)
+        .size(scaled(19.0))
+        .wrapping(text::Wrapping::WordOrGlyph)
+        .into()
+}
+
+/// Render markdown-lite text, one line per block
+fn formatted<'a>(
+    p: &Palette,
+    source: &str,
+    size: u32,
+    color: Color,
+    font: Font,
+) -> Element<'a, Message> {
+    let lines = markup::parse(source).into_iter().map(|block| {
+        let (prefix, spans) = match block {
+            Block::Paragraph(spans) => (None, spans),
+            Block::Bullet(spans) => (Some("•  "), spans),
+        };
+        let spans: Vec<text::Span<'a, (), Font>> = prefix
+            .map(|p| span(p).color(color).font(font))
+            .into_iter()
+            .chain(spans.into_iter().map(|s| match s.style {
+                markup::Style::Plain => span(s.text).color(color).font(font),
+                markup::Style::Bold => span(s.text).color(p.text_primary).font(Font {
+                    weight: Weight::Bold,
+                    ..font
+                }),
+                markup::Style::Code => span(s.text).color(p.accent).font(Font::MONOSPACE),
+            }))
+            .collect();
+        rich_text(spans)
+            .size(scaled(size as f32))
+            .wrapping(text::Wrapping::WordOrGlyph)
+            .into()
+    });
+
+    Column::with_children(lines).spacing(4).into()
+}
+
+/// A key and what it does, as listed in the footer
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KeyHint {
+    key: String,
+    label: String,
+    /// Result the key reports (None for keys that don't decide)
+    result: Option<DialogResult>,
+}
+
+impl KeyHint {
+    fn new(key: &str, label: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            label: label.to_string(),
+            result: None,
+        }
+    }
+}
+
+/// Hints for every key that currently does something
+///
+/// Built from the same bindings `handle_key` uses, so the footer never
+/// advertises a key that is shadowed by a custom action or disabled.
+fn key_hints(config: &DialogConfig, timeout_disabled: bool) -> Vec<KeyHint> {
+    if !config.batch.is_empty() {
+        return vec![
+            KeyHint::new("↑↓", tr("Select")),
+            KeyHint {
+                result: Some(DialogResult::Confirmed),
+                ..KeyHint::new("A", tr("Allow"))
+            },
+            KeyHint {
+                result: Some(DialogResult::Denied),
+                ..KeyHint::new("D", tr("Deny"))
+            },
+            KeyHint::new("Enter", tr("Allow rest")),
+            KeyHint::new("Esc", tr("Deny rest")),
+        ];
+    }
+
+    let actions = config.actions();
+    let bound = |key: &str| actions.iter().any(|a| a.key.eq_ignore_ascii_case(key));
+
+    let mut hints: Vec<KeyHint> = actions
+        .iter()
+        .map(|action| KeyHint {
+            key: clip(action.key.clone(), LABEL_MAX_CHARS),
+            label: clip(tr(&action.label).to_string(), LABEL_MAX_CHARS),
+            result: Some(action.result),
+        })
+        .collect();
+    if config.allow_timeout_cancel
+        && config.timeout_secs.is_some()
+        && !timeout_disabled
+        && !bound(TIMEOUT_CANCEL_KEY)
+    {
+        hints.push(KeyHint::new("T", tr("No timeout")));
+    }
+    if !extra_details(config).is_empty() && !bound(DETAILS_KEY) {
+        hints.push(KeyHint::new("I", tr("Details")));
+    }
+    if copy_fields(config).len() > 1 {
+        // Ctrl+N per field is listed in the copy legend
+        if !bound("Tab") {
+            hints.push(KeyHint::new("Tab", tr("Select")));
+        }
+    } else {
+        hints.push(KeyHint::new("Ctrl+C", tr("Copy")));
+    }
+    hints
+}
+
+/// Whether allowing asks for a `ConnectionScope`
+fn scope_offered(config: &DialogConfig) -> bool {
+    config.offer_scope
+        && config.batch.is_empty()
+        && matches!(config.kind, DialogKind::NetworkConnection { .. })
+}
+
+/// Scope chosen by a number key: 1 for the first of `ConnectionScope::ALL`
+fn scope_for_key(key: &Key) -> Option<ConnectionScope> {
+    let Key::Character(c) = key.as_ref() else {
+        return None;
+    };
+    let index = c.parse::<usize>().ok()?.checked_sub(1)?;
+    ConnectionScope::ALL.get(index).copied()
+}
+
+/// Descriptions of `ConnectionScope::ALL` for this request
+fn scope_labels(config: &DialogConfig) -> Vec<String> {
+    let DialogKind::NetworkConnection {
+        process,
+        destination,
+        port,
+        ..
+    } = &config.kind
+    else {
+        return Vec::new();
+    };
+    if !scope_offered(config) {
+        return Vec::new();
+    }
+    vec![
+        tr("Only {}").replace("{}", &format!("{}:{}", destination, port)),
+        tr("{}, any port").replace("{}", destination),
+        tr("{}, anywhere").replace("{}", process),
+    ]
+}
+
+/// The scope choices with their number keys, the picked one marked
+fn scope_picker<'a>(
+    p: &Palette,
+    picked: ConnectionScope,
+    labels: &[String],
+) -> Element<'a, Message> {
+    let mut picker =
+        column![text(tr("Allow for")).size(scaled(16.0)).color(p.text_muted)].spacing(6);
+    for (i, (scope, label)) in ConnectionScope::ALL.iter().zip(labels).enumerate() {
+        let (mark, color) = if *scope == picked {
+            ("●", p.accent)
+        } else {
+            ("○", p.text_body)
+        };
+        picker = picker.push(
+            row![
+                keycap(p, &(i + 1).to_string()),
+                text(format!("{} {}", mark, label))
+                    .size(scaled(16.0))
+                    .color(color),
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+        );
+    }
+    picker.into()
+}
+
+/// Facts about the request kept out of the summary until the user asks
+///
+/// For firewall prompts: the binary's path, PID and command line.
+fn extra_details(config: &DialogConfig) -> Vec<(String, String)> {
+    let DialogKind::NetworkConnection {
+        process,
+        process_path,
+        pid,
+        cmdline,
+        ancestry,
+        ..
+    } = &config.kind
+    else {
+        return Vec::new();
+    };
+    if !config.batch.is_empty() {
+        return Vec::new();
+    }
+    let mut rows = vec![(tr("Path").to_string(), process_path.display().to_string())];
+    if !ancestry.is_empty() {
+        // Session leader first, down to the requester
+        let tree: Vec<&str> = ancestry
+            .iter()
+            .rev()
+            .chain(std::iter::once(process))
+            .map(String::as_str)
+            .collect();
+        rows.push((tr("Process tree").to_string(), tree.join(" → ")));
+    }
+    if let Some(pid) = pid {
+        rows.push((tr("PID").to_string(), pid.to_string()));
+    }
+    if let Some(cmdline) = cmdline {
+        rows.push((
+            tr("Command line").to_string(),
+            elide_detail(cmdline.clone()),
+        ));
+    }
+    rows
+}
+
+/// Fields of the request that can be copied individually, with labels
+fn copy_fields(config: &DialogConfig) -> Vec<(&'static str, String)> {
+    match &config.kind {
+        DialogKind::PrivilegeEscalation { command } => vec![("command", command.clone())],
+        DialogKind::NetworkConnection {
+            process,
+            process_path,
+            destination,
+            port,
+            ..
+        } => vec![
+            ("process", process.clone()),
+            ("path", process_path.display().to_string()),
+            ("destination", destination.clone()),
+            ("port", port.to_string()),
+        ],
+        DialogKind::Generic { .. } => vec![("detail", config.detail())],
+        DialogKind::DeviceAccess {
+            device_name,
+            vendor,
+            product,
+            serial,
+            ..
+        } => {
+            let mut fields = vec![
+                ("device", device_name.clone()),
+                ("vendor", vendor.clone()),
+                ("product", product.clone()),
+            ];
+            fields.extend(serial.clone().map(|serial| ("serial", serial)));
+            fields
+        }
+    }
+}
+
+/// Text to copy for a field index, or the whole detail line for None
+fn copy_value(config: &DialogConfig, index: Option<usize>) -> Option<String> {
+    match index {
+        None => Some(config.detail()),
+        Some(i) => copy_fields(config).into_iter().nth(i).map(|(_, v)| v),
+    }
+}
+
+/// "Ctrl+1 process  Ctrl+2 path ..." with the focused field highlighted
+fn copy_legend<'a>(p: &Palette, model: &ViewModel) -> Element<'a, Message> {
+    let mut legend = row![].spacing(8).align_y(Alignment::Center);
+    for (i, label) in model.copy_labels.iter().enumerate() {
+        let color = if model.focused_field == Some(i) {
+            p.accent
+        } else {
+            p.text_muted
+        };
+        legend = legend
+            .push(keycap(p, &format!("Ctrl+{}", i + 1)))
+            .push(text(tr(label)).size(scaled(14.0)).color(color));
+    }
+    if let Some(copied) = model.copied {
+        let what = copied.map_or("detail", |i| model.copy_labels[i]);
+        legend = legend.push(Space::new().width(Length::Fill)).push(
+            text(tr("Copied {}").replace("{}", tr(what)))
+                .size(scaled(14.0))
+                .color(p.success),
+        );
+    }
+    legend.into()
+}
+
+/// `DialogConfig::detail_rows` as labels in one column, values in another
+fn detail_grid<'a>(p: &Palette, rows: &[(String, String)]) -> Element<'a, Message> {
+    let mut grid = column![].spacing(6);
+    for (key, value) in rows {
+        grid = grid.push(
+            row![
+                text(key.clone())
+                    .size(scaled(16.0))
+                    .color(p.text_muted)
+                    .width(Length::FillPortion(1)),
+                text(value.clone())
+                    .size(scaled(16.0))
+                    .font(Font::MONOSPACE)
+                    .color(p.text_body)
+                    .wrapping(text::Wrapping::WordOrGlyph)
+                    .width(Length::FillPortion(3)),
+            ]
+            .spacing(12),
+        );
+    }
+    grid.into()
+}
+
+/// The requests of a batch dialog with their decisions so far
+fn batch_list<'a>(p: &Palette, rows: &[BatchRow]) -> Element<'a, Message> {
+    let mut list = Column::new().spacing(6);
+    for row in rows {
+        let (status, color) = match row.decision {
+            None => (tr("Pending"), p.text_muted),
+            Some(DialogResult::Confirmed) => (tr("Allowed"), p.success),
+            Some(_) => (tr("Denied"), p.danger),
+        };
+        let line = row![
+            column![
+                text(row.title.clone())
+                    .size(scaled(15.0))
+                    .color(p.text_body),
+                text(row.detail.clone())
+                    .size(scaled(16.0))
+                    .font(Font::MONOSPACE)
+                    .color(p.accent)
+                    .wrapping(text::Wrapping::WordOrGlyph),
+            ]
+            .spacing(2)
+            .width(Length::Fill),
+            text(status).size(scaled(15.0)).color(color),
+        ]
+        .spacing(12)
+        .align_y(Alignment::Center);
+
+        let p = *p;
+        let selected = row.selected;
+        list = list.push(container(line).width(Length::Fill).padding([8, 12]).style(
+            move |_theme| container::Style {
+                background: selected.then(|| p.inset_bg.into()),
+                border: iced::Border {
+                    color: if selected { p.accent } else { p.card_border },
+                    width: 1.0,
+                    radius: Radius::from(8.0),
+                },
+                ..Default::default()
+            },
+        ));
+    }
+    container(scrollable(list))
+        .max_height(scaled(DETAIL_MAX_HEIGHT))
+        .into()
+}
+
+/// Flag a suspicious request prominently
+fn request_warning<'a>(p: &Palette, warning: &RequestWarning) -> Element<'a, Message> {
+    let message = match warning {
+        RequestWarning::ShellExpansion(expansions) => {
+            tr("Contains shell expansion ({}); the command that actually runs may differ")
+                .replace("{}", &expansions.join(", "))
+        }
+        // The reason itself stays as logged, in English
+        RequestWarning::ProcessMismatch(reason) => {
+            tr("{}. This may not be the program that made the request").replace("{}", reason)
+        }
+        RequestWarning::NestedEscalation(tool) => {
+            tr("Runs {} itself: a privilege request inside a privilege request").replace("{}", tool)
+        }
+    };
+    text(format!("⚠ {}", message))
+        .size(scaled(16.0))
+        .color(p.danger)
+        .wrapping(text::Wrapping::WordOrGlyph)
+        .into()
+}
+
+/// Context menu entries that apply to this request
+fn menu_items(config: &DialogConfig) -> Vec<MenuItem> {
+    let mut items = vec![MenuItem::Copy];
+    if full_path(config).is_some() {
+        items.push(MenuItem::ShowFullPath);
+    }
+    items.extend([MenuItem::Explain, MenuItem::Report]);
+    items
+}
+
+/// The program behind the request, resolved like the shell would
+///
+/// For commands this searches `PATH` for the first word, so it shows what
+/// this user's `PATH` finds, which may differ from what the daemon runs.
+fn full_path(config: &DialogConfig) -> Option<PathBuf> {
+    match &config.kind {
+        DialogKind::PrivilegeEscalation { command } => {
+            let program = command.split_whitespace().next()?;
+            if program.contains('/') {
+                return std::fs::canonicalize(program).ok();
+            }
+            std::env::split_paths(&std::env::var_os("PATH")?)
+                .map(|dir| dir.join(program))
+                .find(|path| path.is_file())
+                .and_then(|path| std::fs::canonicalize(path).ok())
+        }
+        DialogKind::NetworkConnection { process_path, .. } => {
+            Some(std::fs::canonicalize(process_path).unwrap_or_else(|_| process_path.clone()))
+        }
+        DialogKind::Generic { .. } | DialogKind::DeviceAccess { .. } => None,
+    }
+}
+
+/// Plain-language reason for the prompt, for "Why am I seeing this?"
+fn explanation(config: &DialogConfig) -> &'static str {
+    match &config.kind {
+        DialogKind::PrivilegeEscalation { .. } => {
+            "A program asked to run this command with administrator rights. Allow it only if you just started something that needs them."
+        }
+        DialogKind::NetworkConnection { .. } => {
+            "The firewall stopped a program from connecting to the network until you decide. Allow it only if you expect this program to go online."
+        }
+        DialogKind::Generic { .. } => {
+            "A system service needs your decision before it continues. The screen is locked so no other program can answer for you."
+        }
+        DialogKind::DeviceAccess { .. } => {
+            "A device was just plugged in and is held back until you decide. Allow it only if you connected it yourself; unknown USB devices can carry malware."
+        }
+    }
+}
+
+/// Record a user report and run `report_command`, without waiting for it
+fn report(config: &DialogConfig) {
+    let tag = config
+        .request_id
+        .as_deref()
+        .map(|id| format!(" [{}]", id))
+        .unwrap_or_default();
+    let detail = config.log_detail();
+    eprintln!(
+        "session-dialog{}: reported by user: {:?}",
+        tag,
+        audit::clip(&detail)
+    );
+
+    let Some(mut command) = report_command(config, &detail, &[SECRET_FD, RESULT_FD]) else {
+        return;
+    };
+    match command.spawn() {
+        // Reap it in the background so the dialog never blocks on it
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => eprintln!("session-dialog{}: report command failed: {}", tag, e),
+    }
+}
+
+/// `report_command` ready to spawn, with `private_fds` closed in the child
+///
+/// The secret and report pipes lost close-on-exec when they were moved into
+/// place; a helper holding them would keep the caller's read blocked and
+/// could write into them.
+fn report_command(
+    config: &DialogConfig,
+    detail: &str,
+    private_fds: &[RawFd],
+) -> Option<std::process::Command> {
+    use std::os::unix::process::CommandExt;
+
+    let (program, args) = config.report_command.as_ref()?.split_first()?;
+    let mut command = std::process::Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .env("SESSION_DIALOG_REPORT_DETAIL", detail)
+        .env(
+            "SESSION_DIALOG_REQUEST_ID",
+            config.request_id.as_deref().unwrap_or_default(),
+        );
+    let private_fds = private_fds.to_vec();
+    // SAFETY: close is async-signal-safe; a descriptor that isn't open
+    // just fails with EBADF
+    unsafe {
+        command.pre_exec(move || {
+            for &fd in &private_fds {
+                libc::close(fd);
+            }
+            Ok(())
+        });
+    }
+    Some(command)
+}
+
+/// The menu itself, with its top-left corner at `at`
+fn context_menu<'a>(p: &Palette, at: Point, items: &[MenuItem]) -> Element<'a, Message> {
+    let p = *p;
+    let entries = items.iter().map(|&item| {
+        button(text(item.label()).size(scaled(16.0)).color(p.text_primary))
+            .width(Length::Fill)
+            .padding([8, 14])
+            .on_press(Message::Menu(item))
+            .style(move |_theme, status| button::Style {
+                background: matches!(status, button::Status::Hovered | button::Status::Pressed)
+                    .then(|| p.keycap_bg.into()),
+                ..Default::default()
+            })
+            .into()
+    });
+    let menu = container(Column::with_children(entries).width(Length::Fixed(scaled(240.0))))
+        .padding(4)
+        .style(move |_theme| container::Style {
+            background: Some(p.card_bg.into()),
+            border: iced::Border {
+                color: p.card_border,
+                width: 1.0,
+                radius: Radius::from(6.0),
+            },
+            ..Default::default()
+        });
+
+    container(menu)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .padding(Padding {
+            top: at.y,
+            left: at.x,
+            ..Padding::ZERO
+        })
+        .into()
+}
+
+/// A `DialogIcon` ready to draw
+#[derive(Debug, Clone, PartialEq)]
+enum Icon {
+    Glyph(&'static str),
+    Image(image::Handle),
+}
+
+/// Builtins are emoji, drawn with the fallback emoji font
+fn load_icon(icon: &DialogIcon) -> Icon {
+    match icon {
+        DialogIcon::Shield => Icon::Glyph("🛡️"),
+        DialogIcon::Network => Icon::Glyph("🌐"),
+        DialogIcon::Warning => Icon::Glyph("⚠️"),
+        DialogIcon::Png(bytes) => Icon::Image(image::Handle::from_bytes(bytes.clone())),
+    }
+}
+
+/// A `SecureToken` ready to draw
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Phrase(String),
+    Image(image::Handle),
+}
+
+/// Read the token, refusing files other users could have read
+fn load_token(token: &SecureToken) -> Option<Token> {
+    let path = match token {
+        SecureToken::Phrase(phrase) => return Some(Token::Phrase(phrase.clone())),
+        SecureToken::PhraseFile(path) | SecureToken::Image(path) => path,
+    };
+
+    let loaded = private_file(path).and_then(|meta| match token {
+        SecureToken::Image(_) if meta.len() > MAX_TOKEN_IMAGE_LEN => {
+            Err("image is too large".to_string())
+        }
+        SecureToken::Image(_) => std::fs::read(path)
+            .map(|bytes| Token::Image(image::Handle::from_bytes(bytes)))
+            .map_err(|e| e.to_string()),
+        _ => std::fs::read_to_string(path)
+            .map(|phrase| Token::Phrase(phrase.trim().to_string()))
+            .map_err(|e| e.to_string()),
+    });
+    // Never log the token itself, only why it is missing
+    loaded
+        .inspect_err(|e| {
+            eprintln!(
+                "session-dialog: not showing secure token {}: {}",
+                path.display(),
+                e
+            )
+        })
+        .ok()
+}
+
+// Metadata of `path` if it is a regular file only its owner (us) can read
+fn private_file(path: &Path) -> Result<std::fs::Metadata, String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = std::fs::metadata(path).map_err(|e| e.to_string())?;
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+    if !meta.is_file() || meta.uid() != uid {
+        return Err("not a file owned by this user".to_string());
+    }
+    if meta.mode() & 0o077 != 0 {
+        return Err("readable by other users (expected mode 0600)".to_string());
+    }
+    Ok(meta)
+}
+
+/// Persistent "Secure prompt" badge in the screen corner
+///
+/// Drawn outside the card on the lock surface itself, where no ordinary
+/// window can reach, so users learn to expect it on genuine prompts.
+fn secure_badge<'a>(p: &Palette, token: Option<&Token>) -> Element<'a, Message> {
+    let mut badge = row![
+        text("🔒").size(scaled(16.0)),
+        text(tr("Secure prompt"))
+            .size(scaled(15.0))
+            .font(Font {
+                weight: Weight::Bold,
+                ..Font::DEFAULT
+            })
+            .color(p.success),
+    ]
+    .spacing(8)
+    .align_y(Alignment::Center);
+    match token {
+        Some(Token::Phrase(phrase)) => {
+            badge = badge.push(
+                text(format!("· {}", phrase))
+                    .size(scaled(15.0))
+                    .color(p.text_body),
+            );
+        }
+        Some(Token::Image(handle)) => {
+            badge = badge.push(image(handle.clone()).height(32));
+        }
+        None => {}
+    }
+
+    let p = *p;
+    container(badge)
+        .padding([8, 14])
+        .style(move |_theme| container::Style {
+            background: Some(p.inset_bg.into()),
+            border: iced::Border {
+                color: p.success,
+                width: 2.0,
+                radius: Radius::from(16.0),
+            },
+            ..Default::default()
+        })
+        .into()
+}
+
+fn divider<'a>(p: &Palette) -> Element<'a, Message> {
+    let border = p.card_border;
     container(Space::new().width(Length::Fill).height(1))
-        .style(|_theme| container::Style {
-            background: Some(CARD_BORDER.into()),
+        .style(move |_theme| container::Style {
+            background: Some(border.into()),
             ..Default::default()
         })
         .into()
 }
 
-fn footer<'a>(timeout_secs: Option<u32>, elapsed_secs: u32) -> Element<'a, Message> {
-    let mut footer = row![
-        keycap("Enter"),
-        text("Allow").size(17).color(SUCCESS),
-        Space::new().width(16),
-        keycap("Esc"),
-        text("Deny").size(17).color(DANGER),
-    ]
-    .spacing(10)
-    .align_y(Alignment::Center);
+fn footer<'a>(p: &Palette, model: &ViewModel) -> Element<'a, Message> {
+    let mut footer = row![].spacing(10).align_y(Alignment::Center);
+    let mut buttons = row![]
+        .spacing(model.layout.action_spacing)
+        .align_y(Alignment::Center);
+
+    if let Some(remaining) = model.confirming_in {
+        return footer
+            .push(
+                text(tr("Confirming in {}…").replace("{}", &remaining.to_string()))
+                    .size(scaled(17.0))
+                    .color(p.success),
+            )
+            .push(keycap(p, "Esc"))
+            .push(text(tr("to cancel")).size(scaled(17.0)).color(p.text_muted))
+            .into();
+    }
+
+    // Deciding keys are also buttons; the rest stay plain hints
+    let mut first_hint = true;
+    for hint in &model.hints {
+        if let Some(result) = hint.result {
+            buttons = buttons.push(action_button(p, hint, result, model.nag));
+            continue;
+        }
+        if !first_hint {
+            footer = footer.push(Space::new().width(model.layout.action_spacing));
+        }
+        first_hint = false;
+        footer = footer.push(keycap(p, &hint.key)).push(
+            text(hint.label.clone())
+                .size(scaled(17.0))
+                .color(p.text_muted),
+        );
+    }
 
-    if let Some(timeout) = timeout_secs {
-        let remaining = timeout.saturating_sub(elapsed_secs);
-        let color = if remaining <= 5 { DANGER } else { TEXT_MUTED };
+    if let Some(remaining) = model.remaining {
+        let color = if remaining <= Duration::from_secs(5) {
+            p.danger
+        } else {
+            p.text_muted
+        };
         footer = footer.push(Space::new().width(Length::Fill)).push(
-            text(format!("Auto-deny in {}s", remaining))
-                .size(16)
-                .font(Font::MONOSPACE)
-                .color(color),
+            text(format!(
+                "{} {}",
+                countdown_label(model.timeout_action),
+                format_remaining(remaining)
+            ))
+            .size(scaled(16.0))
+            .font(Font::MONOSPACE)
+            .color(color),
         );
     }
 
-    footer.into()
+    let mut footer_rows = column![].spacing(14);
+    if let Some(fraction) = model.remaining_fraction {
+        footer_rows = footer_rows.push(timeout_bar(p, fraction));
+    }
+    footer_rows
+        .push(container(buttons).center_x(Length::Fill))
+        .push(footer)
+        .into()
 }
 
-fn keycap<'a>(label: &'static str) -> Element<'a, Message> {
-    container(text(label).size(15).font(Font::MONOSPACE).color(TEXT_BODY))
-        .padding([4, 10])
-        .style(|_theme| container::Style {
-            background: Some(KEYCAP_BG.into()),
+/// Bar draining from full to empty as the timeout runs out
+fn timeout_bar<'a>(p: &Palette, fraction: f32) -> Element<'a, Message> {
+    let p = *p;
+    progress_bar(0.0..=1.0, fraction)
+        .girth(6)
+        .style(move |_theme| progress_bar::Style {
+            background: p.inset_bg.into(),
+            bar: p.danger.into(),
             border: iced::Border {
-                color: KEYCAP_BORDER,
-                width: 1.0,
-                radius: Radius::from(5.0),
+                radius: Radius::from(3.0),
+                ..Default::default()
             },
-            ..Default::default()
         })
         .into()
 }
 
-fn card_style(_theme: &iced::Theme) -> container::Style {
-    container::Style {
-        background: Some(CARD_BG.into()),
+/// "4.3s", rounded up so it never reads 0.0s before the timeout fires
+fn format_remaining(remaining: Duration) -> String {
+    let tenths = remaining.as_millis().div_ceil(100);
+    format!("{}.{}s", tenths / 10, tenths % 10)
+}
+
+fn countdown_label(action: TimeoutAction) -> &'static str {
+    match action {
+        TimeoutAction::Deny => tr("Auto-deny in"),
+        TimeoutAction::Confirm => tr("Continuing in"),
+        TimeoutAction::ReturnTimeout => tr("Closes in"),
+    }
+}
+
+/// A touch-sized button for a deciding action, labelled with its key
+fn action_button<'a>(
+    p: &Palette,
+    hint: &KeyHint,
+    result: DialogResult,
+    nag: Nag,
+) -> Element<'a, Message> {
+    let p = *p;
+    let fill = action_color(&p, result, nag);
+    let label = row![
+        text(hint.key.clone())
+            .size(scaled(14.0))
+            .font(Font::MONOSPACE)
+            .color(p.card_bg),
+        text(hint.label.clone())
+            .size(scaled(17.0))
+            .font(Font {
+                weight: Weight::Bold,
+                ..Font::DEFAULT
+            })
+            .color(p.card_bg),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    button(label)
+        .padding([12, 22])
+        .on_press(Message::Decide(result))
+        .style(move |_theme, status| {
+            let alpha = match status {
+                button::Status::Hovered => 1.0,
+                button::Status::Pressed => 0.7,
+                _ => 0.85,
+            };
+            button::Style {
+                background: Some(Color { a: alpha, ..fill }.into()),
+                text_color: p.card_bg,
+                border: iced::Border {
+                    radius: Radius::from(8.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        })
+        .into()
+}
+
+/// Color of an action label, pulsing the deny hint while nagging
+fn action_color(p: &Palette, result: DialogResult, nag: Nag) -> Color {
+    match (result, nag) {
+        (DialogResult::Denied, Nag::Dim) => p.text_muted,
+        (DialogResult::Denied, Nag::Bright) => p.text_primary,
+        (result, _) if result.is_confirmed() => p.success,
+        (DialogResult::DeniedRemember, _) => p.danger,
+        (DialogResult::Denied, Nag::Off) => p.danger,
+        (DialogResult::Cancelled, _) => p.text_muted,
+        _ => p.text_body,
+    }
+}
+
+fn keycap<'a>(p: &Palette, label: &str) -> Element<'a, Message> {
+    let p = *p;
+    container(
+        text(label.to_string())
+            .size(scaled(15.0))
+            .font(Font::MONOSPACE)
+            .color(p.text_body),
+    )
+    .padding([4, 10])
+    .style(move |_theme| container::Style {
+        background: Some(p.keycap_bg.into()),
         border: iced::Border {
-            color: CARD_BORDER,
+            color: p.keycap_border,
             width: 1.0,
+            radius: Radius::from(5.0),
+        },
+        ..Default::default()
+    })
+    .into()
+}
+
+/// Check whether a pressed key matches a binding name
+///
+/// Named keys match their iced name case-insensitively ("Enter", "F1"),
+/// with "Esc" accepted for Escape; anything else matches a character key.
+fn key_matches(binding: &str, key: &Key) -> bool {
+    match key {
+        Key::Named(keyboard::key::Named::Escape) if binding.eq_ignore_ascii_case("esc") => true,
+        Key::Named(named) => format!("{:?}", named).eq_ignore_ascii_case(binding),
+        Key::Character(c) => c.as_str().eq_ignore_ascii_case(binding),
+        Key::Unidentified => false,
+    }
+}
+
+/// Whether a key only modifies others, so pressing it alone isn't input
+fn is_modifier(key: &Key) -> bool {
+    use keyboard::key::Named;
+
+    matches!(
+        key,
+        Key::Named(
+            Named::Shift
+                | Named::Control
+                | Named::Alt
+                | Named::AltGraph
+                | Named::Super
+                | Named::Meta
+                | Named::Hyper
+                | Named::Fn
+                | Named::CapsLock
+                | Named::NumLock
+        )
+    )
+}
+
+fn card_style(
+    p: &Palette,
+    opacity: f32,
+    urgency: Option<f32>,
+) -> impl Fn(&iced::Theme) -> container::Style {
+    let p = *p;
+    // An urgent card's border swells toward the danger color and back
+    let (border_color, border_width) = match urgency {
+        Some(phase) => (
+            theming::mix(p.card_border, p.danger, phase),
+            p.border_width.max(2.0) + phase,
+        ),
+        None => (p.card_border, p.border_width),
+    };
+    move |_theme| container::Style {
+        background: Some(
+            Color {
+                a: p.card_bg.a * opacity,
+                ..p.card_bg
+            }
+            .into(),
+        ),
+        border: iced::Border {
+            color: border_color,
+            width: border_width,
             radius: Radius::from(12.0),
         },
         shadow: iced::Shadow {
@@ -271,3 +2864,874 @@ fn card_style(_theme: &iced::Theme) -> container::Style {
         ..Default::default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::MutexGuard;
+
+    /// An `App` showing `config` that appeared a minute ago, so no key is
+    /// too early and the user has been idle since
+    fn app(config: DialogConfig) -> (MutexGuard<'static, ()>, App) {
+        let guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        install(config, Vec::new());
+        EXIT_CODE.store(-1, Ordering::SeqCst);
+        let (mut app, _) = App::new();
+        let shown = Instant::now() - Duration::from_secs(60);
+        app.start_time = shown;
+        app.visible_since = shown;
+        app.last_activity = shown;
+        (guard, app)
+    }
+
+    fn escalation() -> DialogConfig {
+        DialogConfig::new(DialogKind::PrivilegeEscalation {
+            command: "/usr/bin/pacman -Syu".to_string(),
+        })
+    }
+
+    fn network() -> DialogConfig {
+        DialogConfig::new(DialogKind::NetworkConnection {
+            process: "curl".to_string(),
+            process_path: PathBuf::from("/usr/bin/curl"),
+            destination: "example.org".to_string(),
+            port: 443,
+            protocol: "tcp".into(),
+            pid: None,
+            exe_hash: None,
+            cmdline: None,
+            parent_process: None,
+            ancestry: Vec::new(),
+        })
+    }
+
+    fn generic_kind() -> DialogKind {
+        DialogKind::Generic {
+            title: "Lock screen".to_string(),
+            message: "Locking the screen".to_string(),
+            detail: "Esc to stop".into(),
+        }
+    }
+
+    fn exit_code() -> i32 {
+        EXIT_CODE.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn clip_counts_characters_not_bytes() {
+        assert_eq!(clip("short".to_string(), 10), "short");
+        assert_eq!(clip("exactly10!".to_string(), 10), "exactly10!");
+        // One long unbroken token, as iced can only break it per glyph
+        let token = "a".repeat(500);
+        assert_eq!(clip(token, 8), "aaaaaaaa…");
+        // Multibyte characters are never split
+        assert_eq!(clip("äöüßéèêëç".to_string(), 4), "äöüß…");
+        assert_eq!(clip("日本語のタイトル".to_string(), 3), "日本語…");
+        assert_eq!(clip("🔒🔒🔒".to_string(), 2), "🔒🔒…");
+    }
+
+    #[test]
+    fn clip_keeps_zero_width_characters_whole() {
+        // Zero-width joiners and combining marks count like any other char
+        let family = "👩\u{200d}👩\u{200d}👧";
+        assert_eq!(clip(family.to_string(), 2), "👩\u{200d}…");
+        assert_eq!(clip("e\u{301}e\u{301}".to_string(), 3), "e\u{301}e…");
+        let hidden = "\u{200b}".repeat(10);
+        assert_eq!(clip(hidden, 5), format!("{}…", "\u{200b}".repeat(5)));
+    }
+
+    #[test]
+    fn markup_only_when_asked_for_on_generic_dialogs() {
+        let generic = DialogConfig::new(DialogKind::Generic {
+            title: "**Update**".to_string(),
+            message: "Install:".to_string(),
+            detail: "- `pkg` **now**".into(),
+        });
+        assert!(!renders_markup(&generic));
+        // Unrendered text is shown exactly as given
+        assert_eq!(generic.detail(), "- `pkg` **now**");
+        assert!(renders_markup(&DialogConfig {
+            rich_text: true,
+            ..generic
+        }));
+
+        let command = DialogConfig {
+            rich_text: true,
+            ..DialogConfig::new(DialogKind::PrivilegeEscalation {
+                command: "echo **x**".to_string(),
+            })
+        };
+        assert!(!renders_markup(&command));
+    }
+
+    #[test]
+    fn elide_detail_keeps_short_detail() {
+        let detail = "x".repeat(DETAIL_MAX_CHARS);
+        assert_eq!(elide_detail(detail.clone()), detail);
+    }
+
+    #[test]
+    fn elide_detail_counts_hidden_characters() {
+        let detail = format!("{}{}", "é".repeat(DETAIL_MAX_CHARS), "日".repeat(25));
+        let elided = elide_detail(detail);
+        let (shown, note) = elided.split_once('…').unwrap();
+        assert_eq!(shown.chars().count(), DETAIL_MAX_CHARS);
+        assert!(note.contains("25"), "{}", note);
+    }
+
+    #[test]
+    fn activity_restarts_an_inactivity_timeout() {
+        let mut config = escalation();
+        config.timeout_secs = Some(30);
+        config.timeout_resets_on_activity = true;
+        let (_guard, mut app) = app(config);
+
+        let moved = Message::Event(Event::Mouse(mouse::Event::CursorMoved {
+            position: Point::ORIGIN,
+        }));
+        let _ = app.update(moved);
+        let _ = app.update(Message::Tick);
+        assert_eq!(exit_code(), -1, "timed out right after activity");
+
+        app.last_activity = Instant::now() - Duration::from_secs(31);
+        let _ = app.update(Message::Tick);
+        assert_eq!(exit_code(), DialogResult::Timeout.exit_code());
+    }
+
+    #[test]
+    fn activity_leaves_an_absolute_timeout_alone() {
+        let mut config = escalation();
+        config.timeout_secs = Some(30);
+        let (_guard, mut app) = app(config);
+
+        let moved = Message::Event(Event::Mouse(mouse::Event::CursorMoved {
+            position: Point::ORIGIN,
+        }));
+        let _ = app.update(moved);
+        let _ = app.update(Message::Tick);
+        assert_eq!(exit_code(), DialogResult::Timeout.exit_code());
+    }
+
+    #[test]
+    fn layout_is_scaled_with_the_dialog() {
+        let mut config = escalation();
+        config.scale = Some(2.0);
+        config.layout = LayoutConfig {
+            padding: 10.0,
+            spacing: 4.0,
+            action_spacing: 0.0,
+        };
+        let (_guard, _app) = app(config.clone());
+
+        let model = ViewModel::new(&config, Duration::ZERO, Duration::ZERO, None, false);
+        assert_eq!(
+            model.layout,
+            LayoutConfig {
+                padding: 20.0,
+                spacing: 8.0,
+                action_spacing: 0.0,
+            }
+        );
+    }
+
+    #[test]
+    fn nag_starts_after_its_delay_and_pulses() {
+        let after = Some(Duration::from_secs(10));
+        assert_eq!(nag_state(None, Duration::from_secs(3600)), Nag::Off);
+        assert_eq!(nag_state(after, Duration::ZERO), Nag::Off);
+        assert_eq!(nag_state(after, Duration::from_millis(9_999)), Nag::Off);
+        assert_eq!(nag_state(after, Duration::from_millis(10_000)), Nag::Bright);
+        assert_eq!(nag_state(after, Duration::from_millis(10_499)), Nag::Bright);
+        assert_eq!(nag_state(after, Duration::from_millis(10_500)), Nag::Dim);
+        assert_eq!(nag_state(after, Duration::from_millis(11_000)), Nag::Bright);
+        assert_eq!(nag_state(Some(Duration::ZERO), Duration::ZERO), Nag::Bright);
+    }
+
+    #[test]
+    fn nag_never_decides() {
+        let mut config = escalation();
+        config.nag_after = Some(Duration::from_secs(1));
+        let (_guard, mut app) = app(config.clone());
+
+        let _ = app.update(Message::Tick);
+        assert_eq!(exit_code(), -1);
+        let model = ViewModel::new(
+            &config,
+            Duration::from_secs(60),
+            Duration::ZERO,
+            None,
+            false,
+        );
+        assert_ne!(model.nag, Nag::Off);
+    }
+
+    #[test]
+    fn escape_during_the_confirm_countdown_aborts_it() {
+        use keyboard::key::Named;
+
+        let mut config = escalation();
+        config.confirm_delay_secs = Some(3);
+        let (_guard, mut app) = app(config);
+
+        let _ = app.handle_key(&Key::Named(Named::Enter));
+        assert!(app.confirming.is_some());
+        let _ = app.update(Message::Tick);
+        assert_eq!(exit_code(), -1, "confirmed before the countdown ran out");
+
+        // Escape aborts instead of denying, and the dialog stays open
+        let _ = app.handle_key(&Key::Named(Named::Escape));
+        assert!(app.confirming.is_none());
+        let _ = app.update(Message::Tick);
+        assert_eq!(exit_code(), -1);
+
+        // Confirming again runs a fresh countdown to the end
+        let _ = app.handle_key(&Key::Named(Named::Enter));
+        let (_, result) = app.confirming.expect("countdown restarted");
+        app.confirming = Some((Instant::now() - Duration::from_secs(3), result));
+        let _ = app.update(Message::Tick);
+        assert_eq!(exit_code(), DialogResult::Confirmed.exit_code());
+    }
+
+    #[test]
+    fn other_keys_are_ignored_during_the_countdown() {
+        let mut config = escalation();
+        config.confirm_delay_secs = Some(3);
+        config.escape_cancels = true;
+        let (_guard, mut app) = app(config);
+
+        let _ = app.handle_key(&Key::Named(keyboard::key::Named::Enter));
+        let _ = app.handle_key(&Key::Character("d".into()));
+        let _ = app.handle_button(DialogResult::Denied);
+        assert!(app.confirming.is_some());
+        assert_eq!(exit_code(), -1);
+    }
+
+    #[test]
+    fn tiny_outputs_get_the_compact_layout() {
+        let (_guard, _app) = app(escalation());
+
+        for (width, height) in [
+            (0.0, 0.0),
+            (1.0, 1.0),
+            (320.0, 240.0),
+            (MIN_REGULAR_WIDTH - 1.0, 1080.0),
+            (1920.0, MIN_REGULAR_HEIGHT - 1.0),
+        ] {
+            let size = Size::new(width, height);
+            assert_eq!(layout_mode(size), LayoutMode::Compact, "{:?}", size);
+        }
+        for (width, height) in [
+            (MIN_REGULAR_WIDTH, MIN_REGULAR_HEIGHT),
+            (1280.0, 720.0),
+            (3840.0, 2160.0),
+        ] {
+            let size = Size::new(width, height);
+            assert_eq!(layout_mode(size), LayoutMode::Regular, "{:?}", size);
+        }
+    }
+
+    #[test]
+    fn compact_threshold_grows_with_scale() {
+        let mut config = escalation();
+        config.scale = Some(2.0);
+        let (_guard, _app) = app(config);
+
+        let size = Size::new(MIN_REGULAR_WIDTH, MIN_REGULAR_HEIGHT);
+        assert_eq!(layout_mode(size), LayoutMode::Compact);
+        let size = Size::new(MIN_REGULAR_WIDTH * 2.0, MIN_REGULAR_HEIGHT * 2.0);
+        assert_eq!(layout_mode(size), LayoutMode::Regular);
+    }
+
+    #[test]
+    fn t_stops_the_timeout() {
+        let mut config = escalation();
+        config.timeout_secs = Some(30);
+        config.allow_timeout_cancel = true;
+        let (_guard, mut app) = app(config.clone());
+
+        let _ = app.handle_key(&Key::Character("t".into()));
+        assert!(app.timeout_disabled);
+        let _ = app.update(Message::Tick);
+        assert_eq!(exit_code(), -1, "auto-denied after T");
+
+        let model = ViewModel::new(
+            &config,
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+            None,
+            true,
+        );
+        assert_eq!(model.remaining, None);
+        assert_eq!(model.remaining_fraction, None);
+    }
+
+    #[test]
+    fn t_does_nothing_unless_allowed() {
+        let mut config = escalation();
+        config.timeout_secs = Some(30);
+        let (_guard, mut app) = app(config);
+
+        let _ = app.handle_key(&Key::Character("t".into()));
+        assert!(!app.timeout_disabled);
+        let _ = app.update(Message::Tick);
+        assert_eq!(exit_code(), DialogResult::Timeout.exit_code());
+    }
+
+    #[test]
+    fn each_field_copies_its_own_value() {
+        let config = network();
+        assert_eq!(
+            copy_fields(&config),
+            [
+                ("process", "curl".to_string()),
+                ("path", "/usr/bin/curl".to_string()),
+                ("destination", "example.org".to_string()),
+                ("port", "443".to_string()),
+            ]
+        );
+        assert_eq!(copy_value(&config, Some(2)).as_deref(), Some("example.org"));
+        assert_eq!(copy_value(&config, Some(3)).as_deref(), Some("443"));
+        assert_eq!(copy_value(&config, Some(4)), None);
+        assert_eq!(copy_value(&config, None), Some(config.detail()));
+
+        let config = escalation();
+        assert_eq!(
+            copy_value(&config, Some(0)).as_deref(),
+            Some("/usr/bin/pacman -Syu")
+        );
+    }
+
+    #[test]
+    fn device_serial_is_copyable_only_when_known() {
+        let device = |serial: Option<&str>| {
+            DialogConfig::new(DialogKind::DeviceAccess {
+                device_name: "sdb1".to_string(),
+                vendor: "SanDisk".to_string(),
+                product: "Cruzer".to_string(),
+                serial: serial.map(str::to_string),
+                action: "mount".to_string(),
+            })
+        };
+        let labels = |config: &DialogConfig| -> Vec<_> {
+            copy_fields(config)
+                .into_iter()
+                .map(|(label, _)| label)
+                .collect()
+        };
+        assert_eq!(labels(&device(None)), ["device", "vendor", "product"]);
+        assert_eq!(
+            labels(&device(Some("4C53"))),
+            ["device", "vendor", "product", "serial"]
+        );
+        assert_eq!(
+            copy_value(&device(Some("4C53")), Some(3)).as_deref(),
+            Some("4C53")
+        );
+    }
+
+    #[test]
+    fn tab_and_ctrl_digits_pick_the_copied_field() {
+        let (_guard, mut app) = app(network());
+
+        for expected in [Some(0), Some(1), Some(2), Some(3), None, Some(0)] {
+            let _ = app.handle_key(&Key::Named(keyboard::key::Named::Tab));
+            assert_eq!(app.focused_field, expected);
+        }
+
+        // Ctrl+C copies the focused field, Ctrl+N field N
+        let _ = app.handle_copy(&Key::Character("c".into()));
+        assert_eq!(app.copied, Some(Some(0)));
+        let _ = app.handle_copy(&Key::Character("3".into()));
+        assert_eq!((app.focused_field, app.copied), (Some(2), Some(Some(2))));
+        // Fields that don't exist copy nothing
+        let _ = app.handle_copy(&Key::Character("9".into()));
+        assert_eq!((app.focused_field, app.copied), (Some(2), Some(Some(2))));
+    }
+
+    fn hint_keys(config: &DialogConfig, timeout_disabled: bool) -> Vec<String> {
+        key_hints(config, timeout_disabled)
+            .into_iter()
+            .map(|hint| hint.key)
+            .collect()
+    }
+
+    #[test]
+    fn hints_follow_enabled_features() {
+        assert_eq!(hint_keys(&escalation(), false), ["Enter", "Esc", "Ctrl+C"]);
+
+        // Cancelling the timeout needs a timeout to cancel
+        let mut config = escalation();
+        config.allow_timeout_cancel = true;
+        assert_eq!(hint_keys(&config, false), ["Enter", "Esc", "Ctrl+C"]);
+        config.timeout_secs = Some(30);
+        assert_eq!(hint_keys(&config, false), ["Enter", "Esc", "T", "Ctrl+C"]);
+        // Once used, the T hint goes away with the countdown
+        assert_eq!(hint_keys(&config, true), ["Enter", "Esc", "Ctrl+C"]);
+
+        let mut config = escalation();
+        config.offer_remember = true;
+        assert_eq!(
+            hint_keys(&config, false),
+            ["Enter", "a", "Esc", "n", "Ctrl+C"]
+        );
+
+        // Several copyable fields and a details panel
+        assert_eq!(hint_keys(&network(), false), ["Enter", "Esc", "I", "Tab"]);
+    }
+
+    #[test]
+    fn hints_follow_rebound_keys() {
+        let mut config = escalation();
+        config.confirm_key = Some("y".to_string());
+        config.deny_key = Some("F1".to_string());
+        assert_eq!(hint_keys(&config, false), ["y", "F1", "Ctrl+C"]);
+
+        // A custom binding shadows T, so T isn't advertised for the timeout
+        let mut config = escalation();
+        config.timeout_secs = Some(30);
+        config.allow_timeout_cancel = true;
+        config.deny_key = Some("t".to_string());
+        assert_eq!(hint_keys(&config, false), ["Enter", "t", "Ctrl+C"]);
+        let (_guard, mut app) = app(config);
+        let _ = app.handle_key(&Key::Character("t".into()));
+        assert!(!app.timeout_disabled);
+        assert_eq!(exit_code(), DialogResult::Denied.exit_code());
+    }
+
+    #[test]
+    fn batch_hints_replace_the_usual_ones() {
+        let mut config = escalation();
+        config.batch = vec![escalation().kind, network().kind];
+        config.allow_timeout_cancel = true;
+        config.timeout_secs = Some(30);
+        assert_eq!(hint_keys(&config, false), ["↑↓", "A", "D", "Enter", "Esc"]);
+    }
+
+    #[test]
+    fn next_pending_wraps_past_decided_items() {
+        use DialogResult::{Confirmed, Denied};
+
+        let decisions = [None, Some(Confirmed), None, Some(Denied)];
+        assert_eq!(next_pending(&decisions, 0), Some(2));
+        assert_eq!(next_pending(&decisions, 2), Some(0));
+        assert_eq!(next_pending(&decisions, 3), Some(0));
+        // The item just decided comes last, if it's still open
+        assert_eq!(next_pending(&[None, Some(Denied)], 0), Some(0));
+        assert_eq!(next_pending(&[Some(Confirmed), Some(Denied)], 0), None);
+    }
+
+    #[test]
+    fn batch_choices_map_back_to_input_order() {
+        use keyboard::key::Named;
+        use DialogResult::{Confirmed, Denied};
+
+        let mut config = escalation();
+        config.batch = vec![escalation().kind, network().kind, generic_kind()];
+        let (_guard, mut app) = app(config);
+
+        // Deny the second item, then allow the rest
+        let _ = app.handle_key(&Key::Named(Named::ArrowDown));
+        let _ = app.handle_key(&Key::Character("d".into()));
+        assert_eq!(app.batch, [None, Some(Denied), None]);
+        assert_eq!(app.batch_selected, 2);
+        let _ = app.handle_key(&Key::Named(Named::Enter));
+        assert_eq!(
+            *BATCH_RESULTS.lock().unwrap(),
+            [Confirmed, Denied, Confirmed]
+        );
+        assert_eq!(exit_code(), Denied.exit_code());
+    }
+
+    #[test]
+    fn batch_is_confirmed_only_if_every_item_is() {
+        use DialogResult::Confirmed;
+
+        let mut config = escalation();
+        config.batch = vec![escalation().kind, network().kind];
+        let (_guard, mut app) = app(config);
+
+        let _ = app.handle_key(&Key::Character("a".into()));
+        assert_eq!(app.batch_selected, 1);
+        let _ = app.handle_key(&Key::Character("a".into()));
+        assert_eq!(*BATCH_RESULTS.lock().unwrap(), [Confirmed, Confirmed]);
+        assert_eq!(exit_code(), Confirmed.exit_code());
+    }
+
+    #[test]
+    fn batch_timeout_denies_what_is_left() {
+        use DialogResult::{Confirmed, Timeout};
+
+        let mut config = escalation();
+        config.timeout_secs = Some(30);
+        config.timeout_action = TimeoutAction::Confirm;
+        config.batch = vec![escalation().kind, network().kind];
+        let (_guard, mut app) = app(config);
+
+        let _ = app.handle_key(&Key::Character("a".into()));
+        let _ = app.update(Message::Tick);
+        assert_eq!(*BATCH_RESULTS.lock().unwrap(), [Confirmed, Timeout]);
+        assert_eq!(exit_code(), DialogResult::Denied.exit_code());
+    }
+
+    #[test]
+    fn timeout_never_confirms_escalation_or_secrets() {
+        let mut config = escalation();
+        config.timeout_secs = Some(30);
+        config.timeout_action = TimeoutAction::Confirm;
+        let (guard, mut app) = app(config);
+        let _ = app.update(Message::Tick);
+        assert_eq!(exit_code(), DialogResult::Timeout.exit_code());
+        drop(guard);
+
+        let mut config = DialogConfig::new(generic_kind());
+        config.timeout_secs = Some(30);
+        config.timeout_action = TimeoutAction::Confirm;
+        config.secret_prompt = Some("PIN".to_string());
+        let (_guard, mut app) = app(config);
+        let _ = app.update(Message::Tick);
+        assert_eq!(exit_code(), DialogResult::Timeout.exit_code());
+    }
+
+    #[test]
+    fn escape_stops_a_confirm_started_by_the_timeout() {
+        let mut config = DialogConfig::new(generic_kind());
+        config.timeout_secs = Some(30);
+        config.timeout_action = TimeoutAction::Confirm;
+        config.confirm_delay_secs = Some(3);
+        let (_guard, mut app) = app(config);
+
+        let _ = app.update(Message::Tick);
+        assert!(app.confirming.is_some());
+        let _ = app.handle_key(&Key::Named(keyboard::key::Named::Escape));
+        assert!(app.confirming.is_none());
+
+        // The expired timeout doesn't start the countdown again
+        let _ = app.update(Message::Tick);
+        assert!(app.confirming.is_none());
+        assert_eq!(exit_code(), -1);
+    }
+
+    #[test]
+    fn report_command_cannot_see_the_private_pipes() {
+        use std::os::fd::AsRawFd;
+
+        // Inheritable, like the secret and report pipes once moved into place
+        let pipe = std::fs::File::open("/dev/null").unwrap();
+        let fd = pipe.as_raw_fd();
+        // SAFETY: `fd` is open for the lifetime of `pipe`
+        assert_eq!(unsafe { libc::fcntl(fd, libc::F_SETFD, 0) }, 0);
+
+        let mut config = escalation();
+        let script = format!(
+            "test -e /proc/self/fd/{} && test \"$(readlink /proc/self/fd/0)\" = /dev/null",
+            fd
+        );
+        config.report_command = Some(vec!["sh".into(), "-c".into(), script]);
+        let sees_pipe = |private: &[RawFd]| {
+            let mut command = report_command(&config, "", private).unwrap();
+            command.status().unwrap().success()
+        };
+        assert!(sees_pipe(&[]), "the check itself is broken");
+        assert!(!sees_pipe(&[fd]));
+    }
+
+    #[test]
+    fn later_outputs_do_not_restart_the_min_visible_delay() {
+        let (_guard, mut app) = app(escalation());
+        let _ = app.update(Message::SurfaceOpened(Id::unique()));
+        let shown = app.visible_since;
+        let _ = app.update(Message::SurfaceOpened(Id::unique()));
+        assert_eq!(app.visible_since, shown);
+    }
+
+    #[test]
+    fn long_labels_and_batch_titles_are_clipped() {
+        let long = "x".repeat(10_000);
+        let mut config = network();
+        config.actions = vec![crate::DialogAction::new(
+            long.clone(),
+            long.clone(),
+            DialogResult::Denied,
+        )];
+        let hint = &key_hints(&config, false)[0];
+        assert_eq!(hint.label.chars().count(), LABEL_MAX_CHARS + 1);
+        assert_eq!(hint.key.chars().count(), LABEL_MAX_CHARS + 1);
+
+        let item = DialogKind::Generic {
+            title: long.clone(),
+            message: String::new(),
+            detail: long.into(),
+        };
+        let mut config = network();
+        config.batch = vec![item.clone(), item];
+        let rows = batch_rows(&config, &[None, None], 0);
+        assert_eq!(rows[0].title.chars().count(), TITLE_MAX_CHARS + 1);
+        assert!(rows[0].detail.chars().count() < DETAIL_MAX_CHARS + 100);
+    }
+
+    #[test]
+    fn each_dialog_starts_without_frames() {
+        let (_guard, mut app) = app(escalation());
+        let first = start_run();
+        let _ = app.update(Message::Tick);
+        assert!(!stalled(first));
+
+        // A frame of the first dialog doesn't count for the next one
+        let second = start_run();
+        assert!(stalled(second));
+        // and the first dialog's watchdog has nothing left to judge
+        assert!(!stalled(first));
+        let _ = app.update(Message::Tick);
+        assert!(!stalled(second));
+    }
+
+    #[test]
+    fn long_commands_are_shown_whole() {
+        let packages: Vec<String> = (0..60).map(|i| format!("pkg-{:03}", i)).collect();
+        let mut command = format!("/usr/bin/pacman -S --needed {}", packages.join(" "));
+        command.truncate(500);
+        assert_eq!(command.len(), 500);
+        let config = DialogConfig::new(DialogKind::PrivilegeEscalation {
+            command: command.clone(),
+        });
+
+        let model = ViewModel::new(&config, Duration::ZERO, Duration::ZERO, None, false);
+        assert_eq!(model.detail, command);
+        assert!(model.highlight_command);
+        assert_eq!(copy_value(&config, None), Some(command.clone()));
+
+        // Every character reaches a highlighted span, in order
+        let shown: String = cmdline::segments(&command)
+            .into_iter()
+            .map(|segment| &command[segment.range])
+            .collect();
+        assert_eq!(shown, command);
+    }
+
+    #[test]
+    fn escalation_commands_are_never_elided() {
+        // Padding must not push what actually runs out of sight
+        let command = format!("/usr/bin/true {} && rm -rf /", " ".repeat(DETAIL_MAX_CHARS));
+        let config = DialogConfig::new(DialogKind::PrivilegeEscalation {
+            command: command.clone(),
+        });
+        let model = ViewModel::new(&config, Duration::ZERO, Duration::ZERO, None, false);
+        assert_eq!(model.detail, command);
+
+        let config = DialogConfig::new(DialogKind::Generic {
+            title: "Notice".to_string(),
+            message: "Read this".to_string(),
+            detail: command.clone().into(),
+        });
+        let model = ViewModel::new(&config, Duration::ZERO, Duration::ZERO, None, false);
+        assert_ne!(model.detail, command);
+    }
+
+    #[test]
+    fn long_paths_stay_one_wrappable_word() {
+        let path = format!("/{}", "very-long-directory-name/".repeat(20));
+        let command = format!("rm -rf {}", path);
+        let segments = cmdline::segments(&command);
+        let last = segments.last().unwrap();
+        assert_eq!(last.part, Part::Path);
+        assert_eq!(&command[last.range.clone()], path);
+    }
+
+    #[test]
+    fn bindings_match_named_keys() {
+        use keyboard::key::Named;
+
+        for (binding, named) in [
+            ("Enter", Named::Enter),
+            ("enter", Named::Enter),
+            ("Esc", Named::Escape),
+            ("ESC", Named::Escape),
+            ("Escape", Named::Escape),
+            ("F1", Named::F1),
+            ("f12", Named::F12),
+            ("Tab", Named::Tab),
+            ("Space", Named::Space),
+        ] {
+            assert!(key_matches(binding, &Key::Named(named)), "{}", binding);
+        }
+        assert!(!key_matches("Enter", &Key::Named(Named::Escape)));
+        assert!(!key_matches("Esc", &Key::Named(Named::Enter)));
+        assert!(!key_matches("F1", &Key::Named(Named::F11)));
+        assert!(!key_matches("Enter", &Key::Character("e".into())));
+    }
+
+    #[test]
+    fn bindings_match_character_keys() {
+        assert!(key_matches("y", &Key::Character("y".into())));
+        assert!(key_matches("y", &Key::Character("Y".into())));
+        assert!(key_matches("N", &Key::Character("n".into())));
+        assert!(key_matches("ß", &Key::Character("ß".into())));
+        assert!(!key_matches("y", &Key::Character("n".into())));
+        assert!(!key_matches("y", &Key::Unidentified));
+        assert!(!key_matches("", &Key::Unidentified));
+    }
+
+    #[test]
+    fn rebound_keys_replace_enter_and_escape() {
+        use keyboard::key::Named;
+
+        let mut config = escalation();
+        config.confirm_key = Some("F1".to_string());
+        config.deny_key = Some("n".to_string());
+        let (_guard, mut app) = app(config);
+
+        let _ = app.handle_key(&Key::Named(Named::Enter));
+        let _ = app.handle_key(&Key::Named(Named::Escape));
+        assert_eq!(exit_code(), -1, "default keys still decide");
+        let _ = app.handle_key(&Key::Named(Named::F1));
+        assert_eq!(exit_code(), DialogResult::Confirmed.exit_code());
+    }
+
+    #[test]
+    fn sigterm_denies_and_unlocks() {
+        let (_guard, mut app) = app(escalation());
+
+        let _ = app.update(Message::CheckSignal);
+        assert_eq!(exit_code(), -1);
+
+        TERMINATE.store(true, Ordering::SeqCst);
+        let _ = app.update(Message::CheckSignal);
+        TERMINATE.store(false, Ordering::SeqCst);
+        assert_eq!(exit_code(), DialogResult::Denied.exit_code());
+        assert_eq!(*SOURCE.lock().unwrap(), DecisionSource::Forced);
+    }
+
+    #[test]
+    fn each_decision_path_sets_its_source() {
+        use keyboard::key::Named;
+
+        let mut config = escalation();
+        config.timeout_secs = Some(30);
+
+        // A key the user pressed
+        let (guard, mut app) = app(config.clone());
+        let _ = app.handle_key(&Key::Named(Named::Enter));
+        assert_eq!(exit_code(), DialogResult::Confirmed.exit_code());
+        assert_eq!(*SOURCE.lock().unwrap(), DecisionSource::User);
+        drop(guard);
+
+        // A button the user clicked
+        let (guard, mut app) = app(config.clone());
+        let _ = app.update(Message::Decide(DialogResult::Denied));
+        assert_eq!(exit_code(), DialogResult::Denied.exit_code());
+        assert_eq!(*SOURCE.lock().unwrap(), DecisionSource::User);
+        drop(guard);
+
+        // The timeout
+        let (_guard, mut app) = app(config);
+        let _ = app.update(Message::Tick);
+        assert_eq!(exit_code(), DialogResult::Timeout.exit_code());
+        assert_eq!(*SOURCE.lock().unwrap(), DecisionSource::Timeout);
+    }
+
+    #[test]
+    fn unshown_dialogs_are_forced() {
+        let mut config = escalation();
+        config.request_id = Some("req-5".to_string());
+        for result in [DialogResult::Denied, DialogResult::Cancelled] {
+            let outcome = report_unshown(&config, result);
+            assert_eq!(outcome.code, result.exit_code());
+            assert_eq!(outcome.report.result, result);
+            assert_eq!(outcome.report.source, DecisionSource::Forced);
+            assert_eq!(outcome.report.request_id.as_deref(), Some("req-5"));
+        }
+    }
+
+    #[test]
+    fn cancel_request_dismisses_the_dialog() {
+        let mut config = escalation();
+        config.timeout_secs = None;
+        let (_guard, mut app) = app(config);
+
+        enable_cancel();
+        let _ = app.update(Message::CheckSignal);
+        assert_eq!(exit_code(), -1);
+
+        request_cancel();
+        let _ = app.update(Message::CheckSignal);
+        enable_cancel();
+        assert_eq!(exit_code(), DialogResult::Cancelled.exit_code());
+        assert_eq!(*SOURCE.lock().unwrap(), DecisionSource::Forced);
+    }
+
+    /// Leaves stream mode when dropped, even if the test fails
+    struct Streaming;
+
+    impl Drop for Streaming {
+        fn drop(&mut self) {
+            *STREAM.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+    }
+
+    /// Stream mode with `queue` waiting, and the pipe its reports go to
+    fn stream(queue: Vec<Result<DialogConfig, DialogReport>>) -> (Streaming, std::fs::File) {
+        let (read_end, write_end) = crate::auth::cloexec_pipe().unwrap();
+        *STREAM.lock().unwrap() = Some(Stream {
+            queue: queue.into(),
+            closed: false,
+            out: Box::new(std::fs::File::from(write_end)),
+        });
+        (Streaming, std::fs::File::from(read_end))
+    }
+
+    fn shown_request_id() -> Option<String> {
+        CONFIG.lock().unwrap().as_ref().unwrap().request_id.clone()
+    }
+
+    #[test]
+    fn stream_swaps_in_each_config_after_reporting_the_last() {
+        use keyboard::key::Named;
+
+        let request = |id: &str| DialogConfig {
+            request_id: Some(id.to_string()),
+            ..escalation()
+        };
+        let (_guard, mut app) = app(request("first"));
+        let (_streaming, mut reports) = stream(vec![
+            Err(refused(Some("bad".to_string()), "invalid config")),
+            Ok(request("second")),
+        ]);
+        let mut next_report = || -> DialogReport {
+            let bytes = read_frame(&mut reports, MAX_CONFIG_LEN).unwrap().unwrap();
+            rmp_serde::from_slice(&bytes).unwrap()
+        };
+
+        // Each answer is reported in order, refused configs included
+        let _ = app.handle_key(&Key::Named(Named::Enter));
+        let report = next_report();
+        assert_eq!(report.request_id.as_deref(), Some("first"));
+        assert_eq!(report.result, DialogResult::Confirmed);
+        let report = next_report();
+        assert_eq!(report.request_id.as_deref(), Some("bad"));
+        assert_eq!(report.result, DialogResult::Error);
+
+        // The next prompt replaces it under the same lock, shown anew
+        assert_eq!(shown_request_id().as_deref(), Some("second"));
+        assert!(!app.waiting);
+        let _ = app.handle_key(&Key::Named(Named::Enter));
+        assert_eq!(shown_request_id().as_deref(), Some("second"));
+        let _ = app.handle_key(&Key::Named(Named::Escape));
+        let report = next_report();
+        assert_eq!(report.request_id.as_deref(), Some("second"));
+        assert_eq!(report.result, DialogResult::Denied);
+
+        // With nothing queued the lock stays up, and nothing is answered
+        assert!(app.waiting);
+        let _ = app.update(Message::Decide(DialogResult::Confirmed));
+        let _ = app.update(Message::CheckQueue);
+        assert!(app.waiting);
+
+        // Until a config arrives
+        if let Some(stream) = STREAM.lock().unwrap().as_mut() {
+            stream.queue.push_back(Ok(request("third")));
+        }
+        let _ = app.update(Message::CheckQueue);
+        assert!(!app.waiting);
+        assert_eq!(shown_request_id().as_deref(), Some("third"));
+    }
+}
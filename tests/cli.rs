@@ -0,0 +1,319 @@
+//! The dialog binary's handling of its command line and config input
+//!
+//! These never get as far as taking the session lock, so they need no
+//! compositor.
+
+use session_dialog::{
+    DecisionSource, DialogAction, DialogConfig, DialogKind, DialogReport, DialogResult,
+    AUTO_RESULT_ENV, FORCE_DENY_ENV, LEGACY_TIMEOUT_ENV, MALFORMED_CONFIG_ENV, TEST_MODE_ENV,
+};
+use std::io::{Read, Write};
+use std::os::unix::net::UnixListener;
+use std::process::{Command, Output, Stdio};
+
+fn config() -> DialogConfig {
+    DialogConfig::new(DialogKind::PrivilegeEscalation {
+        command: "/usr/bin/true".to_string(),
+    })
+}
+
+/// Run the binary with `args`, feeding `stdin` and closing it
+fn run(args: &[&str], stdin: &[u8], env: &[(&str, &str)]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_session-dialog"))
+        .args(args)
+        .env_remove(MALFORMED_CONFIG_ENV)
+        .env_remove(FORCE_DENY_ENV)
+        .env_remove(AUTO_RESULT_ENV)
+        .env_remove(TEST_MODE_ENV)
+        .env_remove(LEGACY_TIMEOUT_ENV)
+        .envs(env.iter().copied())
+        .env_remove("WAYLAND_DISPLAY")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run session-dialog");
+    // A binary that rejects the input early may not read all of it
+    let _ = child.stdin.take().unwrap().write_all(stdin);
+    child.wait_with_output().expect("wait for session-dialog")
+}
+
+#[test]
+fn malformed_msgpack_denies() {
+    let bytes = config().to_bytes();
+    for input in [&bytes[..bytes.len() / 2], &b"\xc1\xc1\xc1"[..], &[]] {
+        let output = run(&["--config-stdin"], input, &[]);
+        assert_eq!(output.status.code(), Some(1), "{:?}", input);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("failed to parse config"), "{}", stderr);
+    }
+}
+
+#[test]
+fn malformed_json_denies() {
+    let json = config().to_json();
+    for input in [&json[..json.len() / 2], "{\"kind\":", "not json"] {
+        let output = run(&["--config-stdin", "--json"], input.as_bytes(), &[]);
+        assert_eq!(output.status.code(), Some(1), "{:?}", input);
+    }
+    let output = run(&["--config-json", "{\"kind\":"], &[], &[]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn malformed_config_can_exit_with_error_instead() {
+    let output = run(
+        &["--config-stdin"],
+        b"\xc1",
+        &[(MALFORMED_CONFIG_ENV, "error")],
+    );
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("failed to parse config"), "{}", stderr);
+}
+
+#[test]
+fn one_mib_config_is_refused() {
+    let mut config = config();
+    config.request_id = Some("x".repeat(1024 * 1024));
+    let inputs = [
+        (&["--config-stdin"][..], config.to_bytes()),
+        (
+            &["--config-stdin", "--json"][..],
+            config.to_json().into_bytes(),
+        ),
+    ];
+    for (args, input) in inputs {
+        // Too large is an error even where malformed input denies
+        let output = run(args, &input, &[]);
+        assert_eq!(output.status.code(), Some(3), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("config too large"), "{}", stderr);
+    }
+}
+
+#[test]
+fn validate_accepts_a_well_formed_config() {
+    let output = run(&["--validate", "--config-stdin"], &config().to_bytes(), &[]);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn invalid_configs_are_refused_with_or_without_validate() {
+    let mut config = config();
+    config.timeout_secs = Some(0);
+    for args in [&["--validate", "--config-stdin"][..], &["--config-stdin"]] {
+        let output = run(args, &config.to_bytes(), &[]);
+        assert_eq!(output.status.code(), Some(3), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("invalid config"), "{:?}: {}", args, stderr);
+    }
+}
+
+#[test]
+fn out_of_range_actions_are_refused() {
+    // Exit code 16 + 240 would wrap around to 0, which reads as Confirmed
+    let mut config = config();
+    config.actions = vec![DialogAction::new("Wrap", "w", DialogResult::Action(240))];
+    let env = [(TEST_MODE_ENV, "1"), (AUTO_RESULT_ENV, "confirmed")];
+    let output = run(&["--config-stdin"], &config.to_bytes(), &env);
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("custom action 240"), "{}", stderr);
+}
+
+#[test]
+fn force_deny_overrides_everything() {
+    let env = [
+        (TEST_MODE_ENV, "1"),
+        (AUTO_RESULT_ENV, "confirmed"),
+        (FORCE_DENY_ENV, "1"),
+    ];
+    let output = run(&["--config-stdin"], &config().to_bytes(), &env);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(FORCE_DENY_ENV), "{}", stderr);
+    assert!(stderr.contains("/usr/bin/true"), "{}", stderr);
+}
+
+#[test]
+fn force_deny_needs_exactly_1() {
+    for value in ["0", "", "yes", "true"] {
+        let env = [
+            (TEST_MODE_ENV, "1"),
+            (AUTO_RESULT_ENV, "confirmed"),
+            (FORCE_DENY_ENV, value),
+        ];
+        let output = run(&["--config-stdin"], &config().to_bytes(), &env);
+        assert_eq!(
+            output.status.code(),
+            Some(0),
+            "{}={:?}",
+            FORCE_DENY_ENV,
+            value
+        );
+    }
+}
+
+#[test]
+fn auto_result_answers_in_test_mode() {
+    for (value, code) in [
+        ("confirmed", 0),
+        ("CONFIRMED", 0),
+        ("denied", 1),
+        ("Timeout", 2),
+    ] {
+        let env = [(TEST_MODE_ENV, "1"), (AUTO_RESULT_ENV, value)];
+        let output = run(&["--config-stdin"], &config().to_bytes(), &env);
+        assert_eq!(output.status.code(), Some(code), "{:?}", value);
+    }
+}
+
+#[test]
+fn auto_result_never_applies_outside_test_mode() {
+    // With no compositor to connect to, the real dialog fails instead of
+    // locking the screen of whoever runs the tests
+    let no_compositor = ("XDG_RUNTIME_DIR", "/nonexistent/session-dialog-test");
+    for (env, message) in [
+        (&[(AUTO_RESULT_ENV, "confirmed")][..], "ignoring"),
+        (
+            &[(TEST_MODE_ENV, "0"), (AUTO_RESULT_ENV, "confirmed")],
+            "ignoring",
+        ),
+        (&[(TEST_MODE_ENV, "1"), (AUTO_RESULT_ENV, "yes")], "unknown"),
+    ] {
+        let env = [env, &[no_compositor]].concat();
+        let output = run(&["--config-stdin"], &config().to_bytes(), &env);
+        assert_ne!(output.status.code(), Some(0), "{:?}", env);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(message), "{}", stderr);
+    }
+}
+
+#[test]
+fn report_round_trips_through_the_result_socket() {
+    let dir = std::env::temp_dir().join(format!("session-dialog-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("result.sock");
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let mut config = config();
+    config.result_socket = Some(path.clone());
+    config.request_id = Some("req-7".to_string());
+    let env = [(TEST_MODE_ENV, "1"), (AUTO_RESULT_ENV, "denied")];
+    let output = run(&["--config-stdin"], &config.to_bytes(), &env);
+    // The exit code is still reported alongside the socket
+    assert_eq!(output.status.code(), Some(1));
+
+    // The dialog has exited, so its report is queued on the socket
+    let (mut stream, _) = listener.accept().unwrap();
+    let mut bytes = Vec::new();
+    stream.read_to_end(&mut bytes).unwrap();
+    let report: DialogReport = rmp_serde::from_slice(&bytes).unwrap();
+    assert_eq!(report.result, DialogResult::Denied);
+    assert_eq!(report.request_id.as_deref(), Some("req-7"));
+    assert_eq!(report.source, DecisionSource::Forced);
+    assert!(!report.interacted);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// `payloads` framed for `--stream`: a big-endian length, then the bytes
+fn frames(payloads: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for payload in payloads {
+        bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(payload);
+    }
+    bytes
+}
+
+#[test]
+fn stream_answers_every_config_in_order() {
+    let request = |id: &str| {
+        let mut config = config();
+        config.request_id = Some(id.to_string());
+        config
+    };
+    let mut invalid = request("invalid");
+    invalid.timeout_secs = Some(0);
+    let mut secret = request("secret");
+    secret.secret_fd = Some(3);
+    let input = frames(&[
+        request("first").to_bytes(),
+        b"\xc1\xc1".to_vec(),
+        invalid.to_bytes(),
+        secret.to_bytes(),
+        request("last").to_bytes(),
+    ]);
+
+    let env = [(TEST_MODE_ENV, "1"), (AUTO_RESULT_ENV, "denied")];
+    let output = run(&["--stream"], &input, &env);
+    assert_eq!(output.status.code(), Some(0));
+
+    let mut reports = Vec::new();
+    let mut stdout = output.stdout.as_slice();
+    while let Some((len, rest)) = stdout.split_first_chunk::<4>() {
+        let (frame, rest) = rest.split_at(u32::from_be_bytes(*len) as usize);
+        reports.push(rmp_serde::from_slice::<DialogReport>(frame).unwrap());
+        stdout = rest;
+    }
+    let answers: Vec<_> = reports
+        .iter()
+        .map(|r| (r.request_id.as_deref(), r.result))
+        .collect();
+    assert_eq!(
+        answers,
+        [
+            (Some("first"), DialogResult::Denied),
+            (None, DialogResult::Error),
+            (Some("invalid"), DialogResult::Error),
+            (Some("secret"), DialogResult::Error),
+            (Some("last"), DialogResult::Denied),
+        ]
+    );
+}
+
+/// `--validate` output of a legacy invocation, without whitespace
+fn legacy_validate(env: &[(&str, &str)]) -> (Option<i32>, String, String) {
+    let output = run(&["--validate", "/usr/bin/pacman", "-Syu"], &[], env);
+    let stdout: String = String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .collect();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    (output.status.code(), stdout, stderr)
+}
+
+#[test]
+fn legacy_invocation_has_no_timeout_by_default() {
+    let (code, stdout, stderr) = legacy_validate(&[]);
+    assert_eq!(code, Some(0));
+    assert!(
+        stdout.contains("command:\"/usr/bin/pacman-Syu\""),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("timeout_secs:None"), "{}", stdout);
+    assert!(stderr.contains("deprecated"), "{}", stderr);
+}
+
+#[test]
+fn legacy_timeout_is_parsed() {
+    for (value, expected) in [("30", "Some(30,)"), (" 45\n", "Some(45,)")] {
+        let (code, stdout, stderr) = legacy_validate(&[(LEGACY_TIMEOUT_ENV, value)]);
+        assert_eq!(code, Some(0), "{:?}", value);
+        let field = format!("timeout_secs:{}", expected);
+        assert!(stdout.contains(&field), "{:?}: {}", value, stdout);
+        assert!(stderr.contains("deprecated"), "{}", stderr);
+    }
+}
+
+#[test]
+fn invalid_legacy_timeout_is_malformed() {
+    for value in ["", "abc", "-1", "1.5", "99999999999"] {
+        let (code, _, stderr) = legacy_validate(&[(LEGACY_TIMEOUT_ENV, value)]);
+        assert_eq!(code, Some(1), "{:?}", value);
+        assert!(stderr.contains(LEGACY_TIMEOUT_ENV), "{}", stderr);
+    }
+}
@@ -0,0 +1,179 @@
+//! End-to-end tests of the spawned dialog against a headless compositor
+//!
+//! Each test starts its own headless sway, shows a dialog through
+//! `try_show_dialog_with` (so the real binary, config encoding and exit
+//! codes are exercised) and answers it with a key injected by `wtype`.
+//!
+//! Needs `sway` and `wtype` on PATH; run with
+//!
+//! ```sh
+//! cargo test --features headless-tests --test headless
+//! ```
+
+use session_dialog::{try_show_dialog_with, DialogConfig, DialogKind, DialogResult, SpawnOptions};
+use std::collections::HashMap;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Time for the lock surface to come up before a key is injected
+const RENDER_WAIT: Duration = Duration::from_millis(1500);
+
+/// A headless sway with its own runtime directory, stopped on drop
+struct Compositor {
+    child: Child,
+    runtime_dir: PathBuf,
+    display: String,
+}
+
+impl Compositor {
+    fn start(name: &str) -> Self {
+        let runtime_dir = std::env::temp_dir().join(format!(
+            "session-dialog-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&runtime_dir);
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&runtime_dir)
+            .expect("create runtime dir");
+
+        let child = Command::new("sway")
+            .args(["--config", "/dev/null"])
+            .env("XDG_RUNTIME_DIR", &runtime_dir)
+            .env("WLR_BACKENDS", "headless")
+            .env("WLR_LIBINPUT_NO_DEVICES", "1")
+            .env("WLR_RENDERER", "pixman")
+            .env_remove("WAYLAND_DISPLAY")
+            .env_remove("DISPLAY")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("start sway (is it installed?)");
+
+        let display = wait_for_socket(&runtime_dir);
+        Self {
+            child,
+            runtime_dir,
+            display,
+        }
+    }
+
+    /// Environment to pass to `show_dialog`
+    fn env(&self) -> HashMap<String, String> {
+        HashMap::from([
+            ("WAYLAND_DISPLAY".to_string(), self.display.clone()),
+            (
+                "XDG_RUNTIME_DIR".to_string(),
+                self.runtime_dir.display().to_string(),
+            ),
+        ])
+    }
+
+    /// Press and release a key (xkb name, e.g. "Return") on a virtual keyboard
+    fn press(&self, key: &str) {
+        let status = Command::new("wtype")
+            .args(["-k", key])
+            .envs(self.env())
+            .status()
+            .expect("run wtype (is it installed?)");
+        assert!(status.success(), "wtype failed: {}", status);
+    }
+
+    /// Show `config`, pressing `key` (if any) once it has had time to render
+    fn show(&self, config: DialogConfig, key: Option<&str>) -> DialogResult {
+        let env = self.env();
+        let dialog = thread::spawn(move || {
+            let options = SpawnOptions {
+                binary: Some(env!("CARGO_BIN_EXE_session-dialog").into()),
+                ..SpawnOptions::default()
+            };
+            // SAFETY: getuid/getgid have no preconditions
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            try_show_dialog_with(&config, uid, gid, &env, &options)
+        });
+
+        if let Some(key) = key {
+            thread::sleep(RENDER_WAIT);
+            self.press(key);
+        }
+        dialog
+            .join()
+            .expect("dialog thread panicked")
+            .expect("dialog failed")
+    }
+}
+
+impl Drop for Compositor {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.runtime_dir);
+    }
+}
+
+// Name of the first Wayland socket to appear in `dir`
+fn wait_for_socket(dir: &std::path::Path) -> String {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        let socket = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .find(|name| name.starts_with("wayland-") && !name.ends_with(".lock"));
+        if let Some(socket) = socket {
+            return socket;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    panic!("sway did not create a Wayland socket in {}", dir.display());
+}
+
+fn command_config(timeout_secs: Option<u32>) -> DialogConfig {
+    DialogConfig {
+        timeout_secs,
+        ..DialogConfig::new(DialogKind::PrivilegeEscalation {
+            command: "/usr/bin/true".to_string(),
+        })
+    }
+}
+
+#[test]
+fn enter_confirms() {
+    let compositor = Compositor::start("confirm");
+    let result = compositor.show(command_config(Some(20)), Some("Return"));
+    assert_eq!(result, DialogResult::Confirmed);
+}
+
+#[test]
+fn escape_denies() {
+    let compositor = Compositor::start("deny");
+    let result = compositor.show(command_config(Some(20)), Some("Escape"));
+    assert_eq!(result, DialogResult::Denied);
+}
+
+#[test]
+fn unanswered_dialog_times_out() {
+    let compositor = Compositor::start("timeout");
+    let result = compositor.show(command_config(Some(1)), None);
+    assert_eq!(result, DialogResult::Timeout);
+}
+
+#[test]
+fn enter_confirms_generic_dialog() {
+    let compositor = Compositor::start("generic");
+    let config = DialogConfig {
+        timeout_secs: Some(20),
+        ..DialogConfig::new(DialogKind::Generic {
+            title: "Headless test".to_string(),
+            message: "Press Enter:".to_string(),
+            detail: "Generic dialogs take the same keys.".into(),
+        })
+    };
+    let result = compositor.show(config, Some("Return"));
+    assert_eq!(result, DialogResult::Confirmed);
+}
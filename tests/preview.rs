@@ -0,0 +1,39 @@
+//! Smoke test of `render_preview`, which needs no compositor
+//!
+//! ```sh
+//! cargo test --features preview --test preview
+//! ```
+
+use session_dialog::{render_preview, DialogConfig, DialogKind};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+fn configs() -> Vec<DialogConfig> {
+    vec![
+        DialogConfig::new(DialogKind::PrivilegeEscalation {
+            command: "/usr/bin/pacman -Syu".to_string(),
+        }),
+        DialogConfig::new(DialogKind::Generic {
+            title: "Preview".to_string(),
+            message: "A sample request:".to_string(),
+            detail: "Rendered without a session lock.".into(),
+        }),
+    ]
+}
+
+// One test, since previews share the dialog's process-wide state
+#[test]
+fn renders_png_in_both_layouts() {
+    for config in configs() {
+        // Regular card, then the compact layout of a tiny output
+        for (width, height) in [(1280, 800), (480, 360)] {
+            let png = render_preview(&config, width, height).expect("render preview");
+            assert!(
+                png.starts_with(PNG_SIGNATURE),
+                "not a PNG at {}x{}",
+                width,
+                height
+            );
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! The dialog's structured report survives the spawn boundary
+//!
+//! Uses the binary's test mode to answer without a compositor. Kept in its
+//! own test binary since it sets process-wide environment variables.
+
+use session_dialog::{
+    show_dialog_report, DecisionSource, DialogConfig, DialogKind, DialogResult, AUTO_RESULT_ENV,
+    BINARY_ENV, TEST_MODE_ENV,
+};
+use std::collections::HashMap;
+
+#[test]
+fn request_id_round_trips_through_the_dialog() {
+    std::env::set_var(BINARY_ENV, env!("CARGO_BIN_EXE_session-dialog"));
+    std::env::set_var(TEST_MODE_ENV, "1");
+    std::env::set_var(AUTO_RESULT_ENV, "denied");
+
+    for request_id in [Some("fwd-1234"), Some("ünïcode id with spaces"), None] {
+        let mut config = DialogConfig::new(DialogKind::PrivilegeEscalation {
+            command: "/usr/bin/true".to_string(),
+        });
+        config.request_id = request_id.map(str::to_string);
+
+        // SAFETY: getuid/getgid have no preconditions
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let report = show_dialog_report(&config, uid, gid, &HashMap::new()).expect("dialog failed");
+        assert_eq!(report.request_id.as_deref(), request_id);
+        assert_eq!(report.result, DialogResult::Denied);
+        // Sent by the binary, not inferred from its exit code
+        assert_eq!(report.source, DecisionSource::Forced);
+    }
+}
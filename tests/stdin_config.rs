@@ -0,0 +1,87 @@
+//! The config reaches the dialog binary over stdin, never through argv
+//!
+//! Uses the binary's test mode to answer without a compositor, so the
+//! dialog stays up long enough to inspect `/proc/<pid>/cmdline`. Kept in
+//! its own test binary since it sets process-wide environment variables.
+
+use session_dialog::{
+    try_show_dialog_with, DialogConfig, DialogKind, DialogResult, SpawnOptions, WireFormat,
+    AUTO_RESULT_ENV, TEST_MODE_ENV,
+};
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const BINARY: &str = env!("CARGO_BIN_EXE_session-dialog");
+
+// Something to look for that only the config contains
+fn marker() -> String {
+    format!("stdin-config-marker-{}", std::process::id())
+}
+
+fn show(format: WireFormat) -> thread::JoinHandle<DialogResult> {
+    let config = DialogConfig::new(DialogKind::PrivilegeEscalation {
+        command: format!("/usr/bin/true {}", marker()),
+    });
+    thread::spawn(move || {
+        let options = SpawnOptions {
+            format,
+            binary: Some(BINARY.into()),
+            ..SpawnOptions::default()
+        };
+        // SAFETY: getuid/getgid have no preconditions
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        try_show_dialog_with(&config, uid, gid, &HashMap::new(), &options).expect("dialog failed")
+    })
+}
+
+// argv of the first running dialog binary, as NUL-separated bytes
+fn dialog_cmdline() -> Option<Vec<u8>> {
+    let binary = std::fs::canonicalize(BINARY).ok()?;
+    std::fs::read_dir("/proc")
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().parse::<u32>().is_ok())
+        .find(|entry| std::fs::read_link(entry.path().join("exe")).is_ok_and(|exe| exe == binary))
+        .and_then(|entry| std::fs::read(entry.path().join("cmdline")).ok())
+}
+
+#[test]
+fn config_is_not_in_argv() {
+    std::env::set_var(TEST_MODE_ENV, "1");
+    std::env::set_var(AUTO_RESULT_ENV, "confirmed");
+    std::env::set_var("SESSION_DIALOG_AUTO_DELAY_MS", "2000");
+
+    for format in [WireFormat::MsgPack, WireFormat::Json] {
+        let dialog = show(format);
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let cmdline = loop {
+            if let Some(cmdline) = dialog_cmdline() {
+                break cmdline;
+            }
+            assert!(Instant::now() < deadline, "dialog binary never showed up");
+            thread::sleep(Duration::from_millis(20));
+        };
+
+        let args: Vec<String> = cmdline
+            .split(|&b| b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+        assert!(args.iter().any(|a| a == "--config-stdin"), "{:?}", args);
+        assert!(
+            !args.iter().any(|a| a == "--config" || a == "--config-json"),
+            "{:?}",
+            args
+        );
+        assert!(!args.iter().any(|a| a.contains(&marker())), "{:?}", args);
+
+        // Confirmed means the binary parsed the config it read from stdin
+        assert_eq!(
+            dialog.join().unwrap(),
+            DialogResult::Confirmed,
+            "{:?}",
+            format
+        );
+    }
+}
@@ -0,0 +1,43 @@
+//! Several prompts answered through one long-lived dialog binary
+//!
+//! Uses the binary's test mode to answer without a compositor. Kept in its
+//! own test binary since it sets process-wide environment variables.
+
+use session_dialog::{
+    open_dialog_stream, DecisionSource, DialogConfig, DialogError, DialogKind, DialogResult,
+    AUTO_RESULT_ENV, BINARY_ENV, TEST_MODE_ENV,
+};
+use std::collections::HashMap;
+
+#[test]
+fn one_stream_answers_each_request() {
+    std::env::set_var(BINARY_ENV, env!("CARGO_BIN_EXE_session-dialog"));
+    std::env::set_var(TEST_MODE_ENV, "1");
+    std::env::set_var(AUTO_RESULT_ENV, "denied");
+
+    // SAFETY: getuid/getgid have no preconditions
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let mut stream = open_dialog_stream(uid, gid, &HashMap::new()).expect("open stream");
+    for request_id in ["fwd-1", "fwd-2", "fwd-3"] {
+        let mut config = DialogConfig::new(DialogKind::PrivilegeEscalation {
+            command: "/usr/bin/true".to_string(),
+        });
+        config.request_id = Some(request_id.to_string());
+
+        let report = stream.show(&config).expect("dialog failed");
+        assert_eq!(report.request_id.as_deref(), Some(request_id));
+        assert_eq!(report.result, DialogResult::Denied);
+        assert_eq!(report.source, DecisionSource::Forced);
+    }
+
+    // Refused here, and the stream carries on
+    let mut invalid = DialogConfig::new(DialogKind::PrivilegeEscalation {
+        command: " ".to_string(),
+    });
+    invalid.request_id = Some("fwd-4".to_string());
+    assert!(matches!(
+        stream.show(&invalid),
+        Err(DialogError::InvalidConfig(_))
+    ));
+    stream.close().expect("stream ended with an error");
+}